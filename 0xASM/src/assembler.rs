@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use vm::{device::SCREEN_CODES, machine::MACHINE_ADDRESSES};
+
+use crate::{
+    instructions::{
+        instruction_codes::{CALL, PUSH, PUSHR, RET},
+        instruction_to_byte,
+    },
+    parser::{parse, Error, Line, Operand, Span, Spanned, Statement},
+    stdlib::link,
+    testing::split_tests,
+    Byte, Word,
+};
+
+/// Addresses of all labels, values of all constants and argument names of all procedures of a program
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Symbols {
+    pub labels: HashMap<String, Word>,
+    pub constants: HashMap<String, Word>,
+    pub procs: HashMap<String, Vec<String>>,
+}
+
+impl Symbols {
+    /// Adds every symbol of other, replacing existing definitions
+    pub fn extend(&mut self, other: Symbols) {
+        self.labels.extend(other.labels);
+        self.constants.extend(other.constants);
+        self.procs.extend(other.procs);
+    }
+
+    /// Value of a constant, constants of the program shadow the built-in device codes like `SCREEN_CLEAR`
+    /// and addresses like `HARD_DRIVE_ADDRESS`
+    pub fn constant(&self, name: &str) -> Option<Word> {
+        self.constants.get(name).copied().or_else(|| builtin_constant(name))
+    }
+}
+
+/// Built-in constants, the codes the devices understand and the device addresses of the standard machine
+pub fn builtin_constants() -> impl Iterator<Item = &'static (&'static str, Word)> {
+    SCREEN_CODES.iter().chain(MACHINE_ADDRESSES)
+}
+
+/// Value of a built-in constant
+pub fn builtin_constant(name: &str) -> Option<Word> {
+    builtin_constants()
+        .find(|(code, _)| *code == name)
+        .map(|(_, value)| *value)
+}
+
+/// A single instruction of the image with its resolved operands
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub address: Word,
+    pub code: Byte,
+    pub operands: Vec<Word>,
+    /// Span of the mnemonic in the source
+    pub span: Span,
+}
+
+/// Result of assembling a program
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assembly {
+    /// Address the image is loaded at, set with `.org`
+    pub origin: Word,
+    pub image: Vec<Byte>,
+    pub symbols: Symbols,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Size of an assembled instruction, every operand is written as a single word
+#[inline]
+pub fn instruction_size(operand_count: usize) -> Word {
+    1 + operand_count as Word * 4
+}
+
+/// Value of a literal operand, `None` for registers and symbols
+pub fn literal_value(operand: &Operand) -> Option<Word> {
+    match operand {
+        Operand::Hex(val) | Operand::Bin(val) | Operand::Dec(val) => Some(*val),
+        Operand::Char(chars) => {
+            let mut bytes = [0; 4];
+            bytes[..chars.len()].copy_from_slice(chars.as_bytes());
+            Some(Word::from_le_bytes(bytes))
+        }
+        _ => None,
+    }
+}
+
+/// Offset of the first procedure argument from the frame pointer. Above the frame pointer
+/// `CPU::push_state` stores the stackframe size, pc and r8-r1, followed by the argument count
+/// and the arguments, which `invoke` pushes in reverse so the first one ends up on top
+pub const ARGUMENTS_OFFSET: Word = 0x30;
+
+/// Offset of the n-th procedure argument from the frame pointer
+#[inline]
+pub fn argument_offset(n: usize) -> Word {
+    ARGUMENTS_OFFSET + n as Word * 4
+}
+
+/// Size of the instructions generated for a statement
+pub fn statement_size(statement: &Statement) -> Word {
+    match statement {
+        Statement::Instruction { operands, .. } => instruction_size(operands.len()),
+        // RET
+        Statement::EndProc(_) => instruction_size(0),
+        // PUSH/PUSHR for every argument, PUSH of the argument count and CALL
+        Statement::Invoke { args, .. } => (args.len() as Word + 2) * instruction_size(1),
+        Statement::Label(_)
+        | Statement::Constant { .. }
+        | Statement::Proc { .. }
+        | Statement::Origin(_)
+        | Statement::Include(_)
+        | Statement::Test(_)
+        | Statement::EndTest(_)
+        | Statement::Expect { .. } => 0,
+    }
+}
+
+fn define_label(symbols: &mut Symbols, errors: &mut Vec<Error>, label: &Spanned<String>, address: Word) {
+    let duplicate = symbols.labels.insert(label.node.clone(), address).is_some();
+    if duplicate {
+        errors.push(Error::new(label.span, format!("Duplicate label: {}", label.node)));
+    }
+}
+
+/// Collects the address of every label and procedure and the value of every constant,
+/// reporting duplicate definitions, invalid constant values, misplaced origins and unbalanced procedures
+pub fn collect_symbols(lines: &[Line]) -> (Symbols, Vec<Error>) {
+    let mut symbols = Symbols::default();
+    let mut errors = Vec::new();
+    let mut address: Word = 0;
+    let mut has_code = false;
+    let mut has_origin = false;
+    let mut open_proc: Option<&Spanned<String>> = None;
+
+    for line in lines {
+        let statement = match &line.statement {
+            Some(statement) => statement,
+            None => continue,
+        };
+
+        match statement {
+            Statement::Label(label) => define_label(&mut symbols, &mut errors, label, address),
+            Statement::Constant { name, value } => match literal_value(&value.node) {
+                Some(val) => {
+                    if symbols.constants.insert(name.node.clone(), val).is_some() {
+                        errors.push(Error::new(name.span, format!("Duplicate constant: {}", name.node)));
+                    }
+                }
+                None => errors.push(Error::new(
+                    value.span,
+                    format!("Constant value of {} must be a number or char literal", name.node),
+                )),
+            },
+            Statement::Proc { name, args } => {
+                if let Some(outer) = open_proc {
+                    errors.push(Error::new(
+                        name.span,
+                        format!("Procedure {} can't be nested in {}", name.node, outer.node),
+                    ));
+                }
+                open_proc = Some(name);
+
+                define_label(&mut symbols, &mut errors, name, address);
+                for (i, arg) in args.iter().enumerate() {
+                    if args[..i].iter().any(|other| other.node == arg.node) {
+                        errors.push(Error::new(arg.span, format!("Duplicate argument: {}", arg.node)));
+                    }
+                }
+                symbols
+                    .procs
+                    .insert(name.node.clone(), args.iter().map(|arg| arg.node.clone()).collect());
+            }
+            Statement::EndProc(span) => {
+                if open_proc.take().is_none() {
+                    errors.push(Error::new(*span, ".endp without .proc".to_string()));
+                }
+            }
+            Statement::Origin(value) => match literal_value(&value.node) {
+                Some(_) if has_code => {
+                    errors.push(Error::new(value.span, ".org must come before any code".to_string()))
+                }
+                Some(_) if has_origin => errors.push(Error::new(value.span, "Duplicate .org".to_string())),
+                Some(val) => {
+                    address = val;
+                    has_origin = true;
+                }
+                None => errors.push(Error::new(value.span, ".org address must be a number".to_string())),
+            },
+            Statement::Instruction { .. }
+            | Statement::Invoke { .. }
+            | Statement::Include(_)
+            | Statement::Test(_)
+            | Statement::EndTest(_)
+            | Statement::Expect { .. } => {}
+        }
+
+        has_code |= !matches!(
+            statement,
+            Statement::Constant { .. }
+                | Statement::Origin(_)
+                | Statement::Include(_)
+                | Statement::Test(_)
+                | Statement::EndTest(_)
+                | Statement::Expect { .. }
+        );
+        // the address behind the program has to fit into a word as well
+        match address.checked_add(statement_size(statement)) {
+            Some(next) => address = next,
+            None => {
+                errors.push(Error::new(
+                    statement.span(),
+                    "Program runs past the end of the address space".to_string(),
+                ));
+                break;
+            }
+        }
+    }
+
+    if let Some(name) = open_proc {
+        errors.push(Error::new(name.span, format!("Missing .endp for procedure {}", name.node)));
+    }
+
+    (symbols, errors)
+}
+
+/// Resolves an operand, `arguments` are the argument names of the enclosing procedure
+pub fn operand_value(operand: &Spanned<Operand>, symbols: &Symbols, arguments: &[String]) -> Result<Word, Error> {
+    match &operand.node {
+        Operand::Register(addr) => Ok(*addr),
+        Operand::Label(label) => symbols
+            .labels
+            .get(label)
+            .copied()
+            .ok_or_else(|| Error::new(operand.span, format!("Unknown label: {}", label))),
+        Operand::Constant(constant) => match arguments.iter().position(|arg| arg == constant) {
+            Some(n) => Ok(argument_offset(n)),
+            None => symbols
+                .constant(constant)
+                .ok_or_else(|| Error::new(operand.span, format!("Unknown constant: {}", constant))),
+        },
+        literal => Ok(literal_value(literal).unwrap()),
+    }
+}
+
+/// Image and instruction listing built up while assembling
+#[derive(Default)]
+struct Output {
+    origin: Word,
+    image: Vec<Byte>,
+    instructions: Vec<Instruction>,
+}
+
+impl Output {
+    fn emit(&mut self, code: Byte, operands: Vec<Word>, span: Span) {
+        // a program running past the end of the address space was already reported by collect_symbols
+        let address = self.origin.wrapping_add(self.image.len() as Word);
+
+        self.image.push(code);
+        for operand in &operands {
+            self.image.extend_from_slice(&operand.to_le_bytes());
+        }
+
+        self.instructions.push(Instruction {
+            address,
+            code,
+            operands,
+            span,
+        });
+    }
+}
+
+/// Assembles parsed lines and the modules they include into a binary image,
+/// collecting every error instead of stopping at the first
+pub fn assemble(lines: &[Line]) -> Result<Assembly, Vec<Error>> {
+    assemble_with(lines, &Symbols::default())
+}
+
+/// Assembles like `assemble`, resolving symbols the lines don't define themselves from `known`.
+/// The symbols of the result include the known ones
+pub fn assemble_with(lines: &[Line], known: &Symbols) -> Result<Assembly, Vec<Error>> {
+    let (lines, mut errors) = link(lines);
+    let (lines, _, test_errors) = split_tests(&lines);
+    errors.extend(test_errors);
+    let (defined, symbol_errors) = collect_symbols(&lines);
+    errors.extend(symbol_errors);
+
+    let mut symbols = known.clone();
+    symbols.extend(defined);
+
+    let mut output = Output::default();
+    let mut arguments: &[String] = &[];
+
+    for line in &lines {
+        match &line.statement {
+            Some(Statement::Instruction { mnemonic, operands }) => {
+                let (code, operand_count) = match instruction_to_byte(&mnemonic.node) {
+                    Some(instruction_code) => instruction_code,
+                    None => {
+                        errors.push(Error::new(mnemonic.span, format!("Unknown instruction: {}", mnemonic.node)));
+                        continue;
+                    }
+                };
+
+                if operands.len() != operand_count {
+                    errors.push(Error::new(
+                        mnemonic.span,
+                        format!(
+                            "Wrong number of operands for instruction {}, expected {} but got {}",
+                            mnemonic.node,
+                            operand_count,
+                            operands.len()
+                        ),
+                    ));
+                }
+
+                // unresolved operands are written as 0 to keep the addresses intact
+                let values = operands
+                    .iter()
+                    .map(|operand| {
+                        operand_value(operand, &symbols, arguments).unwrap_or_else(|err| {
+                            errors.push(err);
+                            0
+                        })
+                    })
+                    .collect();
+
+                output.emit(code, values, mnemonic.span);
+            }
+            Some(Statement::Proc { name, .. }) => arguments = &symbols.procs[&name.node],
+            Some(Statement::EndProc(span)) => {
+                arguments = &[];
+                output.emit(RET.0, Vec::new(), *span);
+            }
+            Some(Statement::Invoke { mnemonic, target, args }) => {
+                if let Some(proc_args) = symbols.procs.get(&target.node) {
+                    if proc_args.len() != args.len() {
+                        errors.push(Error::new(
+                            mnemonic.span,
+                            format!(
+                                "Procedure {} expects {} argument(s) but got {}",
+                                target.node,
+                                proc_args.len(),
+                                args.len()
+                            ),
+                        ));
+                    }
+                }
+
+                // pushed in reverse so the first argument is closest to the frame
+                for arg in args.iter().rev() {
+                    let val = operand_value(arg, &symbols, arguments).unwrap_or_else(|err| {
+                        errors.push(err);
+                        0
+                    });
+
+                    match arg.node {
+                        Operand::Register(_) => output.emit(PUSHR.0, vec![val], mnemonic.span),
+                        _ => output.emit(PUSH.0, vec![val], mnemonic.span),
+                    }
+                }
+                output.emit(PUSH.0, vec![args.len() as Word], mnemonic.span);
+
+                match symbols.labels.get(&target.node) {
+                    Some(addr) => output.emit(CALL.0, vec![*addr], mnemonic.span),
+                    None => {
+                        errors.push(Error::new(target.span, format!("Unknown procedure: {}", target.node)));
+                        output.emit(CALL.0, vec![0], mnemonic.span);
+                    }
+                }
+            }
+            // collect_symbols reports a misplaced or invalid origin
+            Some(Statement::Origin(value)) => output.origin = literal_value(&value.node).unwrap_or(0),
+            // test blocks were taken out by split_tests
+            Some(Statement::Label(_))
+            | Some(Statement::Constant { .. })
+            | Some(Statement::Include(_))
+            | Some(Statement::Test(_))
+            | Some(Statement::EndTest(_))
+            | Some(Statement::Expect { .. })
+            | None => {}
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Assembly {
+            origin: output.origin,
+            image: output.image,
+            symbols,
+            instructions: output.instructions,
+        })
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parses and assembles a whole source file
+pub fn assemble_source(source: &str) -> Result<Assembly, Vec<Error>> {
+    let (lines, mut errors) = parse(source);
+
+    match assemble(&lines) {
+        Ok(assembly) if errors.is_empty() => Ok(assembly),
+        Ok(_) => Err(errors),
+        Err(assemble_errors) => {
+            errors.extend(assemble_errors);
+            errors.sort_by_key(|err| (err.span.line, err.span.start));
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    parser::{parse, Error, Line, Operand, Statement},
+    registers::register_name,
+};
+
+/// Indentation of instructions, labels and directives start at column 0
+pub const INDENT: usize = 4;
+/// Width of the mnemonic column, the longest mnemonic has 7 characters
+pub const MNEMONIC_WIDTH: usize = 8;
+/// Column trailing comments are aligned to, unless the code is longer
+pub const COMMENT_COLUMN: usize = 40;
+
+/// Canonical representation of an operand: uppercase hex digits, lowercase registers
+pub fn format_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Hex(val) => format!("0x{:X}", val),
+        Operand::Bin(val) => format!("0b{:b}", val),
+        Operand::Dec(val) => format!("{}", val),
+        Operand::Char(chars) => format!("'{}'", chars),
+        Operand::Register(addr) => register_name(*addr).unwrap_or("r?").to_string(),
+        Operand::Label(label) => format!(":{}", label),
+        Operand::Constant(constant) => constant.clone(),
+    }
+}
+
+/// Indented mnemonic followed by its operands in the operand column
+fn format_instruction(mnemonic: &str, operands: &[String]) -> String {
+    let line = format!(
+        "{:indent$}{:<width$}{}",
+        "",
+        mnemonic.to_uppercase(),
+        operands.join(", "),
+        indent = INDENT,
+        width = MNEMONIC_WIDTH
+    );
+
+    line.trim_end().to_string()
+}
+
+fn format_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Label(label) => format!(":{}", label.node),
+        Statement::Constant { name, value } => {
+            format!(".const {}, {}", name.node, format_operand(&value.node))
+        }
+        Statement::Proc { name, args } => {
+            let args: Vec<&str> = args.iter().map(|arg| arg.node.as_str()).collect();
+            format!(".proc {}({})", name.node, args.join(", "))
+        }
+        Statement::EndProc(_) => ".endp".to_string(),
+        Statement::Origin(address) => format!(".org {}", format_operand(&address.node)),
+        Statement::Include(path) => format!(".include \"{}\"", path.node),
+        Statement::Test(name) => format!(".test \"{}\"", name.node),
+        Statement::EndTest(_) => ".endt".to_string(),
+        Statement::Expect { target, memory, value } => {
+            let target = format_operand(&target.node);
+            let target = if *memory { format!("[{}]", target) } else { target };
+            format!("{:indent$}.expect {} == {}", "", target, format_operand(&value.node), indent = INDENT)
+        }
+        Statement::Instruction { mnemonic, operands } => {
+            let operands: Vec<String> = operands
+                .iter()
+                .map(|operand| format_operand(&operand.node))
+                .collect();
+
+            format_instruction(&mnemonic.node, &operands)
+        }
+        Statement::Invoke { mnemonic, target, args } => {
+            let operands: Vec<String> = std::iter::once(target.node.clone())
+                .chain(args.iter().map(|arg| format_operand(&arg.node)))
+                .collect();
+
+            format_instruction(&mnemonic.node, &operands)
+        }
+    }
+}
+
+fn format_line(line: &Line, indent_comment: bool) -> String {
+    let comment = line.comment.as_ref().map(|comment| {
+        let text = comment.node.trim();
+        if text.is_empty() {
+            ";".to_string()
+        } else {
+            format!("; {}", text)
+        }
+    });
+
+    match (&line.statement, comment) {
+        (Some(statement), Some(comment)) => {
+            let code = format_statement(statement);
+            let padding = COMMENT_COLUMN.saturating_sub(code.len()).max(1);
+            format!("{}{:padding$}{}", code, "", comment, padding = padding)
+        }
+        (Some(statement), None) => format_statement(statement),
+        (None, Some(comment)) if indent_comment => format!("{:indent$}{}", "", comment, indent = INDENT),
+        (None, Some(comment)) => comment,
+        (None, None) => String::new(),
+    }
+}
+
+/// Formats a whole source file, refusing to format sources with syntax errors
+/// since invalid lines can't be re-emitted without losing their content.
+///
+/// Comment-only lines stay at column 0 or are indented like instructions,
+/// runs of empty lines are collapsed into one and the result ends in a single newline.
+pub fn format_source(source: &str) -> Result<String, Vec<Error>> {
+    let (lines, errors) = parse(source);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut output = String::new();
+    let mut previous_empty = true;
+
+    for (line, text) in lines.iter().zip(source.lines()) {
+        let indent_comment = line.statement.is_none() && !text.starts_with(';');
+        let formatted = format_line(line, indent_comment);
+
+        if formatted.is_empty() {
+            if previous_empty {
+                continue;
+            }
+            previous_empty = true;
+        } else {
+            previous_empty = false;
+        }
+
+        output.push_str(&formatted);
+        output.push('\n');
+    }
+
+    // drop a trailing empty line
+    if output.ends_with("\n\n") {
+        output.pop();
+    }
+
+    Ok(output)
+}
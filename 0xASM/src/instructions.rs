@@ -32,6 +32,12 @@ pub mod instruction_codes {
     pub const CALLR: (Byte, usize) = (0x03, 1);
     pub const RET: (Byte, usize) = (0x04, 0);
 
+    // Interrupts
+    pub const INT: (Byte, usize) = (0x06, 1);
+    pub const IRET: (Byte, usize) = (0x07, 0);
+    pub const EI: (Byte, usize) = (0x08, 0);
+    pub const DI: (Byte, usize) = (0x09, 0);
+
     // Arithmetic
     pub const ADD: (Byte, usize) = (0x20, 2);
     pub const ADDR: (Byte, usize) = (0x21, 2);
@@ -92,91 +98,129 @@ pub mod instruction_codes {
     pub const BRGTERR: (Byte, usize) = (0x49, 3);
 }
 
+/// Mnemonic, opcode, operand count and documentation of a single instruction
+pub struct InstructionInfo {
+    pub name: &'static str,
+    pub code: (Byte, usize),
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+macro_rules! instruction {
+    ($name:ident, $signature:literal, $description:literal) => {
+        InstructionInfo {
+            name: stringify!($name),
+            code: instruction_codes::$name,
+            signature: $signature,
+            description: $description,
+        }
+    };
+}
+
+/// All instructions understood by the 0xVM, in the same order as in `instruction_codes`
+pub const INSTRUCTION_SET: &[InstructionInfo] = &[
+    instruction!(HALT, "HALT", "Halt the cpu"),
+    instruction!(NOP, "NOP", "Do nothing"),
+
+    // MOV
+    instruction!(MOVR, "MOVR 0x1234, r1", "Move 0x1234 into register r1"),
+    instruction!(MOVM, "MOVM 0x1234, 0xAF", "Move 0x1234 into memory at 0xAF"),
+    instruction!(MOVRR, "MOVRR r1, r2", "Move register r1 into register r2"),
+    instruction!(MOVRM, "MOVRM r1, 0xAF", "Move register r1 into memory at 0xAF"),
+    instruction!(MOVMR, "MOVMR 0xAF, r1", "Move memory at 0xAF into register r1"),
+    instruction!(MOVRPR, "MOVRPR r1, r2", "Move data pointed at by register r1 into register r2"),
+    instruction!(MOVROR, "MOVROR r1, 0x2, r2", "Move data pointed at by register r1 plus an offset 0x2 into register r2"),
+    instruction!(LOAD, "LOAD R1, R2, 0x1238", "Load R2 bytes from device at R1* to memory at 0x1238-0x1238 + R2"),
+    instruction!(LOADR, "LOADR R1, R2, R3", "Load R2 bytes from device at R1* to memory at R3*-R3* + R2"),
+    instruction!(LOADM, "LOADM R1, R2, 0x1238", "Load R2 bytes from device at R1* to memory at 0x1238*-0x1238* + R2"),
+    instruction!(STORE, "STORE 0x1238, R2, R1", "Store R2 bytes from memory at 0x1238-0x1238 + R2 to device at R1*"),
+    instruction!(STORER, "STORER R3, R2, R1", "Store R2 bytes from memory at R3*-R3* + R2 to device at R1*"),
+    instruction!(STOREM, "STOREM 0x1238, R2, R1", "Store R2 bytes from memory at 0x1238*-0x1238* + R2 to device at R1*"),
+
+    // Stack
+    instruction!(POP, "POP r1", "Pop val from stack into register r1"),
+    instruction!(PUSH, "PUSH 0x1234", "Push 0x1234 onto the stack"),
+    instruction!(PUSHR, "PUSHR r1", "Push register r1 onto stack"),
+
+    // Subroutines
+    instruction!(JMP, "JMP 0xAF", "Jumps to addr 0xAF"),
+    instruction!(CALL, "CALL 0xAF", "Call subroutine at 0xAF"),
+    instruction!(CALLR, "CALLR r1", "Call subroutine at r1"),
+    instruction!(RET, "RET", "Return from subroutine"),
+
+    // Interrupts
+    instruction!(INT, "INT 0x3", "Enter the handler of interrupt vector 0x3"),
+    instruction!(IRET, "IRET", "Return from interrupt handler and restore sr"),
+    instruction!(EI, "EI", "Enable interrupts"),
+    instruction!(DI, "DI", "Disable interrupts"),
+
+    // Arithmetic
+    instruction!(ADD, "ADD 0x1234, r1", "Add 0x1234 to register r1 and store the result in acc"),
+    instruction!(ADDR, "ADDR r1, r2", "Add register r1 and register r2 and store the result in acc"),
+    instruction!(SUB, "SUB r1, 0x1234", "Subtract 0x1234 from register r1 and store the result in acc"),
+    instruction!(SUBWR, "SUBWR 0x1234, r1", "Subtract register r1 from 0x1234 and store the result in acc"),
+    instruction!(SUBR, "SUBR r1, r2", "Subtract register r2 from register r1 and store the result in acc"),
+    instruction!(MULT, "MULT 0x1234, r1", "Multiply register r1 by 0x1234 and store the result in acc"),
+    instruction!(MULTR, "MULTR r1, r2", "Multiply register r2 by register r1 and store the result in acc"),
+    instruction!(DIV, "DIV r1, 0x1234", "Divide register r1 by 0x1234 and store the result in acc"),
+    instruction!(DIVWR, "DIVWR 0x1234, r1", "Divide 0x1234 by register r1 and store the result in acc"),
+    instruction!(DIVR, "DIVR r1, r2", "Divide register r2 by register r1 and store the result in acc"),
+    instruction!(INC, "INC r1", "Increment register r1 and store the result in acc"),
+    instruction!(DEC, "DEC r1", "Decrement register r1 and store the result in acc"),
+
+    // Bitwise
+    instruction!(LSF, "LSF r1, 0x4", "Shift register r1 left by 0x4"),
+    instruction!(LSFR, "LSFR r1, r2", "Shift register r1 left by register r2"),
+    instruction!(RSF, "RSF r1, 0x4", "Shift register r1 right by 0x4"),
+    instruction!(RSFR, "RSFR r1, r2", "Shift register r1 right by register r2"),
+    instruction!(WLSF, "WLSF r1, 0x4", "Shift register r1 left by 0x4 wrapping around"),
+    instruction!(WLSFR, "WLSFR r1, r2", "Shift register r1 left by register r2 wrapping around"),
+    instruction!(WRSF, "WRSF r1, 0x4", "Shift register r1 right by 0x4 wrapping around"),
+    instruction!(WRSFR, "WRSFR r1, r2", "Shift register r1 right by register r2 wrapping around"),
+    instruction!(AND, "AND r1, 0x4", "Bitwise AND register r1 with 0x4"),
+    instruction!(ANDR, "ANDR r1, r2", "Bitwise AND register r1 with register r2"),
+    instruction!(OR, "OR r1, 0x4", "Bitwise OR register r1 with 0x4"),
+    instruction!(ORR, "ORR r1, r2", "Bitwise OR register r1 with register r2"),
+    instruction!(XOR, "XOR r1, 0x4", "Bitwise XOR register r1 with 0x4"),
+    instruction!(XORR, "XORR r1, r2", "Bitwise XOR register r1 with register r2"),
+    instruction!(NOT, "NOT r1", "Bitwise NOT register r1"),
+
+    // Conditional jumps
+    instruction!(BRBS, "BRBS FLAG_Z, 0xAF", "If the flag Z is set, jump to 0xAF"),
+    instruction!(BRBC, "BRBC FLAG_Z, 0xAF", "If the flag Z is clear, jump to 0xAF"),
+    instruction!(BREQ, "BREQ 0x1234, 0x5", "Jump to 0x5 if acc does equal 0x1234"),
+    instruction!(BREQR, "BREQR r1, 0x5", "Jump to 0x5 if acc does equal register r1"),
+    instruction!(BREQRW, "BREQRW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 does equal 0x1234"),
+    instruction!(BREQRR, "BREQRR r1, r2, 0x5", "Jump to 0x5 if register r1 does equal register r2"),
+    instruction!(BRNQ, "BRNQ 0x1234, 0x5", "Jump to 0x5 if acc does not equal 0x1234"),
+    instruction!(BRNQR, "BRNQR r1, 0x5", "Jump to 0x5 if acc does not equal register r1"),
+    instruction!(BRNQRW, "BRNQRW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 does not equal 0x1234"),
+    instruction!(BRNQRR, "BRNQRR r1, r2, 0x5", "Jump to 0x5 if register r1 does not equal register r2"),
+    instruction!(BRLT, "BRLT 0x1234, 0x5", "Jump to 0x5 if acc is less than 0x1234"),
+    instruction!(BRLTR, "BRLTR r1, 0x5", "Jump to 0x5 if acc is less than register r1"),
+    instruction!(BRLTRW, "BRLTRW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 is less than 0x1234"),
+    instruction!(BRLTRR, "BRLTRR r1, r2, 0x5", "Jump to 0x5 if register r1 is less than register r2"),
+    instruction!(BRGT, "BRGT 0x1234, 0x5", "Jump to 0x5 if acc is greater than 0x1234"),
+    instruction!(BRGTR, "BRGTR r1, 0x5", "Jump to 0x5 if acc is greater than register r1"),
+    instruction!(BRGTRW, "BRGTRW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 is greater than 0x1234"),
+    instruction!(BRGTRR, "BRGTRR r1, r2, 0x5", "Jump to 0x5 if register r1 is greater than register r2"),
+    instruction!(BRLTE, "BRLTE 0x1234, 0x5", "Jump to 0x5 if acc is less than or equal 0x1234"),
+    instruction!(BRLTER, "BRLTER r1, 0x5", "Jump to 0x5 if acc is less than or equal register r1"),
+    instruction!(BRLTERW, "BRLTERW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 is less than or equal 0x1234"),
+    instruction!(BRLTERR, "BRLTERR r1, r2, 0x5", "Jump to 0x5 if register r1 is less than or equal register r2"),
+    instruction!(BRGTE, "BRGTE 0x1234, 0x5", "Jump to 0x5 if acc is greater than or equal 0x1234"),
+    instruction!(BRGTER, "BRGTER r1, 0x5", "Jump to 0x5 if acc is greater than or equal register r1"),
+    instruction!(BRGTERW, "BRGTERW r1, 0x1234, 0x5", "Jump to 0x5 if register r1 is greater than or equal 0x1234"),
+    instruction!(BRGTERR, "BRGTERR r1, r2, 0x5", "Jump to 0x5 if register r1 is greater than or equal register r2"),
+];
+
+/// Looks up the instruction table entry of a mnemonic, ignoring case
+pub fn instruction_info(i: &str) -> Option<&'static InstructionInfo> {
+    INSTRUCTION_SET
+        .iter()
+        .find(|info| info.name.eq_ignore_ascii_case(i))
+}
+
 pub fn instruction_to_byte(i: &str) -> Option<(Byte, usize)> {
-    use instruction_codes::*;
-
-    match i.to_uppercase().as_str() {
-        "HALT" => Some(HALT),
-        "NOP" => Some(NOP),
-
-        "MOVR" => Some(MOVR),
-        "MOVM" => Some(MOVM),
-        "MOVRR" => Some(MOVRR),
-        "MOVRM" => Some(MOVRM),
-        "MOVMR" => Some(MOVMR),
-        "MOVRPR" => Some(MOVRPR),
-        "MOVROR" => Some(MOVROR),
-        "LOAD" => Some(LOAD),
-        "LOADR" => Some(LOADR),
-        "LOADM" => Some(LOADM),
-        "STORE" => Some(STORE),
-        "STORER" => Some(STORER),
-        "STOREM" => Some(STOREM),
-
-        "POP" => Some(POP),
-        "PUSH" => Some(PUSH),
-        "PUSHR" => Some(PUSHR),
-
-        "JMP" => Some(JMP),
-        "CALL" => Some(CALL),
-        "CALLR" => Some(CALLR),
-        "RET" => Some(RET),
-
-        "ADD" => Some(ADD),
-        "ADDR" => Some(ADDR),
-        "SUB" => Some(SUB),
-        "SUBWR" => Some(SUBWR),
-        "SUBR" => Some(SUBR),
-        "MULT" => Some(MULT),
-        "MULTR" => Some(MULTR),
-        "DIV" => Some(DIV),
-        "DIVWR" => Some(DIVWR),
-        "DIVR" => Some(DIVR),
-        "INC" => Some(INC),
-        "DEC" => Some(DEC),
-
-        "LSF" => Some(LSF),
-        "LSFR" => Some(LSFR),
-        "RSF" => Some(RSF),
-        "RSFR" => Some(RSFR),
-        "WLSF" => Some(WLSF),
-        "WLSFR" => Some(WLSFR),
-        "WRSF" => Some(WRSF),
-        "WRSFR" => Some(WRSFR),
-        "AND" => Some(AND),
-        "ANDR" => Some(ANDR),
-        "OR" => Some(OR),
-        "ORR" => Some(ORR),
-        "XOR" => Some(XOR),
-        "XORR" => Some(XORR),
-        "NOT" => Some(NOT),
-
-        "BRBS" => Some(BRBS),
-        "BRBC" => Some(BRBC),
-        "BREQ" => Some(BREQ),
-        "BREQR" => Some(BREQR),
-        "BREQRW" => Some(BREQRW),
-        "BREQRR" => Some(BREQRR),
-        "BRNQ" => Some(BRNQ),
-        "BRNQR" => Some(BRNQR),
-        "BRNQRW" => Some(BRNQRW),
-        "BRNQRR" => Some(BRNQRR),
-        "BRLT" => Some(BRLT),
-        "BRLTR" => Some(BRLTR),
-        "BRLTRW" => Some(BRLTRW),
-        "BRLTRR" => Some(BRLTRR),
-        "BRGT" => Some(BRGT),
-        "BRGTR" => Some(BRGTR),
-        "BRGTRW" => Some(BRGTRW),
-        "BRGTRR" => Some(BRGTRR),
-        "BRLTE" => Some(BRLTE),
-        "BRLTER" => Some(BRLTER),
-        "BRLTERW" => Some(BRLTERW),
-        "BRLTERR" => Some(BRLTERR),
-        "BRGTE" => Some(BRGTE),
-        "BRGTER" => Some(BRGTER),
-        "BRGTERW" => Some(BRGTERW),
-        "BRGTERR" => Some(BRGTERR),
-        _ => None,
-    }
-}
\ No newline at end of file
+    instruction_info(i).map(|info| info.code)
+}
@@ -0,0 +1,14 @@
+pub type Byte = u8;
+pub type Word = u32;
+
+pub mod assembler;
+pub mod formatter;
+pub mod instructions;
+pub mod lint;
+pub mod lsp;
+pub mod parser;
+pub mod registers;
+pub mod repl;
+pub mod stdlib;
+pub mod testing;
+pub mod xref;
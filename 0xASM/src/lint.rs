@@ -0,0 +1,363 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use vm::{
+    device::{
+        DMA_REGISTERS, HARD_DRIVE_REGISTERS, INTERRUPT_CONTROLLER_REGISTERS, KEYBOARD_REGISTERS, UART_REGISTERS,
+    },
+    machine::{
+        DMA_ADDRESS, HARD_DRIVE_ADDRESS, INTERRUPT_CONTROLLER_ADDRESS, KEYBOARD_ADDRESS, SCREEN_ADDRESS, SCREEN_END,
+        UART_ADDRESS,
+    },
+};
+
+use crate::{
+    assembler::{assemble, Assembly, Instruction},
+    instructions::{instruction_codes::*, instruction_to_byte},
+    parser::{parse, Error, Line, Operand, Span, Spanned, Statement},
+    testing::split_tests,
+    Word,
+};
+
+/// Devices of the default 0xVM memory map: name, mapped range and whether they accept range writes
+const DEVICES: &[(&str, Word, Word, bool)] = &[
+    ("Screen", SCREEN_ADDRESS, SCREEN_END, false),
+    (
+        "InterruptController",
+        INTERRUPT_CONTROLLER_ADDRESS,
+        INTERRUPT_CONTROLLER_ADDRESS + INTERRUPT_CONTROLLER_REGISTERS,
+        false,
+    ),
+    ("Keyboard", KEYBOARD_ADDRESS, KEYBOARD_ADDRESS + KEYBOARD_REGISTERS, false),
+    ("Uart", UART_ADDRESS, UART_ADDRESS + UART_REGISTERS, false),
+    ("HardDrive", HARD_DRIVE_ADDRESS, HARD_DRIVE_ADDRESS + HARD_DRIVE_REGISTERS, true),
+    ("Dma", DMA_ADDRESS, DMA_ADDRESS + DMA_REGISTERS, false),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Warning at line {}: {}", self.span.line + 1, self.message)
+    }
+}
+
+#[inline]
+fn is_branch(code: u8) -> bool {
+    (BRBS.0..=BRGTERR.0).contains(&code)
+}
+
+/// Checks if the operand at index is a jump or call target of the instruction
+fn is_target(code: u8, index: usize, operand_count: usize) -> bool {
+    ((code == JMP.0 || code == CALL.0) && index == 0) || (is_branch(code) && index + 1 == operand_count)
+}
+
+/// Control-flow graph of an assembled program, nodes are instruction addresses
+pub(crate) struct Program<'a> {
+    instructions: HashMap<Word, &'a Instruction>,
+    end: Word,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Program<'a> {
+    pub(crate) fn new(assembly: &'a Assembly) -> Self {
+        Program {
+            instructions: assembly
+                .instructions
+                .iter()
+                .map(|instr| (instr.address, instr))
+                .collect(),
+            end: assembly.origin + assembly.image.len() as Word,
+            lints: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, span: Span, message: String) {
+        self.lints.push(Lint { span, message });
+    }
+
+    /// Addresses execution can continue at after the instruction, calls fall through
+    fn successors(&self, instr: &Instruction) -> Vec<Word> {
+        let next = instr.address + 1 + instr.operands.len() as Word * 4;
+
+        if instr.code == HALT.0 || instr.code == RET.0 || instr.code == IRET.0 {
+            vec![]
+        } else if instr.code == JMP.0 {
+            vec![instr.operands[0]]
+        } else if is_branch(instr.code) {
+            vec![next, *instr.operands.last().unwrap()]
+        } else {
+            vec![next]
+        }
+    }
+
+    /// Visits every instruction of the routine starting at start without entering called routines
+    pub(crate) fn routine(&mut self, start: Word) -> Vec<&'a Instruction> {
+        let mut visited = HashSet::new();
+        let mut routine = Vec::new();
+        let mut queue = vec![start];
+
+        while let Some(addr) = queue.pop() {
+            if !visited.insert(addr) {
+                continue;
+            }
+
+            let instr = match self.instructions.get(&addr) {
+                Some(instr) => *instr,
+                None => continue,
+            };
+            routine.push(instr);
+
+            for successor in self.successors(instr) {
+                if successor >= self.end && !self.instructions.contains_key(&successor) {
+                    self.warn(instr.span, "Execution runs past the end of the program".to_string());
+                }
+                queue.push(successor);
+            }
+        }
+
+        routine
+    }
+
+    /// Tracks the values pushed within the routine, `None` for values not known at assembly time.
+    /// The caller pushes the arguments plus their count before `CALL`, which `RET` pops again
+    fn check_stack(&mut self, start: Word, is_subroutine: bool) {
+        let mut depths: HashMap<Word, usize> = HashMap::new();
+        let mut reported = HashSet::new();
+        let mut queue: Vec<(Word, Vec<Option<Word>>)> = vec![(start, Vec::new())];
+
+        while let Some((addr, mut stack)) = queue.pop() {
+            let instr = match self.instructions.get(&addr) {
+                Some(instr) => *instr,
+                None => continue,
+            };
+
+            if let Some(depth) = depths.get(&addr) {
+                if *depth != stack.len() && reported.insert(addr) {
+                    self.warn(
+                        instr.span,
+                        format!(
+                            "Push/pop imbalance, reached with {} and {} value(s) on the stack",
+                            depth,
+                            stack.len()
+                        ),
+                    );
+                }
+                continue;
+            }
+            depths.insert(addr, stack.len());
+
+            if instr.code == PUSH.0 {
+                stack.push(Some(instr.operands[0]));
+            } else if instr.code == PUSHR.0 {
+                stack.push(None);
+            } else if instr.code == POP.0 && stack.pop().is_none() {
+                self.warn(instr.span, "POP from an empty stack frame".to_string());
+            } else if instr.code == CALL.0 || instr.code == CALLR.0 {
+                match stack.pop() {
+                    Some(Some(count)) => {
+                        let count = count as usize;
+                        if stack.len() < count {
+                            self.warn(
+                                instr.span,
+                                format!(
+                                    "Call pops {} arguments but only {} value(s) are on the stack",
+                                    count,
+                                    stack.len()
+                                ),
+                            );
+                        }
+                        stack.truncate(stack.len().saturating_sub(count));
+                    }
+                    // argument count unknown, the stack can't be tracked any further
+                    Some(None) => continue,
+                    None => {
+                        self.warn(instr.span, "Call without an argument count on the stack".to_string());
+                    }
+                }
+            } else if instr.code == RET.0 && is_subroutine && !stack.is_empty() {
+                self.warn(instr.span, format!("RET with {} value(s) left on the stack", stack.len()));
+            }
+
+            for successor in self.successors(instr) {
+                queue.push((successor, stack.clone()));
+            }
+        }
+    }
+
+    /// Word writes must fit into the device range, range writes need a device supporting them
+    fn check_device_writes(&mut self, instructions: &[Instruction]) {
+        for instr in instructions {
+            let (addr, range_write) = if instr.code == MOVM.0 || instr.code == MOVRM.0 {
+                (instr.operands[1], false)
+            } else if instr.code == LOAD.0 {
+                (instr.operands[2], true)
+            } else {
+                continue;
+            };
+
+            let device = DEVICES
+                .iter()
+                .find(|(_, start, end, _)| *start <= addr && addr < *end);
+
+            match device {
+                Some((name, _, _, false)) if range_write => self.warn(
+                    instr.span,
+                    format!("Range write to 0x{:08X}, the {} only supports word writes", addr, name),
+                ),
+                Some((name, _, end, _)) if !range_write && addr + 4 > *end => self.warn(
+                    instr.span,
+                    format!("Word write to 0x{:08X} crosses the end of the {} range", addr, name),
+                ),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs every lint over the parsed lines and their assembly
+pub fn lint(lines: &[Line], assembly: &Assembly) -> Vec<Lint> {
+    let mut program = Program::new(assembly);
+    let entry = match assembly.instructions.first() {
+        Some(instr) => instr.address,
+        None => return Vec::new(),
+    };
+
+    // labels used as data instead of jump targets may be called through CALLR
+    let mut referenced = HashSet::new();
+    let mut roots = vec![entry];
+
+    // routines exercised by tests are referenced and reachable
+    let (lines, tests, _) = split_tests(lines);
+    for line in tests.iter().flat_map(|test| &test.lines) {
+        let operands: Vec<&Spanned<Operand>> = match &line.statement {
+            Some(Statement::Instruction { operands, .. }) => operands.iter().collect(),
+            Some(Statement::Invoke { target, args, .. }) => {
+                referenced.insert(target.node.clone());
+                roots.extend(assembly.symbols.labels.get(&target.node));
+                args.iter().collect()
+            }
+            _ => continue,
+        };
+
+        for operand in operands {
+            if let Operand::Label(label) = &operand.node {
+                referenced.insert(label.clone());
+                roots.extend(assembly.symbols.labels.get(label));
+            }
+        }
+    }
+
+    for line in &lines {
+        match &line.statement {
+            Some(Statement::Instruction { mnemonic, operands }) => {
+                let code = instruction_to_byte(&mnemonic.node).map_or(0, |code| code.0);
+                for (i, operand) in operands.iter().enumerate() {
+                    if let Operand::Label(label) = &operand.node {
+                        referenced.insert(label.clone());
+                        if !is_target(code, i, operands.len()) {
+                            roots.push(assembly.symbols.labels[label]);
+                        }
+                    }
+                }
+            }
+            // the target is called, label arguments are pushed as data
+            Some(Statement::Invoke { target, args, .. }) => {
+                referenced.insert(target.node.clone());
+                for arg in args {
+                    if let Operand::Label(label) = &arg.node {
+                        referenced.insert(label.clone());
+                        roots.push(assembly.symbols.labels[label]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // walk every routine reachable from the entry point or a referenced label
+    let mut reachable = HashSet::new();
+    let mut subroutines = Vec::new();
+    let mut visited_routines = HashSet::new();
+    while let Some(start) = roots.pop() {
+        if !visited_routines.insert(start) {
+            continue;
+        }
+
+        let routine = program.routine(start);
+        for instr in &routine {
+            reachable.insert(instr.address);
+            if instr.code == CALL.0 {
+                roots.push(instr.operands[0]);
+                subroutines.push(instr.operands[0]);
+            }
+        }
+
+        if start == entry && !routine.iter().any(|instr| instr.code == HALT.0) {
+            program.warn(program.instructions[&entry].span, "Program never reaches HALT".to_string());
+        }
+    }
+
+    let mut addr_to_label: HashMap<Word, &str> = HashMap::new();
+    for line in &lines {
+        let (label, kind) = match &line.statement {
+            Some(Statement::Label(label)) => (label, "Label"),
+            Some(Statement::Proc { name, .. }) => (name, "Procedure"),
+            _ => continue,
+        };
+
+        addr_to_label.entry(assembly.symbols.labels[&label.node]).or_insert(&label.node);
+        if !referenced.contains(&label.node) {
+            program.warn(label.span, format!("{} :{} is never referenced", kind, label.node));
+        }
+    }
+
+    subroutines.sort_unstable();
+    subroutines.dedup();
+    for &start in &subroutines {
+        let routine = program.routine(start);
+        if !routine.is_empty() && !routine.iter().any(|instr| instr.code == RET.0) {
+            let name = addr_to_label.get(&start).copied().unwrap_or("?");
+            let span = program.instructions[&start].span;
+            program.warn(span, format!("Subroutine :{} never reaches RET", name));
+        }
+        program.check_stack(start, true);
+    }
+    program.check_stack(entry, false);
+
+    // report only the first instruction of every unreachable block,
+    // routines of included modules the program doesn't use are fine
+    let mut previous_reachable = true;
+    for instr in &assembly.instructions {
+        let is_library = matches!(lines[instr.span.line].statement, Some(Statement::Include(_)));
+        let is_reachable = is_library || reachable.contains(&instr.address);
+        if !is_reachable && previous_reachable {
+            program.warn(instr.span, "Unreachable code".to_string());
+        }
+        previous_reachable = is_reachable;
+    }
+
+    program.check_device_writes(&assembly.instructions);
+
+    let mut lints = program.lints;
+    lints.sort_by(|a, b| (a.span.line, a.span.start, &a.message).cmp(&(b.span.line, b.span.start, &b.message)));
+    lints.dedup();
+    lints
+}
+
+/// Parses, assembles and lints a whole source file
+pub fn lint_source(source: &str) -> Result<Vec<Lint>, Vec<Error>> {
+    let (lines, errors) = parse(source);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let assembly = assemble(&lines)?;
+    Ok(lint(&lines, &assembly))
+}
@@ -0,0 +1,526 @@
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, References, Request as RequestTrait},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, Range, ReferenceParams, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use crate::{
+    assembler::{argument_offset, assemble, builtin_constants, collect_symbols, Symbols},
+    instructions::{instruction_info, INSTRUCTION_SET},
+    lint::lint,
+    parser::{parse, Line, Operand, Span, Statement},
+    registers::{register_name, REGISTERS},
+    stdlib::link,
+};
+
+/// Directives and pseudo-ops offered by completion next to the mnemonics
+const DIRECTIVES: &[(&str, &str)] = &[
+    (".const", ".const NAME, value"),
+    (".proc", ".proc name(arg1, arg2, ...)"),
+    (".endp", ".endp"),
+    ("INVOKE", "INVOKE name, arg1, arg2, ..."),
+    (".org", ".org address"),
+    (".include", ".include \"std/module\""),
+    (".test", ".test \"name\""),
+    (".endt", ".endt"),
+    (".expect", ".expect r1 == value"),
+];
+
+/// A label, constant or procedure argument, identified by its name
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Symbol {
+    Label(String),
+    Constant(String),
+    /// Procedure name and argument name
+    Argument(String, String),
+}
+
+/// Token found under the cursor
+enum Token {
+    Mnemonic(String),
+    Register(u32),
+    Symbol(Symbol),
+}
+
+/// Byte offset in the line of a position's character, LSP counts characters in UTF-16 code units
+fn byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, char) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += char.len_utf16();
+    }
+    line.len()
+}
+
+/// UTF-16 code units in the line before the byte offset, offsets past the end count as one each
+fn utf16_offset(line: &str, offset: usize) -> u32 {
+    let units: usize = line
+        .char_indices()
+        .take_while(|(i, _)| *i < offset)
+        .map(|(_, char)| char.len_utf16())
+        .sum();
+    (units + offset.saturating_sub(line.len())) as u32
+}
+
+/// Range of the span, `text` are the lines of the source the span's byte offsets point into
+fn to_range(text: &[&str], span: Span) -> Range {
+    let line = text.get(span.line).copied().unwrap_or("");
+    Range::new(
+        Position::new(span.line as u32, utf16_offset(line, span.start)),
+        Position::new(span.line as u32, utf16_offset(line, span.end)),
+    )
+}
+
+/// Symbol used by an operand, constants inside a procedure resolve to its arguments first
+fn operand_symbol(proc: &Option<(&String, Vec<&String>)>, operand: &Operand) -> Option<Symbol> {
+    match operand {
+        Operand::Label(label) => Some(Symbol::Label(label.clone())),
+        Operand::Constant(constant) => match proc {
+            Some((name, args)) if args.contains(&constant) => {
+                Some(Symbol::Argument(name.to_string(), constant.clone()))
+            }
+            _ => Some(Symbol::Constant(constant.clone())),
+        },
+        _ => None,
+    }
+}
+
+/// Every definition and use of a symbol together with its location
+pub(crate) fn symbol_occurrences(lines: &[Line]) -> Vec<(Symbol, Span, bool)> {
+    let mut occurrences = Vec::new();
+    let mut proc: Option<(&String, Vec<&String>)> = None;
+
+    for line in lines {
+        match &line.statement {
+            Some(Statement::Label(label)) => {
+                occurrences.push((Symbol::Label(label.node.clone()), label.span, true))
+            }
+            Some(Statement::Constant { name, .. }) => {
+                occurrences.push((Symbol::Constant(name.node.clone()), name.span, true))
+            }
+            Some(Statement::Proc { name, args }) => {
+                occurrences.push((Symbol::Label(name.node.clone()), name.span, true));
+                for arg in args {
+                    occurrences.push((Symbol::Argument(name.node.clone(), arg.node.clone()), arg.span, true));
+                }
+                proc = Some((&name.node, args.iter().map(|arg| &arg.node).collect()));
+            }
+            Some(Statement::EndProc(_)) => proc = None,
+            Some(Statement::Instruction { operands, .. }) => {
+                for operand in operands {
+                    if let Some(symbol) = operand_symbol(&proc, &operand.node) {
+                        occurrences.push((symbol, operand.span, false));
+                    }
+                }
+            }
+            Some(Statement::Invoke { target, args, .. }) => {
+                occurrences.push((Symbol::Label(target.node.clone()), target.span, false));
+                for arg in args {
+                    if let Some(symbol) = operand_symbol(&proc, &arg.node) {
+                        occurrences.push((symbol, arg.span, false));
+                    }
+                }
+            }
+            Some(Statement::Expect { target, value, .. }) => {
+                for operand in [target, value] {
+                    if let Some(symbol) = operand_symbol(&proc, &operand.node) {
+                        occurrences.push((symbol, operand.span, false));
+                    }
+                }
+            }
+            Some(Statement::Origin(_))
+            | Some(Statement::Include(_))
+            | Some(Statement::Test(_))
+            | Some(Statement::EndTest(_))
+            | None => {}
+        }
+    }
+
+    occurrences
+}
+
+fn token_at(source: &str, lines: &[Line], position: Position) -> Option<Token> {
+    let line = position.line as usize;
+    let column = byte_offset(source.lines().nth(line).unwrap_or(""), position.character);
+
+    // the leading ':' of a label definition belongs to the label
+    let symbol = symbol_occurrences(lines)
+        .into_iter()
+        .find(|(_, span, is_definition)| {
+            span.contains(line, column) || (*is_definition && span.contains(line, column + 1))
+        });
+    if let Some((symbol, _, _)) = symbol {
+        return Some(Token::Symbol(symbol));
+    }
+
+    match &lines.get(line)?.statement {
+        Some(Statement::Instruction { mnemonic, operands }) => {
+            if mnemonic.span.contains(line, column) {
+                return Some(Token::Mnemonic(mnemonic.node.clone()));
+            }
+
+            operands
+                .iter()
+                .find(|operand| operand.span.contains(line, column))
+                .and_then(|operand| match &operand.node {
+                    Operand::Register(addr) => Some(Token::Register(*addr)),
+                    _ => None,
+                })
+        }
+        Some(Statement::Invoke { args, .. }) => args
+            .iter()
+            .find(|arg| arg.span.contains(line, column))
+            .and_then(|arg| match &arg.node {
+                Operand::Register(addr) => Some(Token::Register(*addr)),
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+fn diagnostic(text: &[&str], span: Span, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+    Diagnostic {
+        range: to_range(text, span),
+        severity: Some(severity),
+        source: Some("0xasm".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Parses and assembles the source, reporting every error as a diagnostic
+/// and lint warnings once the source assembles
+pub fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let (lines, errors) = parse(source);
+    let text: Vec<&str> = source.lines().collect();
+    let mut diagnostics: Vec<Diagnostic> = errors
+        .into_iter()
+        .map(|err| diagnostic(&text, err.span, DiagnosticSeverity::ERROR, err.message))
+        .collect();
+
+    match assemble(&lines) {
+        Ok(assembly) if diagnostics.is_empty() => {
+            diagnostics.extend(
+                lint(&lines, &assembly)
+                    .into_iter()
+                    .map(|lint| diagnostic(&text, lint.span, DiagnosticSeverity::WARNING, lint.message)),
+            );
+        }
+        Ok(_) => {}
+        Err(errors) => diagnostics.extend(
+            errors
+                .into_iter()
+                .map(|err| diagnostic(&text, err.span, DiagnosticSeverity::ERROR, err.message)),
+        ),
+    }
+
+    diagnostics
+}
+
+/// Describes the symbol, `None` for an argument whose `.proc` isn't known like one defined twice
+fn symbol_description(symbol: &Symbol, symbols: &Symbols) -> Option<String> {
+    let description = match symbol {
+        Symbol::Label(label) => match symbols.labels.get(label) {
+            Some(addr) => format!("label `:{}` at offset `0x{:08X}`", label, addr),
+            None => format!("undefined label `:{}`", label),
+        },
+        Symbol::Constant(constant) => match symbols.constant(constant) {
+            Some(val) => format!("constant `{}` = `0x{:08X}` ({})", constant, val, val),
+            None => format!("undefined constant `{}`", constant),
+        },
+        Symbol::Argument(proc, arg) => {
+            let n = symbols.procs.get(proc)?.iter().position(|other| other == arg)?;
+            format!("argument `{}` of `{}` at `fp + 0x{:02X}`", arg, proc, argument_offset(n))
+        }
+    };
+    Some(description)
+}
+
+/// Shows signature and opcode of instructions, register addresses and symbol values
+pub fn hover(source: &str, position: Position) -> Option<Hover> {
+    let (lines, _) = parse(source);
+
+    let value = match token_at(source, &lines, position)? {
+        Token::Mnemonic(mnemonic) => {
+            let info = instruction_info(&mnemonic)?;
+            format!(
+                "```\n{}\n```\n{}\n\nOpcode `0x{:02X}`, {} operand(s)",
+                info.signature, info.description, info.code.0, info.code.1
+            )
+        }
+        Token::Register(addr) => {
+            format!("register `{}` at `0x{:02X}`", register_name(addr)?, addr)
+        }
+        Token::Symbol(symbol) => symbol_description(&symbol, &collect_symbols(&link(&lines).0).0)?,
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    })
+}
+
+/// Finds the definition of the label or constant under the cursor
+pub fn definition(source: &str, position: Position) -> Option<Range> {
+    let (lines, _) = parse(source);
+    let symbol = match token_at(source, &lines, position)? {
+        Token::Symbol(symbol) => symbol,
+        _ => return None,
+    };
+
+    let text: Vec<&str> = source.lines().collect();
+    symbol_occurrences(&lines)
+        .into_iter()
+        .find(|(occurrence, _, is_definition)| *is_definition && *occurrence == symbol)
+        .map(|(_, span, _)| to_range(&text, span))
+}
+
+/// Finds every use of the label or constant under the cursor
+pub fn references(source: &str, position: Position, include_declaration: bool) -> Vec<Range> {
+    let (lines, _) = parse(source);
+    let symbol = match token_at(source, &lines, position) {
+        Some(Token::Symbol(symbol)) => symbol,
+        _ => return Vec::new(),
+    };
+
+    let text: Vec<&str> = source.lines().collect();
+    symbol_occurrences(&lines)
+        .into_iter()
+        .filter(|(occurrence, _, is_definition)| {
+            *occurrence == symbol && (include_declaration || !is_definition)
+        })
+        .map(|(_, span, _)| to_range(&text, span))
+        .collect()
+}
+
+/// Offers mnemonics at the start of a line and registers, arguments, constants and labels for operands
+pub fn completion(source: &str, position: Position) -> Vec<CompletionItem> {
+    let line = source.lines().nth(position.line as usize).unwrap_or("");
+    let before_cursor = &line[..byte_offset(line, position.character)];
+
+    if !before_cursor.trim_start().contains(char::is_whitespace) {
+        let mnemonics = INSTRUCTION_SET.iter().map(|info| CompletionItem {
+            label: info.name.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(info.signature.to_string()),
+            documentation: Some(lsp_types::Documentation::String(info.description.to_string())),
+            ..Default::default()
+        });
+        let directives = DIRECTIVES.iter().map(|(name, signature)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(signature.to_string()),
+            ..Default::default()
+        });
+
+        return mnemonics.chain(directives).collect();
+    }
+
+    let (lines, _) = parse(source);
+    let symbols = collect_symbols(&link(&lines).0).0;
+
+    // arguments of the procedure enclosing the cursor
+    let mut arguments = Vec::new();
+    for line in lines.iter().take(position.line as usize + 1) {
+        match &line.statement {
+            Some(Statement::Proc { name, args }) => {
+                arguments = args
+                    .iter()
+                    .enumerate()
+                    .map(|(n, arg)| CompletionItem {
+                        label: arg.node.clone(),
+                        kind: Some(CompletionItemKind::VARIABLE),
+                        detail: Some(format!("argument of {}, fp + 0x{:02X}", name.node, argument_offset(n))),
+                        ..Default::default()
+                    })
+                    .collect()
+            }
+            Some(Statement::EndProc(_)) => arguments.clear(),
+            _ => {}
+        }
+    }
+
+    let registers = REGISTERS.iter().map(|(name, addr)| CompletionItem {
+        label: name.to_string(),
+        kind: Some(CompletionItemKind::VARIABLE),
+        detail: Some(format!("register 0x{:02X}", addr)),
+        ..Default::default()
+    });
+    let builtins = builtin_constants()
+        .filter(|(name, _)| !symbols.constants.contains_key(*name))
+        .map(|(name, val)| (name.to_string(), *val));
+    let constants = symbols.constants.clone().into_iter().chain(builtins).map(|(name, val)| CompletionItem {
+        label: name,
+        kind: Some(CompletionItemKind::CONSTANT),
+        detail: Some(format!("0x{:08X}", val)),
+        ..Default::default()
+    });
+    let labels = symbols.labels.iter().map(|(name, addr)| CompletionItem {
+        label: format!(":{}", name),
+        filter_text: Some(name.clone()),
+        insert_text: Some(name.clone()),
+        kind: Some(CompletionItemKind::REFERENCE),
+        detail: Some(format!("0x{:08X}", addr)),
+        ..Default::default()
+    });
+
+    registers.chain(arguments).chain(constants).chain(labels).collect()
+}
+
+/// State of the server: the current text of every open document
+struct Server {
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn document(&self, uri: &Url) -> &str {
+        self.documents.get(uri).map(String::as_str).unwrap_or("")
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => serde_json::from_value::<HoverParams>(req.params).map(|params| {
+                let doc = params.text_document_position_params;
+                serde_json::to_value(hover(self.document(&doc.text_document.uri), doc.position))
+            }),
+            GotoDefinition::METHOD => {
+                serde_json::from_value::<GotoDefinitionParams>(req.params).map(|params| {
+                    let doc = params.text_document_position_params;
+                    let uri = doc.text_document.uri;
+                    let location = definition(self.document(&uri), doc.position)
+                        .map(|range| GotoDefinitionResponse::Scalar(Location::new(uri, range)));
+                    serde_json::to_value(location)
+                })
+            }
+            References::METHOD => serde_json::from_value::<ReferenceParams>(req.params).map(|params| {
+                let doc = params.text_document_position;
+                let uri = doc.text_document.uri;
+                let locations: Vec<Location> = references(
+                    self.document(&uri),
+                    doc.position,
+                    params.context.include_declaration,
+                )
+                .into_iter()
+                .map(|range| Location::new(uri.clone(), range))
+                .collect();
+                serde_json::to_value(locations)
+            }),
+            Completion::METHOD => serde_json::from_value::<CompletionParams>(req.params).map(|params| {
+                let doc = params.text_document_position;
+                let items = completion(self.document(&doc.text_document.uri), doc.position);
+                serde_json::to_value(CompletionResponse::Array(items))
+            }),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(Ok(value)) => Response::new_ok(id, value),
+            Ok(Err(err)) | Err(err) => {
+                Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string())
+            }
+        }
+    }
+
+    /// Updates the open documents, returns the uri of a document which needs new diagnostics
+    fn handle_notification(&mut self, not: Notification) -> Option<Url> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), params.text_document.text);
+                Some(uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                let uri = params.text_document.uri;
+                // full sync, the last change contains the whole document
+                let text = params.content_changes.into_iter().last()?.text;
+                self.documents.insert(uri.clone(), text);
+                Some(uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                self.documents.remove(&params.text_document.uri);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, uri: Url, source: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let params = PublishDiagnosticsParams::new(uri, diagnostics(source), None);
+    let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+    connection.sender.send(Message::Notification(not))?;
+
+    Ok(())
+}
+
+/// Runs the language server on stdin/stdout until the client shuts it down
+pub fn run() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })?;
+    connection.initialize(capabilities)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+    };
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+
+                let response = server.handle_request(req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => {
+                if let Some(uri) = server.handle_notification(not) {
+                    publish_diagnostics(&connection, uri.clone(), server.document(&uri))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
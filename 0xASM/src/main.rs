@@ -1,235 +1,167 @@
-use std::collections::HashMap;
-use std::convert::TryInto;
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
-use std::os::windows::prelude::FileExt;
-
-use instructions::instruction_to_byte;
-
-pub type Byte = u8;
-pub type Word = u32;
-
-mod instructions;
-
-fn write_output_byte(
-    output: &mut File,
-    data: Byte,
-    total_bytes_written: &mut u32,
-) -> Result<(), String> {
-    match output.write_all(&data.to_le_bytes()) {
-        Ok(_) => {
-            *total_bytes_written += 1;
-            Ok(())
-        }
-        Err(_) => Err("Failed to write to output file".to_string()),
-    }
+use std::fs;
+
+use asm::{
+    assembler::assemble_source,
+    formatter::format_source,
+    lint::lint_source,
+    lsp,
+    parser::{parse, Error, Statement},
+    repl, stdlib,
+    testing::{run_tests, DEFAULT_BUDGET},
+    xref::{call_graph_source, cross_reference_source, format_cross_reference},
+};
+
+fn usage(program: &str) -> Result<(), String> {
+    println!(
+        "Usage: {0} <input> <output>\n       {0} fmt [--check] <input>\n       {0} lint <input>\n       {0} lsp\n       {0} repl\n       {0} std [module]\n       {0} test [--budget <instructions>] <input>\n       {0} xref <input>\n       {0} calls [--dot] <input>",
+        program
+    );
+    Err("Invalid arguments".to_string())
 }
 
-fn write_output_word(
-    output: &mut File,
-    data: Word,
-    total_bytes_written: &mut u32,
-) -> Result<(), String> {
-    match output.write_all(&data.to_le_bytes()) {
-        Ok(_) => {
-            *total_bytes_written += 4;
-            Ok(())
-        }
-        Err(_) => Err("Failed to write to output file".to_string()),
-    }
+fn read_source(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|_| format!("Error opening input file: {}", path))
 }
 
-fn write_parsed_number(
-    output: &mut File,
-    total_bytes_written: &mut u32,
-    operand: &str,
-    prefix: &str,
-    radix: u32,
-    name: &str,
-    n: usize,
-) -> Result<(), String> {
-    match Word::from_str_radix(operand.trim_start_matches(prefix), radix) {
-        Ok(op) => write_output_word(output, op, total_bytes_written),
-        Err(_) => Err(format!(
-            "Error parsing {} operand at line {}: {}",
-            name, n, operand
-        )),
+/// Prints every error and returns the final error message
+fn report(errors: &[Error], message: String) -> String {
+    for err in errors {
+        println!("{}", err);
     }
+    message
 }
 
-fn parse_line(
-    output: &mut File,
-    mut n: usize,
-    line: &String,
-    jump_label_locations: &mut Vec<(Word, String)>,
-    total_bytes_written: &mut u32,
-) -> Result<(), String> {
-    n += 1;
-
-    let mut split = line.splitn(2, ' ');
-    let instruction = split.next();
-    if instruction.is_none() {
-        return Err(format!("Error reading instruction at line {}", n));
-    }
+fn assemble_file(input: &str, output: &str) -> Result<(), String> {
+    let source = read_source(input)?;
+    let assembly = assemble_source(&source)
+        .map_err(|errors| report(&errors, format!("Failed to assemble {}", input)))?;
 
-    let instruction = instruction.unwrap();
+    fs::write(output, assembly.image).map_err(|_| format!("Error creating output file: {}", output))
+}
 
-    let instruction_code = instruction_to_byte(instruction);
-    if instruction_code.is_none() {
-        return Err(format!(
-            "Unknown instruction at line {}: {}",
-            n, instruction
-        ));
+/// Rewrites the file in its canonical format, or with `check` only reports if it isn't formatted
+fn format_file(input: &str, check: bool) -> Result<(), String> {
+    let source = read_source(input)?;
+    let formatted = format_source(&source)
+        .map_err(|errors| report(&errors, format!("Failed to format {}", input)))?;
+
+    if formatted == source {
+        Ok(())
+    } else if check {
+        Err(format!("{} is not formatted", input))
+    } else {
+        fs::write(input, formatted).map_err(|_| format!("Error writing file: {}", input))
     }
+}
+
+/// Prints every lint warning, failing if there are any
+fn lint_file(input: &str) -> Result<(), String> {
+    let source = read_source(input)?;
+    let lints = lint_source(&source)
+        .map_err(|errors| report(&errors, format!("Failed to assemble {}", input)))?;
 
-    // write instruction byte
-    write_output_byte(output, instruction_code.unwrap().0, total_bytes_written)?;
-
-    let operands = split.next();
-    // if we have no operands but expect one, then error
-    if operands.is_none() && instruction_code.unwrap().1 != 0 {
-        return Err(format!(
-            "Missing parameter for instruction {} at line {}",
-            instruction, n
-        ));
-    // else if we have no operands and don't expect any, then return
-    } else if operands.is_none() {
-        return Ok(());
+    for lint in &lints {
+        println!("{}", lint);
     }
 
-    let operands = operands.unwrap().replace(" ", "");
-    let operands_split = operands.split(',');
-    // keep track of number of operands for error checking
-    let mut operand_count = 0;
-    for operand in operands_split {
-        let op: Word;
-
-        if operand.starts_with("0x") {
-            write_parsed_number(output, total_bytes_written, operand, "0x", 16, "hex", n)?;
-        } else if operand.starts_with("0b") {
-            write_parsed_number(output, total_bytes_written, operand, "0b", 2, "bin", n)?;
-        } else if operand.to_uppercase().starts_with("R") {
-            // remove the R part of the register name
-            let reg_name = operand[1..].to_string();
-
-            let reg_num = reg_name.parse::<Word>();
-            if reg_num.is_err() {
-                return Err(format!("Error parsing register at line {}: {}", n, operand));
-            }
+    if lints.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} warning(s) in {}", lints.len(), input))
+    }
+}
 
-            // check if it is inbounds and calculate the offset
-            let reg_num = reg_num.unwrap();
-            if reg_num > 0 && reg_num <= 8 {
-                op = (reg_num - 1) * 4;
-            } else {
-                return Err(format!("Invalid register at line {}: {}", n, operand));
-            }
+/// Runs every test of the file, failing if any test fails
+fn test_file(input: &str, budget: usize) -> Result<(), String> {
+    let source = read_source(input)?;
+    let results = run_tests(&source, budget)
+        .map_err(|errors| report(&errors, format!("Failed to assemble {}", input)))?;
 
-            write_output_word(output, op, total_bytes_written)?;
-        } else if operand.starts_with(":") {
-            // add the location of the label and the labels name for later and fill it with 0xFFFF FFFF FFFF FFFF
-            jump_label_locations.push((*total_bytes_written, operand[1..].to_string()));
-            write_output_word(output, Word::MAX, total_bytes_written)?;
-        } else if operand.starts_with("'") {
-            // parse char literals (single quotes) and write as little endian bytes
-            let mut op = operand.trim_matches('\'').as_bytes().to_vec();
-            for _ in 0..4 - op.len() {
-                op.push(0);
-            }
-            write_output_word(output, Word::from_le_bytes(op.clone().try_into().expect("Failed to convert char literal to Word")), total_bytes_written)?;
-        } else {
-            write_parsed_number(output, total_bytes_written, operand, "", 10, "dec", n)?;
+    for result in &results {
+        println!("test {} ... {}", result.name, if result.passed() { "ok" } else { "FAILED" });
+        for failure in &result.failures {
+            println!("    {}", failure);
         }
-
-        operand_count += 1;
-    }
-
-    // of the operator count of the current instruction is not equal to the number of operands expected, then error
-    if operand_count != instruction_code.unwrap().1 {
-        return Err(format!(
-            "Wrong number of operands for instruction {} at line {}",
-            instruction, n
-        ));
     }
 
-    Ok(())
-}
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    println!("\n{} passed, {} failed", results.len() - failed, failed);
 
-fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        println!("Usage: {} <input> <output>", args.get(0).unwrap());
-        return Err("Invalid arguments".to_string());
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} test(s) failed in {}", failed, input))
     }
+}
 
-    let mut output = match File::create(args.get(2).unwrap()) {
-        Ok(file) => file,
-        Err(_) => {
-            return Err(format!(
-                "Error creating output file: {}",
-                args.get(2).unwrap()
-            ))
-        }
-    };
-    let input = match File::open(args.get(1).unwrap()) {
-        Ok(file) => BufReader::new(file),
-        Err(_) => {
-            return Err(format!(
-                "Error opening input file: {}",
-                args.get(1).unwrap()
-            ))
-        }
-    };
-
-    let mut jump_label_map: HashMap<String, Word> = HashMap::new();
-    let mut jump_label_locations: Vec<(Word, String)> = Vec::new();
-    let mut total_bytes_written: u32 = 0;
+/// Prints where every label is defined and used
+fn xref_file(input: &str) -> Result<(), String> {
+    let source = read_source(input)?;
+    let references = cross_reference_source(&source)
+        .map_err(|errors| report(&errors, format!("Failed to parse {}", input)))?;
 
-    // Parse the input file
-    for (n, line) in input.lines().enumerate() {
-        if line.is_err() {
-            return Err(format!("Error reading line {}", n));
-        }
+    print!("{}", format_cross_reference(&references));
+    Ok(())
+}
 
-        let line = line.unwrap();
+/// Prints the call graph as text or Graphviz DOT
+fn calls_file(input: &str, dot: bool) -> Result<(), String> {
+    let source = read_source(input)?;
+    let graph = call_graph_source(&source)
+        .map_err(|errors| report(&errors, format!("Failed to assemble {}", input)))?;
 
-        if !line.is_empty() {
-            // comments with ; are allowed
-            if line.starts_with(";") {
-                continue;
-            } else if line.starts_with(":") {
-                // catch label definition and save the label plus its location in the output file
-                let label = line[1..].to_string();
+    print!("{}", if dot { graph.to_dot() } else { graph.to_text() });
+    Ok(())
+}
 
-                jump_label_map.insert(label, total_bytes_written);
+/// Lists the modules of the standard library with their procedures
+fn list_stdlib() -> Result<(), String> {
+    println!("0xASM standard library {}", stdlib::VERSION);
 
-                continue;
+    for (name, source) in stdlib::MODULES {
+        println!("\n{}{}", stdlib::PREFIX, name);
+        for line in parse(source).0 {
+            if let Some(Statement::Proc { name, args }) = line.statement {
+                let args: Vec<String> = args.into_iter().map(|arg| arg.node).collect();
+                println!("    {}({})", name.node, args.join(", "));
             }
-
-            match parse_line(
-                &mut output,
-                n,
-                &line,
-                &mut jump_label_locations,
-                &mut total_bytes_written,
-            ) {
-                Ok(_) => {}
-                Err(err) => {
-                    println!("{}", err);
-                    break;
-                }
-            };
         }
     }
 
-    for (location, label) in jump_label_locations {
-        let address = *jump_label_map.get(&label).unwrap();
-        match output.seek_write(&address.to_le_bytes(), location as u64) {
-            Ok(_) => {}
-            Err(_) => return Err(format!("Error writing jump label {}", label)),
-        }
-    }
+    Ok(())
+}
+
+/// Prints the source of a standard library module
+fn print_module(name: &str) -> Result<(), String> {
+    let path = format!("{}{}", stdlib::PREFIX, name.trim_start_matches(stdlib::PREFIX));
+    let source = stdlib::module(&path).ok_or_else(|| format!("Unknown module: {}", path))?;
 
+    print!("{}", source);
     Ok(())
 }
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args[1..] {
+        ["lsp"] => lsp::run().map_err(|err| format!("Language server error: {}", err)),
+        ["fmt", input] => format_file(input, false),
+        ["fmt", "--check", input] => format_file(input, true),
+        ["lint", input] => lint_file(input),
+        ["repl"] => repl::run().map_err(|err| format!("REPL error: {}", err)),
+        ["std"] => list_stdlib(),
+        ["std", module] => print_module(module),
+        ["test", input] => test_file(input, DEFAULT_BUDGET),
+        ["test", "--budget", budget, input] => match budget.parse() {
+            Ok(budget) => test_file(input, budget),
+            Err(_) => Err(format!("Invalid instruction budget: {}", budget)),
+        },
+        ["xref", input] => xref_file(input),
+        ["calls", input] => calls_file(input, false),
+        ["calls", "--dot", input] => calls_file(input, true),
+        [input, output] => assemble_file(input, output),
+        _ => usage(args[0]),
+    }
+}
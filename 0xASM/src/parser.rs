@@ -0,0 +1,482 @@
+use std::fmt;
+
+use crate::{registers::register_address, Word};
+
+/// Location of a token: zero-based line and the byte columns `start..end` within it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, start: usize, end: usize) -> Self {
+        Span { line, start, end }
+    }
+
+    /// Checks if the given position lies within (or directly behind) the span
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        self.line == line && self.start <= column && column <= self.end
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Hex(Word),
+    Bin(Word),
+    Dec(Word),
+    /// Up to four characters between single quotes, written as little endian bytes
+    Char(String),
+    /// Address of the register
+    Register(Word),
+    Label(String),
+    Constant(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    /// `:name`
+    Label(Spanned<String>),
+    /// `MNEMONIC operand, operand, ...`
+    Instruction {
+        mnemonic: Spanned<String>,
+        operands: Vec<Spanned<Operand>>,
+    },
+    /// `.const NAME, value`
+    Constant {
+        name: Spanned<String>,
+        value: Spanned<Operand>,
+    },
+    /// `.proc name(arg1, arg2, ...)`, defines the label `name` and the
+    /// fp-relative offsets of the arguments until the matching `.endp`
+    Proc {
+        name: Spanned<String>,
+        args: Vec<Spanned<String>>,
+    },
+    /// `.endp`, returns from the procedure
+    EndProc(Span),
+    /// `invoke name, arg1, arg2, ...`, pushes the arguments and their count and calls `name`
+    Invoke {
+        mnemonic: Spanned<String>,
+        target: Spanned<String>,
+        args: Vec<Spanned<Operand>>,
+    },
+    /// `.org 0x408`, address the image is loaded at
+    Origin(Spanned<Operand>),
+    /// `.include "std/module"`, links a module of the standard library into the program
+    Include(Spanned<String>),
+    /// `.test "name"`, starts a test block which is only assembled by the test runner
+    Test(Spanned<String>),
+    /// `.endt`, ends the test block
+    EndTest(Span),
+    /// `.expect r1 == value` or `.expect [address] == value`, checks a register or the word
+    /// at an address when the test reaches it
+    Expect {
+        target: Spanned<Operand>,
+        memory: bool,
+        value: Spanned<Operand>,
+    },
+}
+
+impl Statement {
+    /// Span of the name or mnemonic that starts the statement
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Label(label) => label.span,
+            Statement::Instruction { mnemonic, .. } | Statement::Invoke { mnemonic, .. } => mnemonic.span,
+            Statement::Constant { name, .. } | Statement::Proc { name, .. } => name.span,
+            Statement::EndProc(span) | Statement::EndTest(span) => *span,
+            Statement::Origin(value) => value.span,
+            Statement::Include(path) | Statement::Test(path) => path.span,
+            Statement::Expect { target, .. } => target.span,
+        }
+    }
+}
+
+/// A single source line, `statement` is `None` for empty, comment-only and invalid lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub statement: Option<Statement>,
+    /// Comment text without the leading `;`
+    pub comment: Option<Spanned<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(span: Span, message: String) -> Self {
+        Error { span, message }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error at line {}: {}", self.span.line + 1, self.message)
+    }
+}
+
+/// Checks if name is a valid label or constant name
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Splits `text` at every `separator` outside of char literals,
+/// returning the trimmed parts together with their byte offsets
+fn split_outside_quotes(text: &str, separator: char) -> Vec<(usize, &str)> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut part_start = 0;
+
+    for (i, c) in text.char_indices() {
+        if c == '\'' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push((part_start, &text[part_start..i]));
+            part_start = i + 1;
+        }
+    }
+    parts.push((part_start, &text[part_start..]));
+
+    parts
+        .into_iter()
+        .map(|(offset, part)| {
+            let trimmed = part.trim_start();
+            let offset = offset + part.len() - trimmed.len();
+            (offset, trimmed.trim_end())
+        })
+        .collect()
+}
+
+/// Returns the position of `pattern` outside of char literals
+fn find_outside_quotes(text: &str, pattern: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    for (i, c) in text.char_indices() {
+        if c == '\'' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && text[i..].starts_with(pattern) {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+fn parse_number(text: &str, prefix: &str, radix: u32, name: &str, span: Span) -> Result<Word, Error> {
+    Word::from_str_radix(&text[prefix.len()..], radix)
+        .map_err(|_| Error::new(span, format!("Error parsing {} operand: {}", name, text)))
+}
+
+pub fn parse_operand(text: &str, span: Span) -> Result<Operand, Error> {
+    let lowercase = text.to_lowercase();
+
+    if text.is_empty() {
+        Err(Error::new(span, "Missing operand".to_string()))
+    } else if lowercase.starts_with("0x") {
+        parse_number(text, "0x", 16, "hex", span).map(Operand::Hex)
+    } else if lowercase.starts_with("0b") {
+        parse_number(text, "0b", 2, "bin", span).map(Operand::Bin)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        parse_number(text, "", 10, "dec", span).map(Operand::Dec)
+    } else if text.starts_with('\'') {
+        // char literals are written as little endian bytes into a single word
+        if text.len() < 3 || !text.ends_with('\'') {
+            return Err(Error::new(span, format!("Error parsing char operand: {}", text)));
+        }
+
+        let chars = &text[1..text.len() - 1];
+        if chars.len() > 4 {
+            return Err(Error::new(span, format!("Char operand longer than 4 bytes: {}", text)));
+        }
+
+        Ok(Operand::Char(chars.to_string()))
+    } else if let Some(label) = text.strip_prefix(':') {
+        if !is_identifier(label) {
+            return Err(Error::new(span, format!("Invalid label name: {}", label)));
+        }
+
+        Ok(Operand::Label(label.to_string()))
+    } else if let Some(addr) = register_address(text) {
+        Ok(Operand::Register(addr))
+    } else if lowercase.starts_with('r') && lowercase[1..].chars().all(|c| c.is_ascii_digit()) {
+        Err(Error::new(span, format!("Invalid register: {}", text)))
+    } else if is_identifier(text) {
+        Ok(Operand::Constant(text.to_string()))
+    } else {
+        Err(Error::new(span, format!("Invalid operand: {}", text)))
+    }
+}
+
+/// Parses a comma separated operand list starting at column `offset`
+fn parse_operands(text: &str, line: usize, offset: usize) -> Result<Vec<Spanned<Operand>>, Error> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    split_outside_quotes(text, ',')
+        .into_iter()
+        .map(|(start, operand)| {
+            let span = Span::new(line, offset + start, offset + start + operand.len());
+            parse_operand(operand, span).map(|node| Spanned { node, span })
+        })
+        .collect()
+}
+
+/// Parses an identifier starting at column `offset`
+fn parse_identifier(text: &str, line: usize, offset: usize) -> Result<Spanned<String>, Error> {
+    let span = Span::new(line, offset, offset + text.len());
+    if !is_identifier(text) {
+        return Err(Error::new(span, format!("Invalid name: {}", text)));
+    }
+
+    Ok(Spanned {
+        node: text.to_string(),
+        span,
+    })
+}
+
+/// Parses `name(arg1, arg2, ...)` of a `.proc` directive starting at column `offset`
+fn parse_proc(text: &str, line: usize, offset: usize, directive_span: Span) -> Result<Statement, Error> {
+    let expected = || Error::new(directive_span, "Expected '.proc name(arg1, arg2, ...)'".to_string());
+
+    let open = text.find('(').ok_or_else(expected)?;
+    if !text.ends_with(')') {
+        return Err(expected());
+    }
+
+    let name = parse_identifier(text[..open].trim_end(), line, offset)?;
+    let args_text = &text[open + 1..text.len() - 1];
+    let args = if args_text.trim().is_empty() {
+        Vec::new()
+    } else {
+        split_outside_quotes(args_text, ',')
+            .into_iter()
+            .map(|(start, arg)| parse_identifier(arg, line, offset + open + 1 + start))
+            .collect::<Result<Vec<_>, Error>>()?
+    };
+
+    Ok(Statement::Proc { name, args })
+}
+
+/// Parses a double quoted string starting at column `offset`, the span covers the text between the quotes
+fn parse_quoted(text: &str, line: usize, offset: usize) -> Option<Spanned<String>> {
+    match text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+        Some(text) if !text.is_empty() => Some(Spanned {
+            node: text.to_string(),
+            span: Span::new(line, offset + 1, offset + 1 + text.len()),
+        }),
+        _ => None,
+    }
+}
+
+/// Parses `target == value` of an `.expect` directive starting at column `offset`
+fn parse_expect(text: &str, line: usize, offset: usize, directive_span: Span) -> Result<Statement, Error> {
+    let expected = || {
+        Error::new(
+            directive_span,
+            "Expected '.expect register == value' or '.expect [address] == value'".to_string(),
+        )
+    };
+
+    let eq = find_outside_quotes(text, "==").ok_or_else(expected)?;
+    let target_text = text[..eq].trim_end();
+    let value_text = text[eq + 2..].trim_start();
+    let value_offset = offset + text.len() - value_text.len();
+
+    let (target_text, target_offset, memory) =
+        match target_text.strip_prefix('[').and_then(|text| text.strip_suffix(']')) {
+            Some(address) => {
+                let trimmed = address.trim_start();
+                (trimmed.trim_end(), offset + 1 + address.len() - trimmed.len(), true)
+            }
+            None => (target_text, offset, false),
+        };
+
+    let target_span = Span::new(line, target_offset, target_offset + target_text.len());
+    let target = parse_operand(target_text, target_span)?;
+    if !memory && !matches!(target, Operand::Register(_)) {
+        return Err(expected());
+    }
+
+    let value_span = Span::new(line, value_offset, value_offset + value_text.len());
+    let value = parse_operand(value_text, value_span)?;
+
+    Ok(Statement::Expect {
+        target: Spanned {
+            node: target,
+            span: target_span,
+        },
+        memory,
+        value: Spanned {
+            node: value,
+            span: value_span,
+        },
+    })
+}
+
+fn parse_directive(name: Spanned<String>, rest: &str, line: usize, offset: usize) -> Result<Statement, Error> {
+    match name.node.to_lowercase().as_str() {
+        "const" => {
+            let mut args = parse_operands(rest, line, offset)?.into_iter();
+            match (args.next(), args.next(), args.next()) {
+                (
+                    Some(Spanned {
+                        node: Operand::Constant(constant),
+                        span,
+                    }),
+                    Some(value),
+                    None,
+                ) => Ok(Statement::Constant {
+                    name: Spanned { node: constant, span },
+                    value,
+                }),
+                _ => Err(Error::new(name.span, "Expected '.const NAME, value'".to_string())),
+            }
+        }
+        "org" => {
+            let mut args = parse_operands(rest, line, offset)?.into_iter();
+            match (args.next(), args.next()) {
+                (Some(address), None) => Ok(Statement::Origin(address)),
+                _ => Err(Error::new(name.span, "Expected '.org address'".to_string())),
+            }
+        }
+        "include" => parse_quoted(rest, line, offset)
+            .map(Statement::Include)
+            .ok_or_else(|| Error::new(name.span, "Expected '.include \"std/module\"'".to_string())),
+        "test" => parse_quoted(rest, line, offset)
+            .map(Statement::Test)
+            .ok_or_else(|| Error::new(name.span, "Expected '.test \"name\"'".to_string())),
+        "endt" if rest.is_empty() => Ok(Statement::EndTest(name.span)),
+        "endt" => Err(Error::new(name.span, "Unexpected operands for .endt".to_string())),
+        "expect" => parse_expect(rest, line, offset, name.span),
+        "proc" => parse_proc(rest, line, offset, name.span),
+        "endp" if rest.is_empty() => Ok(Statement::EndProc(name.span)),
+        "endp" => Err(Error::new(name.span, "Unexpected operands for .endp".to_string())),
+        _ => Err(Error::new(name.span, format!("Unknown directive: .{}", name.node))),
+    }
+}
+
+fn parse_statement(code: &str, line: usize, offset: usize) -> Result<Statement, Error> {
+    if let Some(label) = code.strip_prefix(':') {
+        let span = Span::new(line, offset + 1, offset + code.len());
+        if !is_identifier(label) {
+            return Err(Error::new(span, format!("Invalid label name: {}", label)));
+        }
+
+        return Ok(Statement::Label(Spanned {
+            node: label.to_string(),
+            span,
+        }));
+    }
+
+    // split the mnemonic (or directive) from its operands
+    let word_end = code.find(char::is_whitespace).unwrap_or(code.len());
+    let rest = &code[word_end..];
+    let rest_offset = offset + word_end + rest.len() - rest.trim_start().len();
+    let rest = rest.trim();
+    let word = Spanned {
+        node: code[..word_end].to_string(),
+        span: Span::new(line, offset, offset + word_end),
+    };
+
+    if let Some(directive) = word.node.strip_prefix('.') {
+        let name = Spanned {
+            node: directive.to_string(),
+            span: word.span,
+        };
+
+        return parse_directive(name, rest, line, rest_offset);
+    }
+
+    let operands = parse_operands(rest, line, rest_offset)?;
+
+    if word.node.eq_ignore_ascii_case("invoke") {
+        let mut operands = operands.into_iter();
+        let target = match operands.next() {
+            Some(Spanned {
+                node: Operand::Constant(target),
+                span,
+            })
+            | Some(Spanned {
+                node: Operand::Label(target),
+                span,
+            }) => Spanned { node: target, span },
+            _ => return Err(Error::new(word.span, "Expected 'invoke name, arg1, arg2, ...'".to_string())),
+        };
+
+        return Ok(Statement::Invoke {
+            mnemonic: word,
+            target,
+            args: operands.collect(),
+        });
+    }
+
+    Ok(Statement::Instruction {
+        mnemonic: word,
+        operands,
+    })
+}
+
+/// Parses a single line of source, `line` is the zero-based line number used for spans
+pub fn parse_line(text: &str, line: usize) -> Result<Line, Error> {
+    let (code, comment) = match find_outside_quotes(text, ";") {
+        Some(i) => (
+            &text[..i],
+            Some(Spanned {
+                node: text[i + 1..].trim_end().to_string(),
+                span: Span::new(line, i, text.trim_end().len()),
+            }),
+        ),
+        None => (text, None),
+    };
+
+    let trimmed = code.trim_start();
+    let offset = code.len() - trimmed.len();
+    let trimmed = trimmed.trim_end();
+
+    let statement = if trimmed.is_empty() {
+        None
+    } else {
+        Some(parse_statement(trimmed, line, offset)?)
+    };
+
+    Ok(Line { statement, comment })
+}
+
+/// Parses a whole source file, keeping one `Line` per source line
+/// so that the line index always matches the line number
+pub fn parse(source: &str) -> (Vec<Line>, Vec<Error>) {
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+
+    for (n, text) in source.lines().enumerate() {
+        match parse_line(text, n) {
+            Ok(line) => lines.push(line),
+            Err(err) => {
+                errors.push(err);
+                lines.push(Line {
+                    statement: None,
+                    comment: None,
+                });
+            }
+        }
+    }
+
+    (lines, errors)
+}
@@ -0,0 +1,35 @@
+use crate::Word;
+
+/// Register names and their addresses, mirroring the register layout of the 0xVM
+pub const REGISTERS: &[(&str, Word)] = &[
+    ("r1", 0x00),
+    ("r2", 0x04),
+    ("r3", 0x08),
+    ("r4", 0x0C),
+    ("r5", 0x10),
+    ("r6", 0x14),
+    ("r7", 0x18),
+    ("r8", 0x1C),
+    ("pc", 0x20),
+    ("acc", 0x24),
+    ("sr", 0x28),
+    ("sp", 0x2C),
+    ("fp", 0x30),
+    ("ivt", 0x34),
+];
+
+/// Looks up the address of a register, ignoring case
+pub fn register_address(name: &str) -> Option<Word> {
+    REGISTERS
+        .iter()
+        .find(|(register, _)| register.eq_ignore_ascii_case(name))
+        .map(|(_, addr)| *addr)
+}
+
+/// Looks up the name of the register at addr
+pub fn register_name(addr: Word) -> Option<&'static str> {
+    REGISTERS
+        .iter()
+        .find(|(_, register_addr)| *register_addr == addr)
+        .map(|(name, _)| *name)
+}
@@ -0,0 +1,312 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use vm::{
+    cpu::CPU,
+    device::Device,
+    machine::{MachineBuilder, LOAD_ADDRESS, MEMORY_SIZE, SCREEN_ADDRESS, SCREEN_END},
+    memory::Memory,
+    trap::Trap,
+};
+
+use crate::{
+    assembler::{assemble_source, assemble_with, literal_value, statement_size, Symbols},
+    parser::{is_identifier, parse_line, parse_operand, Span},
+    registers::{register_address, REGISTERS},
+    Byte, Word,
+};
+
+/// Instructions a single line may execute before it is stopped, e.g. a `JMP` that never comes back
+pub const STEP_LIMIT: usize = 100_000;
+
+const HELP: &str = "\
+Enter 0xASM lines to assemble and execute them, `:label` and `.const` define symbols
+    .reset                  reset the machine and forget all symbols
+    .load <file>            assemble a file and load it at its .org address
+    .label <name> [address] define a label at the address or the next line
+    .help                   show this help
+    .quit                   leave the REPL";
+
+/// Bytes written since the last line, as absolute address and length
+pub(crate) type Writes = Rc<RefCell<Vec<(Word, Word)>>>;
+
+/// Device wrapper recording the writes to the wrapped device
+struct Watched {
+    device: Box<dyn Device>,
+    start: Word,
+    writes: Writes,
+}
+
+impl Watched {
+    fn record(&self, addr: Word, len: Word) {
+        self.writes.borrow_mut().push((self.start + addr, len));
+    }
+}
+
+impl Device for Watched {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        self.device.get_word(addr)
+    }
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        self.device.set_word(addr, value)?;
+        self.record(addr, 4);
+        Ok(())
+    }
+
+    fn get_byte(&self, addr: Word) -> Result<Byte, Trap> {
+        self.device.get_byte(addr)
+    }
+    fn set_byte(&mut self, addr: Word, value: Byte) -> Result<(), Trap> {
+        self.device.set_byte(addr, value)?;
+        self.record(addr, 1);
+        Ok(())
+    }
+
+    fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+        self.device.get_range(addr, size)
+    }
+    fn set_range(&mut self, addr: Word, values: Vec<Byte>) -> Result<(), Trap> {
+        let len = values.len() as Word;
+        self.device.set_range(addr, values)?;
+        self.record(addr, len);
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        self.device.tick();
+    }
+
+    fn flush(&mut self) -> Result<(), Trap> {
+        self.device.flush()
+    }
+}
+
+/// The standard 0xVM machine with plain memory in place of the Screen, so printed cells show up as
+/// touched memory instead of being drawn. Both it and main memory record their writes
+pub(crate) fn machine(writes: &Writes) -> CPU {
+    let watched = |start: Word, size: Word| -> Box<dyn Device> {
+        let device = Box::new(Memory::new(size));
+        let writes = Rc::clone(writes);
+        Box::new(Watched { device, start, writes })
+    };
+
+    MachineBuilder::standard()
+        .device(watched(SCREEN_ADDRESS, SCREEN_END - SCREEN_ADDRESS), SCREEN_ADDRESS, SCREEN_END)
+        .device(watched(LOAD_ADDRESS, MEMORY_SIZE), LOAD_ADDRESS, LOAD_ADDRESS + MEMORY_SIZE)
+        .build()
+        .cpu
+}
+
+/// Formats the sr flags, Z is set for a zero result and C if the result is smaller than the first operand
+fn flags(sr: Word) -> String {
+    format!("Z={} C={}", sr & 1, (sr >> 1) & 1)
+}
+
+/// Merges overlapping and adjacent writes into sorted ranges
+fn touched_ranges(writes: &[(Word, Word)]) -> Vec<(Word, Word)> {
+    let mut writes = writes.to_vec();
+    writes.sort_unstable();
+
+    let mut ranges: Vec<(Word, Word)> = Vec::new();
+    for (addr, len) in writes {
+        match ranges.last_mut() {
+            Some((start, end)) if addr <= *end => *end = (*end).max(addr + len),
+            _ => ranges.push((addr, addr + len)),
+        }
+    }
+    ranges
+}
+
+/// Interactive session assembling and executing one line at a time on a live CPU
+pub struct Repl {
+    pub cpu: CPU,
+    pub symbols: Symbols,
+    /// Address the next line is assembled at
+    pub address: Word,
+    writes: Writes,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let writes = Writes::default();
+        Repl {
+            cpu: machine(&writes),
+            symbols: Symbols::default(),
+            address: LOAD_ADDRESS,
+            writes,
+        }
+    }
+
+    /// Evaluates a command or 0xASM line, returning the lines to show.
+    /// `None` ends the session
+    pub fn eval(&mut self, input: &str) -> Option<Vec<String>> {
+        let mut words = input.split_whitespace();
+        let output = match (words.next(), words.next(), words.next(), words.next()) {
+            (None, ..) => Ok(Vec::new()),
+            (Some(".quit"), None, ..) => return None,
+            (Some(".help"), None, ..) => Ok(HELP.lines().map(str::to_string).collect()),
+            (Some(".reset"), None, ..) => {
+                *self = Repl::new();
+                Ok(vec!["Machine reset".to_string()])
+            }
+            (Some(".load"), Some(path), None, _) => self.load(path),
+            (Some(".label"), Some(name), address, None) => self.label(name, address),
+            (Some(".load"), ..) | (Some(".label"), ..) | (Some(".reset"), ..) | (Some(".help"), ..) => {
+                Err("Invalid arguments, see .help".to_string())
+            }
+            _ => self.execute(input),
+        };
+
+        Some(output.unwrap_or_else(|err| vec![format!("Error: {}", err)]))
+    }
+
+    fn load(&mut self, path: &str) -> Result<Vec<String>, String> {
+        let source = fs::read_to_string(path).map_err(|_| format!("Error opening input file: {}", path))?;
+        let assembly = assemble_source(&source).map_err(|errors| {
+            let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+            messages.join("\n")
+        })?;
+        if assembly.origin < LOAD_ADDRESS {
+            return Err(format!("{} must be assembled with .org 0x{:X} or above", path, LOAD_ADDRESS));
+        }
+
+        let len = assembly.image.len() as Word;
+        self.cpu
+            .memory_mapper
+            .set_range(assembly.origin, assembly.image)
+            .map_err(|trap| format!("Can't load {}: {}", path, trap))?;
+        self.writes.borrow_mut().clear();
+
+        let labels = assembly.symbols.labels.len();
+        self.symbols.extend(assembly.symbols);
+        self.address = self.address.max(assembly.origin + len);
+
+        Ok(vec![format!(
+            "Loaded {} bytes at 0x{:08X} with {} label(s)",
+            len, assembly.origin, labels
+        )])
+    }
+
+    fn label(&mut self, name: &str, address: Option<&str>) -> Result<Vec<String>, String> {
+        if !is_identifier(name) {
+            return Err(format!("Invalid label name: {}", name));
+        }
+        let address = match address {
+            Some(text) => parse_operand(text, Span::new(0, 0, text.len()))
+                .ok()
+                .and_then(|operand| literal_value(&operand))
+                .ok_or_else(|| format!("Invalid address: {}", text))?,
+            None => self.address,
+        };
+
+        self.symbols.labels.insert(name.to_string(), address);
+        Ok(vec![format!(":{} = 0x{:08X}", name, address)])
+    }
+
+    /// Assembles the line at the current address and runs its code until the pc reaches its end
+    fn execute(&mut self, input: &str) -> Result<Vec<String>, String> {
+        let line = parse_line(input, 0).map_err(|err| err.message)?;
+        // modules linked by .include are placed behind the code of the line and only loaded
+        let code_size = line.statement.as_ref().map_or(0, statement_size);
+        let lines = vec![parse_line(&format!(".org 0x{:X}", self.address), 0).unwrap(), line];
+        let assembly = assemble_with(&lines, &self.symbols).map_err(|errors| {
+            let messages: Vec<String> = errors.into_iter().map(|err| err.message).collect();
+            messages.join("\n")
+        })?;
+
+        let start = self.address;
+        let len = assembly.image.len() as Word;
+        self.symbols = assembly.symbols;
+        if len > 0 {
+            self.cpu.memory_mapper.set_range(start, assembly.image).map_err(|trap| trap.to_string())?;
+            self.writes.borrow_mut().clear();
+            self.address = start + len;
+        }
+        if code_size == 0 {
+            return Ok(match len {
+                0 => Vec::new(),
+                _ => vec![format!("Linked {} bytes at 0x{:08X}", len, start)],
+            });
+        }
+        let end = start + code_size;
+
+        let registers: Vec<Word> = REGISTERS.iter().map(|(_, addr)| self.cpu.get_reg(*addr)).collect();
+        let pc = register_address("pc").unwrap();
+        self.cpu.set_reg(pc, start);
+        self.cpu.resume();
+
+        let mut steps = 0;
+        let mut result = Ok(());
+        while steps < STEP_LIMIT && !self.cpu.is_halted() && self.cpu.get_reg(pc) != end && result.is_ok() {
+            result = self.cpu.step();
+            steps += 1;
+        }
+
+        let mut output = Vec::new();
+        for ((name, addr), before) in REGISTERS.iter().zip(registers) {
+            let after = self.cpu.get_reg(*addr);
+            // the pc always moves and sr is shown as flags below
+            if after != before && !["pc", "sr"].contains(name) {
+                output.push(format!("{:<4} 0x{:08X} -> 0x{:08X}", name, before, after));
+            }
+        }
+        output.push(format!("{:<4} {}", "sr", flags(self.cpu.get_reg(register_address("sr").unwrap()))));
+
+        for (start, end) in touched_ranges(&self.writes.borrow()) {
+            for line_start in (start..end).step_by(16) {
+                let bytes: Vec<String> = (line_start..end.min(line_start + 16))
+                    .map(|addr| match self.cpu.memory_mapper.get_byte(addr) {
+                        Ok(byte) => format!("{:02X}", byte),
+                        Err(_) => "--".to_string(),
+                    })
+                    .collect();
+                output.push(format!("0x{:08X}: {}", line_start, bytes.join(" ")));
+            }
+        }
+
+        match result {
+            Err(err) => output.push(format!("Error: {}", err)),
+            Ok(()) if self.cpu.is_halted() => output.push("Halted".to_string()),
+            Ok(()) if steps == STEP_LIMIT => output.push(format!(
+                "Stopped after {} instructions at pc 0x{:08X}",
+                STEP_LIMIT,
+                self.cpu.get_reg(pc)
+            )),
+            Ok(()) => {}
+        }
+        Ok(output)
+    }
+}
+
+/// Runs the REPL on stdin/stdout
+pub fn run() -> io::Result<()> {
+    let mut repl = Repl::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    println!("0xASM REPL, .help lists the commands");
+    loop {
+        print!("0x{:08X}> ", repl.address);
+        stdout.flush()?;
+
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+
+        match repl.eval(input.trim()) {
+            Some(output) => output.iter().for_each(|line| println!("{}", line)),
+            None => return Ok(()),
+        }
+    }
+}
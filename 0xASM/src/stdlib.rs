@@ -0,0 +1,102 @@
+use crate::parser::{parse, Error, Line, Span, Statement};
+
+/// Version of the standard library, the major version changes whenever a routine changes its arguments or result
+pub const VERSION: &str = "1.0.0";
+
+/// Prefix of the module paths accepted by `.include`
+pub const PREFIX: &str = "std/";
+
+/// Name and source of every bundled module
+pub const MODULES: &[(&str, &str)] = &[
+    ("disk", include_str!("../std/disk.asm")),
+    ("memory", include_str!("../std/memory.asm")),
+    ("screen", include_str!("../std/screen.asm")),
+    ("string", include_str!("../std/string.asm")),
+];
+
+/// Looks up the source of the module at `std/name`
+pub fn module(path: &str) -> Option<&'static str> {
+    let name = path.strip_prefix(PREFIX)?;
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
+
+/// Moves every span of the statement to `span`
+fn relocate(statement: &mut Statement, span: Span) {
+    match statement {
+        Statement::Label(label) => label.span = span,
+        Statement::Instruction { mnemonic, operands } => {
+            mnemonic.span = span;
+            operands.iter_mut().for_each(|operand| operand.span = span);
+        }
+        Statement::Constant { name, value } => {
+            name.span = span;
+            value.span = span;
+        }
+        Statement::Proc { name, args } => {
+            name.span = span;
+            args.iter_mut().for_each(|arg| arg.span = span);
+        }
+        Statement::EndProc(end) => *end = span,
+        Statement::Invoke { mnemonic, target, args } => {
+            mnemonic.span = span;
+            target.span = span;
+            args.iter_mut().for_each(|arg| arg.span = span);
+        }
+        Statement::Origin(address) => address.span = span,
+        Statement::Include(path) => path.span = span,
+        Statement::Test(name) => name.span = span,
+        Statement::EndTest(end) => *end = span,
+        Statement::Expect { target, value, .. } => {
+            target.span = span;
+            value.span = span;
+        }
+    }
+}
+
+/// Appends every module included by the program behind its last line, so execution never falls into
+/// a module. Each module is linked once and its spans point at the `.include` that pulled it in
+pub fn link(lines: &[Line]) -> (Vec<Line>, Vec<Error>) {
+    let mut linked = lines.to_vec();
+    let mut errors = Vec::new();
+    let mut included: Vec<&str> = Vec::new();
+
+    for line in lines {
+        let path = match &line.statement {
+            Some(Statement::Include(path)) => path,
+            _ => continue,
+        };
+
+        if included.contains(&path.node.as_str()) {
+            continue;
+        }
+        included.push(&path.node);
+
+        let source = match module(&path.node) {
+            Some(source) => source,
+            None => {
+                errors.push(Error::new(path.span, format!("Unknown module: {}", path.node)));
+                continue;
+            }
+        };
+
+        let (module_lines, module_errors) = parse(source);
+        errors.extend(
+            module_errors
+                .into_iter()
+                .map(|err| Error::new(path.span, format!("{} in {}", err.message, path.node))),
+        );
+
+        for mut statement in module_lines.into_iter().filter_map(|line| line.statement) {
+            relocate(&mut statement, path.span);
+            linked.push(Line {
+                statement: Some(statement),
+                comment: None,
+            });
+        }
+    }
+
+    (linked, errors)
+}
@@ -0,0 +1,307 @@
+use crate::{
+    assembler::{assemble, operand_value, Assembly},
+    formatter::format_operand,
+    instructions::instruction_codes::HALT,
+    parser::{parse, parse_line, Error, Line, Span, Spanned, Statement},
+    registers::register_address,
+    repl::{machine, Writes},
+    Word,
+};
+use vm::machine::LOAD_ADDRESS;
+
+/// Instructions a test may execute before it fails, e.g. stuck in an endless loop
+pub const DEFAULT_BUDGET: usize = 1_000_000;
+
+/// A `.test` block with the lines between `.test` and `.endt`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Test {
+    pub name: Spanned<String>,
+    pub lines: Vec<Line>,
+}
+
+/// Result of running a single test, it passed if there are no failures
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub failures: Vec<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// An `.expect` resolved against the test image
+struct Check {
+    /// Address of the HALT taking the place of the `.expect`
+    address: Word,
+    line: usize,
+    text: String,
+    target: Word,
+    memory: bool,
+    expected: Word,
+}
+
+fn empty_line() -> Line {
+    Line {
+        statement: None,
+        comment: None,
+    }
+}
+
+/// Takes the `.test` blocks out of the program. The returned program keeps one line per source line,
+/// the lines of the test blocks are left empty, so spans still point at the right line
+pub fn split_tests(lines: &[Line]) -> (Vec<Line>, Vec<Test>, Vec<Error>) {
+    let mut program = Vec::with_capacity(lines.len());
+    let mut tests: Vec<Test> = Vec::new();
+    let mut errors = Vec::new();
+    let mut open: Option<Test> = None;
+
+    for line in lines {
+        match (&line.statement, &mut open) {
+            (Some(Statement::Test(name)), _) => {
+                if let Some(outer) = open.take() {
+                    errors.push(Error::new(
+                        name.span,
+                        format!("Test {} can't be nested in {}", name.node, outer.name.node),
+                    ));
+                }
+                if tests.iter().any(|test| test.name.node == name.node) {
+                    errors.push(Error::new(name.span, format!("Duplicate test: {}", name.node)));
+                }
+
+                open = Some(Test {
+                    name: name.clone(),
+                    lines: Vec::new(),
+                });
+                program.push(empty_line());
+            }
+            (Some(Statement::EndTest(span)), None) => {
+                errors.push(Error::new(*span, ".endt without .test".to_string()));
+                program.push(empty_line());
+            }
+            (Some(Statement::EndTest(_)), Some(_)) => {
+                tests.extend(open.take());
+                program.push(empty_line());
+            }
+            (Some(Statement::Expect { target, .. }), None) => {
+                errors.push(Error::new(target.span, ".expect outside of a .test block".to_string()));
+                program.push(empty_line());
+            }
+            (_, Some(test)) => {
+                test.lines.push(line.clone());
+                program.push(empty_line());
+            }
+            (_, None) => program.push(line.clone()),
+        }
+    }
+
+    if let Some(test) = open {
+        errors.push(Error::new(test.name.span, format!("Missing .endt for test {}", test.name.node)));
+    }
+
+    (program, tests, errors)
+}
+
+/// Address of the HALT generated for the span
+fn halt_address(assembly: &Assembly, span: Span) -> Word {
+    assembly
+        .instructions
+        .iter()
+        .find(|instr| instr.code == HALT.0 && instr.span == span)
+        .map(|instr| instr.address)
+        .unwrap()
+}
+
+/// Assembles the test in front of the program: the test body runs first and ends in a HALT, every
+/// `.expect` is replaced by a HALT the runner checks the expectation at
+fn assemble_test(program: &[Line], test: &Test) -> Result<(Assembly, Vec<Check>), Vec<Error>> {
+    // without .org the image is loaded where the 0xVM loads programs
+    let origin = program.iter().find_map(|line| match &line.statement {
+        Some(Statement::Origin(address)) => Some(address),
+        _ => None,
+    });
+    let origin_line = match origin {
+        Some(address) => Line {
+            statement: Some(Statement::Origin(address.clone())),
+            comment: None,
+        },
+        None => parse_line(&format!(".org 0x{:X}", LOAD_ADDRESS), 0).unwrap(),
+    };
+
+    let mut lines = vec![origin_line];
+    let mut expectations = Vec::new();
+    for line in &test.lines {
+        match &line.statement {
+            Some(Statement::Expect { target, memory, value }) => {
+                let halt = Spanned {
+                    node: "HALT".to_string(),
+                    span: target.span,
+                };
+                lines.push(Line {
+                    statement: Some(Statement::Instruction {
+                        mnemonic: halt,
+                        operands: Vec::new(),
+                    }),
+                    comment: None,
+                });
+                expectations.push((target, *memory, value));
+            }
+            _ => lines.push(line.clone()),
+        }
+    }
+
+    let end = Spanned {
+        node: "HALT".to_string(),
+        span: test.name.span,
+    };
+    lines.push(Line {
+        statement: Some(Statement::Instruction {
+            mnemonic: end,
+            operands: Vec::new(),
+        }),
+        comment: None,
+    });
+    lines.extend(program.iter().map(|line| match &line.statement {
+        Some(Statement::Origin(_)) => empty_line(),
+        _ => line.clone(),
+    }));
+
+    let assembly = assemble(&lines)?;
+    if assembly.origin < LOAD_ADDRESS {
+        return Err(vec![Error::new(
+            test.name.span,
+            format!("Tests need an .org of 0x{:X} or above", LOAD_ADDRESS),
+        )]);
+    }
+
+    let mut errors = Vec::new();
+    let mut checks = Vec::new();
+    for (target, memory, value) in expectations {
+        let address = halt_address(&assembly, target.span);
+        let resolved = (
+            operand_value(target, &assembly.symbols, &[]),
+            operand_value(value, &assembly.symbols, &[]),
+        );
+        match resolved {
+            (Ok(target_value), Ok(expected)) => {
+                let target_text = format_operand(&target.node);
+                let target_text = if memory { format!("[{}]", target_text) } else { target_text };
+                checks.push(Check {
+                    address,
+                    line: target.span.line,
+                    text: format!("{} == {}", target_text, format_operand(&value.node)),
+                    target: target_value,
+                    memory,
+                    expected,
+                });
+            }
+            (target, value) => errors.extend(target.err().into_iter().chain(value.err())),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok((assembly, checks))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Runs the test on a fresh standard 0xVM machine, with plain memory in place of the Screen, until it reaches its
+/// end, stopping after `budget` instructions
+fn run_test(program: &[Line], test: &Test, budget: usize) -> Result<TestResult, Vec<Error>> {
+    let (assembly, checks) = assemble_test(program, test)?;
+    let end = halt_address(&assembly, test.name.span);
+
+    let mut cpu = machine(&Writes::default());
+    let pc = register_address("pc").unwrap();
+    cpu.set_reg(pc, assembly.origin);
+
+    let mut failures = Vec::new();
+    let run = || {
+        cpu.memory_mapper
+            .set_range(assembly.origin, assembly.image)
+            .map_err(|trap| trap.to_string())?;
+
+        let mut steps = 0;
+        loop {
+            while steps < budget && !cpu.is_halted() {
+                cpu.step().map_err(|err| err.to_string())?;
+                steps += 1;
+            }
+            if !cpu.is_halted() {
+                return Err(format!(
+                    "Instruction budget of {} exhausted at pc 0x{:08X}",
+                    budget,
+                    cpu.get_reg(pc)
+                ));
+            }
+
+            let halted_at = cpu.get_reg(pc) - 1;
+            if halted_at == end {
+                return Ok(());
+            }
+
+            let check = checks
+                .iter()
+                .find(|check| check.address == halted_at)
+                .ok_or_else(|| format!("Halted at 0x{:08X} before the end of the test", halted_at))?;
+            let actual = if check.memory {
+                cpu.memory_mapper.get_word(check.target).map_err(|trap| trap.to_string())?
+            } else {
+                cpu.try_get_reg(check.target).map_err(|trap| trap.to_string())?
+            };
+            if actual != check.expected {
+                failures.push(format!(
+                    "line {}: {}, expected 0x{:08X} but got 0x{:08X}",
+                    check.line + 1,
+                    check.text,
+                    check.expected,
+                    actual
+                ));
+            }
+            cpu.resume();
+        }
+    };
+
+    if let Err(message) = run() {
+        failures.push(message);
+    }
+
+    Ok(TestResult {
+        name: test.name.node.clone(),
+        failures,
+    })
+}
+
+/// Assembles every test of the source into its own image and runs them, stopping each one after
+/// `budget` instructions. Fails with the errors of the program or any test that doesn't assemble
+pub fn run_tests(source: &str, budget: usize) -> Result<Vec<TestResult>, Vec<Error>> {
+    let (lines, mut errors) = parse(source);
+    let (program, tests, split_errors) = split_tests(&lines);
+    errors.extend(split_errors);
+    if let Err(program_errors) = assemble(&program) {
+        errors.extend(program_errors);
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|err| (err.span.line, err.span.start));
+        return Err(errors);
+    }
+
+    let mut results = Vec::new();
+    for test in &tests {
+        match run_test(&program, test, budget) {
+            Ok(result) => results.push(result),
+            Err(test_errors) => errors.extend(test_errors),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(results)
+    } else {
+        errors.sort_by_key(|err| (err.span.line, err.span.start));
+        errors.dedup();
+        Err(errors)
+    }
+}
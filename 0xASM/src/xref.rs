@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    assembler::{assemble, instruction_size, Assembly, Instruction},
+    instructions::instruction_codes::*,
+    lint::Program,
+    lsp::{symbol_occurrences, Symbol},
+    parser::{parse, Error, Line, Span, Statement},
+    registers::{register_address, register_name},
+    stdlib::link,
+    Word,
+};
+
+/// Name of the routine the program starts in when there is no label at its first instruction
+pub const ENTRY: &str = "<entry>";
+
+/// Definition and use sites of a label or procedure
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    /// `None` for labels that are used but never defined
+    pub definition: Option<Span>,
+    pub uses: Vec<Span>,
+    /// Defined by a module of the standard library, the definition points at its `.include`
+    pub library: bool,
+}
+
+/// A `CALL` or `CALLR` made by a routine, the callee is `None` if a `CALLR` target isn't known
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub caller: Word,
+    pub callee: Option<Word>,
+    /// Span of the call instruction
+    pub span: Span,
+    /// Register jumped through by `CALLR`
+    pub register: Option<Word>,
+}
+
+/// Routines reachable from the entry point, named after the label at their first instruction,
+/// and the calls between them
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallGraph {
+    pub routines: Vec<(Word, String)>,
+    pub calls: Vec<Call>,
+}
+
+/// Collects every label and procedure with its definition and use sites, sorted by name.
+/// Modules linked by `.include` only show up with the procedures the program uses
+pub fn cross_reference(lines: &[Line]) -> Vec<Reference> {
+    let is_include = |span: Span| {
+        matches!(
+            lines.get(span.line),
+            Some(Line {
+                statement: Some(Statement::Include(_)),
+                ..
+            })
+        )
+    };
+
+    let mut references: HashMap<String, Reference> = HashMap::new();
+    for (symbol, span, is_definition) in symbol_occurrences(&link(lines).0) {
+        let name = match symbol {
+            Symbol::Label(name) => name,
+            _ => continue,
+        };
+        // uses within a module are spanned to its .include
+        if !is_definition && is_include(span) {
+            continue;
+        }
+
+        let reference = references.entry(name.clone()).or_insert_with(|| Reference {
+            name,
+            definition: None,
+            uses: Vec::new(),
+            library: false,
+        });
+        if is_definition {
+            reference.definition.get_or_insert(span);
+            reference.library = is_include(span);
+        } else {
+            reference.uses.push(span);
+        }
+    }
+
+    let mut references: Vec<Reference> = references
+        .into_values()
+        .filter(|reference| !reference.library || !reference.uses.is_empty())
+        .collect();
+    references.sort_by(|a, b| a.name.cmp(&b.name));
+    references
+}
+
+/// Formats the cross-reference as one line per label
+pub fn format_cross_reference(references: &[Reference]) -> String {
+    let width = references
+        .iter()
+        .map(|reference| reference.name.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for reference in references {
+        let definition = match reference.definition {
+            Some(span) if reference.library => format!("included at line {}", span.line + 1),
+            Some(span) => format!("defined at line {}", span.line + 1),
+            None => "undefined".to_string(),
+        };
+        let uses = if reference.uses.is_empty() {
+            "never used".to_string()
+        } else {
+            let mut lines: Vec<String> = reference.uses.iter().map(|span| (span.line + 1).to_string()).collect();
+            lines.dedup();
+            format!("used at line {}", lines.join(", "))
+        };
+        output.push_str(&format!(
+            ":{:<width$}  {}, {}\n",
+            reference.name,
+            definition,
+            uses,
+            width = width
+        ));
+    }
+    output
+}
+
+/// Register the instruction writes, results of arithmetic and calls end up in acc
+fn written_register(instr: &Instruction) -> Option<Word> {
+    match instr.code {
+        code if code == MOVR.0 || code == MOVRR.0 || code == MOVMR.0 || code == MOVRPR.0 => Some(instr.operands[1]),
+        code if code == MOVROR.0 => Some(instr.operands[2]),
+        code if code == POP.0 || code == INC.0 || code == DEC.0 || code == NOT.0 => Some(instr.operands[0]),
+        code if (LSF.0..=XORR.0).contains(&code) => Some(instr.operands[0]),
+        code if (ADD.0..=DIVR.0).contains(&code) || code == CALL.0 || code == CALLR.0 => register_address("acc"),
+        _ => None,
+    }
+}
+
+/// Resolves the target of the `CALLR` at index by looking back through the straight-line code in
+/// front of it for the `MOVR` loading the register. Any label on the way could be jumped to with
+/// another value in the register, so the search stops there
+fn callr_target(assembly: &Assembly, index: usize, labels: &HashSet<Word>) -> Option<Word> {
+    let call = &assembly.instructions[index];
+    let mut register = call.operands[0];
+    let mut next = call.address;
+
+    for instr in assembly.instructions[..index].iter().rev() {
+        if labels.contains(&next) || instr.address + instruction_size(instr.operands.len()) != next {
+            return None;
+        }
+        if instr.code == JMP.0 || instr.code == RET.0 || instr.code == IRET.0 || instr.code == HALT.0 {
+            return None;
+        }
+
+        if written_register(instr) == Some(register) {
+            match instr.code {
+                code if code == MOVR.0 => return Some(instr.operands[0]),
+                // follow the register the value was copied from
+                code if code == MOVRR.0 => register = instr.operands[0],
+                _ => return None,
+            }
+        }
+        next = instr.address;
+    }
+
+    None
+}
+
+/// Builds the call graph of the assembled program from its entry point, following `CALL :label`
+/// and every `CALLR` whose target can be resolved
+pub fn call_graph(lines: &[Line], assembly: &Assembly) -> CallGraph {
+    let entry = match assembly.instructions.first() {
+        Some(instr) => instr.address,
+        None => {
+            return CallGraph {
+                routines: Vec::new(),
+                calls: Vec::new(),
+            }
+        }
+    };
+
+    let (linked, _) = link(lines);
+    let mut names: HashMap<Word, &str> = HashMap::new();
+    for line in &linked {
+        match &line.statement {
+            Some(Statement::Label(label)) | Some(Statement::Proc { name: label, .. }) => {
+                if let Some(&addr) = assembly.symbols.labels.get(&label.node) {
+                    names.entry(addr).or_insert(&label.node);
+                }
+            }
+            _ => {}
+        }
+    }
+    let labels: HashSet<Word> = assembly.symbols.labels.values().copied().collect();
+    let indices: HashMap<Word, usize> = assembly
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| (instr.address, i))
+        .collect();
+
+    let mut program = Program::new(assembly);
+    let mut graph = CallGraph {
+        routines: Vec::new(),
+        calls: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    let mut queue = vec![entry];
+    while !queue.is_empty() {
+        let start = queue.remove(0);
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let name = match names.get(&start) {
+            Some(name) => name.to_string(),
+            None if start == entry => ENTRY.to_string(),
+            None => format!("0x{:08X}", start),
+        };
+        graph.routines.push((start, name));
+
+        let mut routine = program.routine(start);
+        routine.sort_by_key(|instr| instr.address);
+        for instr in routine {
+            let (callee, register) = if instr.code == CALL.0 {
+                (Some(instr.operands[0]), None)
+            } else if instr.code == CALLR.0 {
+                (
+                    callr_target(assembly, indices[&instr.address], &labels),
+                    Some(instr.operands[0]),
+                )
+            } else {
+                continue;
+            };
+
+            queue.extend(callee);
+            graph.calls.push(Call {
+                caller: start,
+                callee,
+                span: instr.span,
+                register,
+            });
+        }
+    }
+
+    graph
+}
+
+impl CallGraph {
+    fn name(&self, addr: Word) -> &str {
+        self.routines
+            .iter()
+            .find(|(start, _)| *start == addr)
+            .map_or("?", |(_, name)| name.as_str())
+    }
+
+    /// Every routine followed by the calls it makes, unresolved `CALLR` targets are shown as `?`
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        for (start, name) in &self.routines {
+            output.push_str(&format!("{}\n", name));
+            for call in self.calls.iter().filter(|call| call.caller == *start) {
+                let callee = call.callee.map_or("?", |addr| self.name(addr));
+                match call.register {
+                    Some(register) => output.push_str(&format!(
+                        "    -> {} (CALLR {}, line {})\n",
+                        callee,
+                        register_name(register).unwrap_or("?"),
+                        call.span.line + 1
+                    )),
+                    None => output.push_str(&format!("    -> {} (line {})\n", callee, call.span.line + 1)),
+                }
+            }
+        }
+        output
+    }
+
+    /// Graphviz DOT digraph with one edge per caller and callee, unresolved `CALLR` targets are
+    /// drawn as dashed edges into a single `?` node
+    pub fn to_dot(&self) -> String {
+        let mut output = "digraph calls {\n".to_string();
+        for (_, name) in &self.routines {
+            output.push_str(&format!("    \"{}\";\n", name));
+        }
+        if self.calls.iter().any(|call| call.callee.is_none()) {
+            output.push_str("    \"?\" [shape=plaintext];\n");
+        }
+
+        let mut edges = HashSet::new();
+        for call in &self.calls {
+            if !edges.insert((call.caller, call.callee)) {
+                continue;
+            }
+            match call.callee {
+                Some(callee) => output.push_str(&format!(
+                    "    \"{}\" -> \"{}\";\n",
+                    self.name(call.caller),
+                    self.name(callee)
+                )),
+                None => output.push_str(&format!(
+                    "    \"{}\" -> \"?\" [style=dashed];\n",
+                    self.name(call.caller)
+                )),
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// Parses a whole source file and builds its cross-reference, the program doesn't need to assemble
+pub fn cross_reference_source(source: &str) -> Result<Vec<Reference>, Vec<Error>> {
+    let (lines, errors) = parse(source);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(cross_reference(&lines))
+}
+
+/// Parses and assembles a whole source file and builds its call graph
+pub fn call_graph_source(source: &str) -> Result<CallGraph, Vec<Error>> {
+    let (lines, errors) = parse(source);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let assembly = assemble(&lines)?;
+    Ok(call_graph(&lines, &assembly))
+}
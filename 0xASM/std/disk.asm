@@ -0,0 +1,27 @@
+; std/disk: sector transfers with the HardDrive controller mapped at HARD_DRIVE_ADDRESS with 8 byte sectors
+
+; copies the sector into memory at dest, acc = error code of the controller, 0 on success
+.proc read_sector(sector, dest)
+    MOVROR  fp, sector, r1
+    MOVROR  fp, dest, r3
+    MOVRM   r1, HARD_DRIVE_SECTOR_ADDRESS
+    MOVM    1, HARD_DRIVE_COUNT_ADDRESS
+    MOVM    1, HARD_DRIVE_COMMAND_ADDRESS
+    MOVR    HARD_DRIVE_DATA_ADDRESS, r2
+    MOVR    8, r4
+    LOADR   r2, r4, r3
+    MOVMR   HARD_DRIVE_ERROR_ADDRESS, acc
+.endp
+
+; copies the 8 bytes at src into the sector, acc = error code of the controller, 0 on success
+.proc write_sector(sector, src)
+    MOVROR  fp, sector, r1
+    MOVROR  fp, src, r3
+    MOVRM   r1, HARD_DRIVE_SECTOR_ADDRESS
+    MOVM    1, HARD_DRIVE_COUNT_ADDRESS
+    MOVM    2, HARD_DRIVE_COMMAND_ADDRESS
+    MOVR    HARD_DRIVE_DATA_ADDRESS, r2
+    MOVR    8, r4
+    STORER  r3, r4, r2
+    MOVMR   HARD_DRIVE_ERROR_ADDRESS, acc
+.endp
@@ -0,0 +1,26 @@
+; std/memory: copying and filling memory
+
+; copies count bytes from src to dest, the ranges may overlap
+.proc memcpy(dest, src, count)
+    MOVROR  fp, dest, r1
+    MOVROR  fp, src, r2
+    MOVROR  fp, count, r3
+    STORER  r2, r3, r1
+.endp
+
+; sets count bytes at dest to the lowest byte of value
+.proc memset(dest, value, count)
+    MOVROR  fp, dest, r1
+    MOVROR  fp, count, r3
+    MOVR    1, r4
+    ; STORER copies from memory, so the byte is read from the argument on the stack
+    ADD     value, fp
+    MOVRR   acc, r2
+:memset_loop
+    BREQRW  r3, 0, :memset_end
+    STORER  r2, r4, r1
+    INC     r1
+    DEC     r3
+    JMP     :memset_loop
+:memset_end
+.endp
@@ -0,0 +1,29 @@
+; std/screen: printing to the Screen mapped at 0x000
+
+; prints the zero terminated string str one char per cell starting at cell, acc = cell behind the last char
+.proc print(str, cell)
+    MOVROR  fp, str, r1
+    MOVROR  fp, cell, r2
+    ; there is no store through a register, so the address operand of the MOVRM at
+    ; :print_put is patched with the current cell before every write
+    MOVR    :print_put, r3
+    ADD     5, r3
+    MOVRR   acc, r3
+    MOVR    4, r4
+:print_loop
+    MOVRPR  r1, r5
+    AND     r5, 0xFF
+    BREQRW  r5, 0, :print_end
+    PUSHR   r2
+    ADD     4, sp
+    MOVRR   acc, r6
+    STORER  r6, r4, r3
+    POP     r2
+:print_put
+    MOVRM   r5, 0
+    INC     r1
+    INC     r2
+    JMP     :print_loop
+:print_end
+    MOVRR   r2, acc
+.endp
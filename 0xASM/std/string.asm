@@ -0,0 +1,55 @@
+; std/string: zero terminated strings
+
+; acc = number of bytes in front of the terminating zero
+.proc strlen(str)
+    MOVROR  fp, str, r1
+    MOVRR   r1, r2
+:strlen_loop
+    MOVRPR  r2, r3
+    AND     r3, 0xFF
+    BREQRW  r3, 0, :strlen_end
+    INC     r2
+    JMP     :strlen_loop
+:strlen_end
+    SUBR    r2, r1
+.endp
+
+; writes value as zero terminated decimal string to buffer, acc = number of digits
+.proc itoa(value, buffer)
+    MOVROR  fp, value, r1
+    MOVROR  fp, buffer, r8
+    MOVR    1, r7
+    ; count the digits
+    MOVRR   r1, r3
+    MOVR    1, r4
+:itoa_count
+    DIV     r3, 10
+    BREQ    0, :itoa_digits
+    MOVRR   acc, r3
+    INC     r4
+    JMP     :itoa_count
+:itoa_digits
+    ; write the terminator, then the digits from the last to the first
+    ADDR    r8, r4
+    MOVRR   acc, r2
+    MOVR    0, r5
+:itoa_loop
+    ; STORER copies from memory, so the byte is written through the stack
+    PUSHR   r5
+    ADD     4, sp
+    MOVRR   acc, r6
+    STORER  r6, r7, r2
+    POP     r5
+    BREQRR  r2, r8, :itoa_end
+    DEC     r2
+    DIV     r1, 10
+    MOVRR   acc, r3
+    MULT    10, r3
+    SUBR    r1, acc
+    ADD     '0', acc
+    MOVRR   acc, r5
+    MOVRR   r3, r1
+    JMP     :itoa_loop
+:itoa_end
+    MOVRR   r4, acc
+.endp
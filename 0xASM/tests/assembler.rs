@@ -0,0 +1,53 @@
+use asm::assembler::assemble_source;
+
+#[test]
+fn encoding() {
+    let source = "\
+; comment only line
+.const VALUE, 0x10
+:start
+MOVR VALUE, r2 ; inline comment
+    PUSH 'ab'
+JMP :start
+HALT
+";
+    let assembly = assemble_source(source).unwrap();
+
+    assert_eq!(
+        assembly.image,
+        vec![
+            0x10, 0x10, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // MOVR 0x10, r2
+            0x15, 0x61, 0x62, 0x00, 0x00, // PUSH 'ab'
+            0x01, 0x00, 0x00, 0x00, 0x00, // JMP :start
+            0xFF, // HALT
+        ]
+    );
+    assert_eq!(assembly.symbols.labels["start"], 0);
+    assert_eq!(assembly.symbols.constants["VALUE"], 0x10);
+}
+
+#[test]
+fn errors() {
+    let errors = assemble_source("MOVR 0xZZ, r1\nFOO\nJMP :nowhere\nMOVR 1, r9\nPUSH 1, 2").unwrap_err();
+    let lines: Vec<usize> = errors.iter().map(|err| err.span.line).collect();
+
+    assert_eq!(lines, vec![0, 1, 2, 3, 4]);
+    assert_eq!(errors[1].to_string(), "Error at line 2: Unknown instruction: FOO");
+
+    let errors = assemble_source(".org 0xFFFFFFFE\nHALT\nMOVR 1, r1").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "Error at line 3: Program runs past the end of the address space"
+    );
+}
+
+#[test]
+fn builtin_constants() {
+    let image = |source: &str| assemble_source(source).unwrap().image;
+
+    assert_eq!(image("MOVR SCREEN_BOLD, r1"), image("MOVR 0xF4808082, r1"));
+    assert_eq!(image("MOVR SCREEN_MOVE_CURSOR, r1"), image("MOVR 0xF4850000, r1"));
+    assert_eq!(image("MOVMR HARD_DRIVE_ERROR_ADDRESS, r1"), image("MOVMR 0x11040, r1"));
+    // constants of the program shadow the built-in ones
+    assert_eq!(image(".const SCREEN_BOLD, 1\nMOVR SCREEN_BOLD, r1"), image("MOVR 1, r1"));
+}
@@ -0,0 +1,44 @@
+use asm::formatter::format_source;
+
+const SOURCE: &str = "
+
+; setup
+.const   max,0x00ff
+:start
+movr 0xaf,R1   ;load
+    pushr r1
+
+
+  ; loop body
+:loop
+BRNQRW r1 , 0B0101 , :loop
+halt ;done
+";
+
+const FORMATTED: &str = "\
+; setup
+.const max, 0xFF
+:start
+    MOVR    0xAF, r1                    ; load
+    PUSHR   r1
+
+    ; loop body
+:loop
+    BRNQRW  r1, 0b101, :loop
+    HALT                                ; done
+";
+
+#[test]
+fn canonical_format() {
+    assert_eq!(format_source(SOURCE).unwrap(), FORMATTED);
+}
+
+#[test]
+fn idempotent() {
+    assert_eq!(format_source(FORMATTED).unwrap(), FORMATTED);
+}
+
+#[test]
+fn refuses_invalid_source() {
+    assert!(format_source("MOVR 0xZZ, r1").is_err());
+}
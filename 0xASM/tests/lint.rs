@@ -0,0 +1,90 @@
+use asm::lint::lint_source;
+
+fn messages(source: &str) -> Vec<(usize, String)> {
+    lint_source(source)
+        .unwrap()
+        .into_iter()
+        .map(|lint| (lint.span.line + 1, lint.message))
+        .collect()
+}
+
+#[test]
+fn clean_program() {
+    let source = "\
+PUSH 0x2A
+PUSH 1
+CALL :routine
+HALT
+:routine
+PUSHR r1
+POP r2
+RET
+";
+    assert!(messages(source).is_empty());
+}
+
+#[test]
+fn control_flow() {
+    let source = "\
+JMP :end
+MOVR 1, r1
+:unused
+:end
+PUSH 0
+CALL :forever
+:forever
+JMP :forever
+";
+    assert_eq!(
+        messages(source),
+        vec![
+            (1, "Program never reaches HALT".to_string()),
+            (2, "Unreachable code".to_string()),
+            (3, "Label :unused is never referenced".to_string()),
+            (8, "Subroutine :forever never reaches RET".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn stack_balance() {
+    let source = "\
+CALL :routine
+HALT
+:routine
+PUSHR r1
+BRBS 0, :skip
+POP r1
+:skip
+RET
+";
+    assert_eq!(
+        messages(source),
+        vec![
+            (1, "Call without an argument count on the stack".to_string()),
+            (8, "Push/pop imbalance, reached with 1 and 0 value(s) on the stack".to_string()),
+            (8, "RET with 1 value(s) left on the stack".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn device_writes() {
+    let source = "\
+MOVM 0x41, 0x3FC
+MOVM 0x41, 0x1104E
+LOAD r1, r2, 0x10
+MOVM 0x41, 0x11006
+LOAD r1, r2, 0x11050
+HALT
+";
+    assert_eq!(
+        messages(source),
+        vec![
+            (2, "Word write to 0x0001104E crosses the end of the HardDrive range".to_string()),
+            (3, "Range write to 0x00000010, the Screen only supports word writes".to_string()),
+            (4, "Word write to 0x00011006 crosses the end of the InterruptController range".to_string()),
+            (5, "Range write to 0x00011050, the Dma only supports word writes".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,81 @@
+use asm::lsp::{completion, definition, diagnostics, hover, references};
+use lsp_types::{DiagnosticSeverity, HoverContents, Position, Range};
+
+const SOURCE: &str = "\
+.const COUNT, 3
+:loop
+DEC r1
+BRNQ COUNT, :loop
+JMP :loop
+";
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[test]
+fn diagnostics_as_you_type() {
+    // the endless loop never reaches HALT, which is only a warning
+    let warnings = diagnostics(SOURCE);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Some(DiagnosticSeverity::WARNING));
+
+    // an origin close to the end of the address space is an error instead of a crash
+    let errors = diagnostics(".org 0xFFFFFFFE\nMOVR 1, r1");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].range, range(1, 0, 4));
+
+    let diagnostics = diagnostics("MOVR 1, r1\nJMP :missing");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range, range(1, 4, 12));
+}
+
+#[test]
+fn symbols() {
+    assert_eq!(definition(SOURCE, Position::new(3, 14)), Some(range(1, 1, 5)));
+    assert_eq!(definition(SOURCE, Position::new(3, 6)), Some(range(0, 7, 12)));
+    assert_eq!(
+        references(SOURCE, Position::new(4, 6), false),
+        vec![range(3, 12, 17), range(4, 4, 9)]
+    );
+    assert_eq!(references(SOURCE, Position::new(1, 2), true).len(), 3);
+}
+
+#[test]
+fn hover_and_completion() {
+    let contents = match hover(SOURCE, Position::new(2, 1)).unwrap().contents {
+        HoverContents::Markup(markup) => markup.value,
+        _ => unreachable!(),
+    };
+    assert!(contents.contains("DEC r1"));
+    assert!(contents.contains("0x2B"));
+
+    let mnemonics = completion("MO", Position::new(0, 2));
+    assert!(mnemonics.iter().any(|item| item.label == "MOVROR"));
+
+    let operands = completion(SOURCE, Position::new(2, 4));
+    assert!(operands.iter().any(|item| item.label == "r8"));
+    assert!(operands.iter().any(|item| item.label == "COUNT"));
+}
+
+#[test]
+fn hover_duplicate_proc() {
+    let source = "\
+.proc f(a)
+    MOVROR fp, a, r1
+.endp
+.proc f(b)
+.endp
+";
+    // the second definition replaced the arguments of the first, which must not bring the server down
+    assert_eq!(hover(source, Position::new(1, 16)), None);
+}
+
+#[test]
+fn utf16_positions() {
+    // '😀' takes 4 bytes but 2 UTF-16 code units, the label behind it starts at character 11
+    let source = ":loop\nBRNQ '😀', :loop\n";
+    assert_eq!(definition(source, Position::new(1, 15)), Some(range(0, 1, 5)));
+    assert_eq!(references(source, Position::new(1, 15), false), vec![range(1, 11, 16)]);
+    assert!(completion(source, Position::new(1, 15)).iter().any(|item| item.label == "r1"));
+}
@@ -0,0 +1,74 @@
+use asm::{assembler::assemble_source, formatter::format_source, lint::lint_source};
+
+const SOURCE: &str = "\
+invoke add, 0x10, r2
+HALT
+.proc add(a, b)
+    MOVROR fp, a, r1
+    MOVROR fp, b, r3
+.endp
+";
+
+#[test]
+fn invoke_and_arguments() {
+    let assembly = assemble_source(SOURCE).unwrap();
+    let instructions: Vec<(u8, Vec<u32>)> = assembly
+        .instructions
+        .iter()
+        .map(|instr| (instr.code, instr.operands.clone()))
+        .collect();
+
+    assert_eq!(
+        instructions,
+        vec![
+            (0x16, vec![0x04]),             // PUSHR r2
+            (0x15, vec![0x10]),             // PUSH 0x10
+            (0x15, vec![2]),                // PUSH 2
+            (0x02, vec![21]),               // CALL :add
+            (0xFF, vec![]),                 // HALT
+            (0x18, vec![0x30, 0x30, 0x00]), // MOVROR fp, a, r1
+            (0x18, vec![0x30, 0x34, 0x08]), // MOVROR fp, b, r3
+            (0x04, vec![]),                 // RET
+        ]
+    );
+    assert_eq!(assembly.symbols.labels["add"], 21);
+    assert_eq!(assembly.symbols.procs["add"], vec!["a", "b"]);
+    assert_eq!(lint_source(SOURCE).unwrap(), vec![]);
+}
+
+#[test]
+fn formatting() {
+    let source = "INVOKE   add,0x10 , r2\nhalt\n.proc  add( a,b )\nmovror fp, a, r1\n.endp\n";
+
+    assert_eq!(
+        format_source(source).unwrap(),
+        "    INVOKE  add, 0x10, r2\n    HALT\n.proc add(a, b)\n    MOVROR  fp, a, r1\n.endp\n"
+    );
+}
+
+#[test]
+fn errors() {
+    let source = "\
+invoke add, 1
+invoke missing
+.proc add(a, a)
+.proc inner()
+.endp
+.endp
+.proc open()
+";
+    let errors = assemble_source(source).unwrap_err();
+    let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Error at line 1: Procedure add expects 2 argument(s) but got 1",
+            "Error at line 2: Unknown procedure: missing",
+            "Error at line 3: Duplicate argument: a",
+            "Error at line 4: Procedure inner can't be nested in add",
+            "Error at line 6: .endp without .proc",
+            "Error at line 7: Missing .endp for procedure open",
+        ]
+    );
+}
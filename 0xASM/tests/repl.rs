@@ -0,0 +1,84 @@
+use std::{env, fs};
+
+use asm::repl::{Repl, STEP_LIMIT};
+
+fn eval(repl: &mut Repl, input: &str) -> Vec<String> {
+    repl.eval(input).unwrap()
+}
+
+#[test]
+fn register_deltas_and_flags() {
+    let mut repl = Repl::new();
+
+    assert_eq!(eval(&mut repl, "MOVR 5, r1"), vec!["r1   0x00000000 -> 0x00000005", "sr   Z=0 C=0"]);
+    assert_eq!(eval(&mut repl, "SUB r1, 5"), vec!["sr   Z=1 C=1"]);
+    assert_eq!(eval(&mut repl, "ADD 0xFFFFFFFF, r1"), vec!["acc  0x00000000 -> 0x00000004", "sr   Z=0 C=1"]);
+    assert_eq!(eval(&mut repl, "; comment only"), Vec::<String>::new());
+    assert_eq!(repl.address, 0x408 + 3 * 9);
+}
+
+#[test]
+fn touched_memory() {
+    let mut repl = Repl::new();
+
+    assert_eq!(eval(&mut repl, "MOVM 'AB', 0x2000"), vec!["sr   Z=0 C=0", "0x00002000: 41 42 00 00"]);
+    assert_eq!(
+        eval(&mut repl, "PUSH 0x12345678"),
+        vec!["sp   0x0000FFFB -> 0x0000FFF7", "sr   Z=0 C=0", "0x0000FFFB: 78 56 34 12"]
+    );
+}
+
+#[test]
+fn labels_and_constants() {
+    let mut repl = Repl::new();
+
+    assert_eq!(eval(&mut repl, ".label target 0x500"), vec![":target = 0x00000500"]);
+    assert_eq!(eval(&mut repl, ".const VALUE, 7"), Vec::<String>::new());
+    assert_eq!(eval(&mut repl, "MOVR :target, r2")[0], "r2   0x00000000 -> 0x00000500");
+    assert_eq!(eval(&mut repl, "MOVR VALUE, r2")[0], "r2   0x00000500 -> 0x00000007");
+
+    // a jump that never comes back is stopped
+    assert_eq!(eval(&mut repl, ":loop"), Vec::<String>::new());
+    assert_eq!(repl.symbols.labels["loop"], 0x408 + 18);
+    assert_eq!(
+        eval(&mut repl, "JMP :loop").last().unwrap(),
+        &format!("Stopped after {} instructions at pc 0x{:08X}", STEP_LIMIT, 0x408 + 18)
+    );
+
+    assert_eq!(eval(&mut repl, ".label 1abc"), vec!["Error: Invalid label name: 1abc"]);
+    assert_eq!(eval(&mut repl, "MOVR :nothing, r1"), vec!["Error: Unknown label: nothing"]);
+}
+
+#[test]
+fn load_and_call() {
+    let path = env::temp_dir().join("0xasm_repl_square.asm");
+    fs::write(
+        &path,
+        "\
+.org 0x1000
+.proc square(n)
+    MOVROR  fp, n, r1
+    MULTR   r1, r1
+.endp
+",
+    )
+    .unwrap();
+
+    let mut repl = Repl::new();
+    assert_eq!(
+        eval(&mut repl, &format!(".load {}", path.display())),
+        vec!["Loaded 23 bytes at 0x00001000 with 1 label(s)"]
+    );
+
+    // the call runs until it returns behind the line, r1 is restored by RET
+    let output = eval(&mut repl, "INVOKE square, 12");
+    assert_eq!(output[0], "acc  0x00000000 -> 0x00000090");
+    assert_eq!(repl.address, 0x1017 + 5 * 3);
+
+    assert_eq!(eval(&mut repl, "HALT"), vec!["sr   Z=0 C=0", "Halted"]);
+    assert_eq!(eval(&mut repl, "POP r1").last().unwrap(), "Error: [CPU] Stack underflow (pc 0x00001027, instruction 0x05)");
+
+    assert_eq!(eval(&mut repl, ".reset"), vec!["Machine reset"]);
+    assert_eq!(eval(&mut repl, "INVOKE square, 12"), vec!["Error: Unknown procedure: square"]);
+    assert_eq!(repl.eval(".quit"), None);
+}
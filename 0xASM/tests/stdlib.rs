@@ -0,0 +1,133 @@
+use asm::{
+    assembler::assemble_source, formatter::format_source, lint::lint_source, parser::Span,
+    registers::register_address, stdlib::MODULES,
+};
+use vm::{
+    cpu::CPU,
+    machine::{MachineBuilder, LOAD_ADDRESS, SCREEN_ADDRESS, SCREEN_END},
+    memory::Memory,
+};
+
+/// Runs the program on the standard 0xVM machine until HALT. Plain memory takes the place of the
+/// Screen so that printed cells can be read back
+fn run(source: &str) -> CPU {
+    let assembly = assemble_source(source).unwrap();
+    assert_eq!(assembly.origin, LOAD_ADDRESS);
+
+    let mut machine = MachineBuilder::standard()
+        .device(Box::new(Memory::new(SCREEN_END - SCREEN_ADDRESS)), SCREEN_ADDRESS, SCREEN_END)
+        .image(assembly.image)
+        .build();
+    machine.run().unwrap();
+    machine.cpu
+}
+
+fn acc(cpu: &CPU) -> u32 {
+    cpu.get_reg(register_address("acc").unwrap())
+}
+
+#[test]
+fn modules_are_formatted() {
+    for (name, source) in MODULES {
+        assert_eq!(&format_source(source).unwrap(), source, "std/{} is not formatted", name);
+    }
+}
+
+#[test]
+fn memory() {
+    let cpu = run("\
+.org 0x408
+.include \"std/memory\"
+    MOVM    'abcd', 0x2000
+    MOVM    'efgh', 0x2004
+    INVOKE  memcpy, 0x3000, 0x2001, 6
+    INVOKE  memset, 0x3002, 0x12A, 3
+    INVOKE  memset, 0x3008, 0, 0
+    HALT
+");
+
+    assert_eq!(cpu.memory_mapper.get_range(0x3000, 8).unwrap(), b"bc***g\0\0".to_vec());
+}
+
+#[test]
+fn string() {
+    let cpu = run("\
+.org 0x408
+.include \"std/string\"
+    INVOKE  itoa, 4294967295, 0x2000
+    MOVRM   acc, 0x2100
+    INVOKE  itoa, 0, 0x2010
+    MOVRM   acc, 0x2104
+    INVOKE  strlen, 0x2000
+    HALT
+");
+
+    assert_eq!(cpu.memory_mapper.get_range(0x2000, 11).unwrap(), b"4294967295\0".to_vec());
+    assert_eq!(cpu.memory_mapper.get_range(0x2010, 2).unwrap(), b"0\0".to_vec());
+    assert_eq!(cpu.memory_mapper.get_word(0x2100).unwrap(), 10);
+    assert_eq!(cpu.memory_mapper.get_word(0x2104).unwrap(), 1);
+    assert_eq!(acc(&cpu), 10);
+}
+
+#[test]
+fn screen() {
+    let cpu = run("\
+.org 0x408
+.include \"std/screen\"
+.include \"std/string\"
+    INVOKE  itoa, 42, 0x2000
+    INVOKE  print, 0x2000, 0x10
+    MOVM    '!', 0x2000
+    INVOKE  print, 0x2000, acc
+    HALT
+");
+
+    assert_eq!(cpu.memory_mapper.get_range(0x10, 4).unwrap(), b"42!\0".to_vec());
+    assert_eq!(acc(&cpu), 0x13);
+}
+
+#[test]
+fn disk() {
+    let cpu = run("\
+.org 0x408
+.include \"std/disk\"
+    MOVM    'sect', 0x2000
+    MOVM    'or 5', 0x2004
+    INVOKE  write_sector, 5, 0x2000
+    INVOKE  read_sector, 0, 0x3000
+    INVOKE  read_sector, 5, 0x3008
+    INVOKE  read_sector, 128, 0x3010
+    HALT
+");
+
+    // a sector past the end of the drive is reported by the controller
+    assert_eq!(acc(&cpu), 2);
+
+    assert_eq!(cpu.memory_mapper.get_range(0x3000, 16).unwrap(), b"\0\0\0\0\0\0\0\0sector 5".to_vec());
+}
+
+#[test]
+fn linking() {
+    let source = "\
+.include \"std/memory\"
+.include \"std/memory\"
+    INVOKE  memset, 0x2000, 0, 4
+    HALT
+";
+    let assembly = assemble_source(source).unwrap();
+
+    // the module is linked once behind the program
+    assert_eq!(assembly.symbols.labels["memcpy"], 26);
+    assert_eq!(assembly.instructions[6].span, Span::new(0, 10, 20));
+    assert_eq!(lint_source(source).unwrap(), vec![]);
+
+    let errors = assemble_source(".include \"std/nothing\"\nHALT\n.org 0x408").unwrap_err();
+    let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Error at line 1: Unknown module: std/nothing",
+            "Error at line 3: .org must come before any code"
+        ]
+    );
+}
@@ -0,0 +1,122 @@
+use asm::{
+    assembler::assemble_source,
+    formatter::format_source,
+    lint::lint_source,
+    testing::{run_tests, TestResult, DEFAULT_BUDGET},
+};
+
+const SOURCE: &str = "\
+.org 0x408
+.include \"std/memory\"
+    HALT
+
+; acc = r1 * r1, RET restores r1
+:square
+    MULTR   r1, r1
+    RET
+
+.test \"square\"
+    MOVR    12, r1
+    PUSH    0
+    CALL    :square
+    .expect acc == 144
+    .expect r1 == 12
+    MOVR    0, r1
+    PUSH    0
+    CALL    :square
+    .expect acc == 0
+.endt
+
+.test \"memset\"
+    INVOKE  memset, 0x2001, 'A', 2
+    .expect [0x2000] == 0x414100
+    .expect [0x2001] == 'AA'
+.endt
+";
+
+fn result(name: &str, failures: &[&str]) -> TestResult {
+    TestResult {
+        name: name.to_string(),
+        failures: failures.iter().map(|failure| failure.to_string()).collect(),
+    }
+}
+
+fn errors(source: &str) -> Vec<String> {
+    run_tests(source, DEFAULT_BUDGET)
+        .unwrap_err()
+        .iter()
+        .map(|err| err.to_string())
+        .collect()
+}
+
+#[test]
+fn passing_tests() {
+    assert_eq!(
+        run_tests(SOURCE, DEFAULT_BUDGET).unwrap(),
+        vec![result("square", &[]), result("memset", &[])]
+    );
+
+    // tests are left out of the program and don't upset the linter
+    let program = assemble_source("HALT\n.test \"t\"\n    HALT\n.endt\n").unwrap();
+    assert_eq!(program.image, vec![0xFF]);
+    assert_eq!(lint_source(SOURCE).unwrap(), vec![]);
+    assert_eq!(format_source(SOURCE).unwrap(), SOURCE);
+}
+
+#[test]
+fn failing_tests() {
+    let source = "\
+.const EXPECTED, 6
+.test \"values\"
+    MOVR    5, r1
+    MOVM    'AB', 0x2000
+    .expect r1 == EXPECTED
+    .expect [0x2000] == 'AB'
+    .expect [0x2000] == 'A'
+.endt
+.test \"loop\"
+:forever
+    JMP     :forever
+.endt
+.test \"stack\"
+    POP     r1
+.endt
+.test \"halt\"
+    JMP     :stop
+.endt
+:stop
+    HALT
+";
+
+    assert_eq!(
+        run_tests(source, 1000).unwrap(),
+        vec![
+            result("values", &[
+                "line 5: r1 == EXPECTED, expected 0x00000006 but got 0x00000005",
+                "line 7: [0x2000] == 'A', expected 0x00000041 but got 0x00004241",
+            ]),
+            result("loop", &["Instruction budget of 1000 exhausted at pc 0x00000408"]),
+            result("stack", &["[CPU] Stack underflow (pc 0x00000408, instruction 0x05)"]),
+            result("halt", &["Halted at 0x0000040E before the end of the test"]),
+        ]
+    );
+}
+
+#[test]
+fn invalid_tests() {
+    assert_eq!(
+        errors(".test \"a\"\n.test \"b\"\n.endt\n.endt\n.expect r1 == 1\n.test \"b\"\n.expect 5 == 1\n"),
+        vec![
+            "Error at line 2: Test b can't be nested in a",
+            "Error at line 4: .endt without .test",
+            "Error at line 5: .expect outside of a .test block",
+            "Error at line 6: Duplicate test: b",
+            "Error at line 6: Missing .endt for test b",
+            "Error at line 7: Expected '.expect register == value' or '.expect [address] == value'",
+        ]
+    );
+    assert_eq!(
+        errors(".test \"a\"\n    .expect [ADDRESS] == 1\n.endt\n.test \"b\"\n    CALL :nothing\n.endt\n"),
+        vec!["Error at line 2: Unknown constant: ADDRESS", "Error at line 5: Unknown label: nothing"]
+    );
+}
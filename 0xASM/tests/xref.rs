@@ -0,0 +1,87 @@
+use asm::xref::{call_graph_source, cross_reference_source, format_cross_reference};
+
+#[test]
+fn cross_reference() {
+    let source = "\
+.include \"std/memory\"
+:start
+PUSH 0
+CALL :square
+INVOKE memset, 0x2000, 0, 8
+JMP :start
+:square
+RET
+:unused
+";
+    let references = cross_reference_source(source).unwrap();
+    assert_eq!(
+        format_cross_reference(&references),
+        "\
+:memset  included at line 1, used at line 5
+:square  defined at line 7, used at line 4
+:start   defined at line 2, used at line 6
+:unused  defined at line 9, never used
+"
+    );
+}
+
+#[test]
+fn call_graph() {
+    let source = "\
+PUSH 0
+CALL :first
+MOVR :second, r2
+MOVRR r2, r1
+CALLR r1
+CALLR r3
+HALT
+:first
+PUSH 0
+CALL :second
+RET
+:second
+RET
+";
+    let graph = call_graph_source(source).unwrap();
+    assert_eq!(
+        graph.to_text(),
+        "\
+<entry>
+    -> first (line 2)
+    -> second (CALLR r1, line 5)
+    -> ? (CALLR r3, line 6)
+first
+    -> second (line 10)
+second
+"
+    );
+    assert_eq!(
+        graph.to_dot(),
+        "\
+digraph calls {
+    \"<entry>\";
+    \"first\";
+    \"second\";
+    \"?\" [shape=plaintext];
+    \"<entry>\" -> \"first\";
+    \"<entry>\" -> \"second\";
+    \"<entry>\" -> \"?\" [style=dashed];
+    \"first\" -> \"second\";
+}
+"
+    );
+}
+
+#[test]
+fn callr_after_label_is_unresolved() {
+    let source = "\
+MOVR :routine, r1
+:again
+CALLR r1
+HALT
+:routine
+RET
+";
+    let graph = call_graph_source(source).unwrap();
+    assert_eq!(graph.to_text(), "<entry>\n    -> ? (CALLR r1, line 3)\n");
+}
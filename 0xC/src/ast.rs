@@ -0,0 +1,134 @@
+use std::fmt;
+
+use crate::Word;
+
+/// Every value is a single word, so pointer arithmetic scales by 4
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Void,
+    Pointer(Box<Type>),
+    /// Element type and length
+    Array(Box<Type>, Word),
+}
+
+impl Type {
+    /// Number of stack or data words taken by a variable of the type
+    pub fn words(&self) -> Word {
+        match self {
+            Type::Array(element, len) => element.words() * len,
+            _ => 1,
+        }
+    }
+
+    /// Type pointed at by pointers and arrays, arrays decay into pointers to their elements
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Pointer(pointee) | Type::Array(pointee, _) => Some(pointee),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Void => write!(f, "void"),
+            Type::Pointer(pointee) => write!(f, "{}*", pointee),
+            Type::Array(element, len) => write!(f, "{}[{}]", element, len),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    Deref,
+    AddressOf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Number(Word),
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub ty: Type,
+    pub init: Option<Expr>,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    Declare(Declaration),
+    Expr(Expr),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Return(Option<Expr>, usize),
+    Break(usize),
+    Continue(usize),
+    Block(Vec<Stmt>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub return_type: Type,
+    pub params: Vec<(String, Type)>,
+    pub body: Vec<Stmt>,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    pub globals: Vec<Declaration>,
+    pub functions: Vec<Function>,
+}
@@ -0,0 +1,893 @@
+use std::collections::HashMap;
+
+use vm::machine::LOAD_ADDRESS;
+
+use crate::{
+    ast::{BinaryOp, Declaration, Expr, ExprKind, Function, Program, Stmt, Type, UnaryOp},
+    Error, Word,
+};
+
+/// Globals are placed from this address upwards, between the program and the stack
+pub const DATA_ADDRESS: Word = 0xC000;
+
+/// r1..r8, `CALL` saves and `RET` restores all of them
+const REGISTER_COUNT: usize = 8;
+
+type Register = usize;
+
+fn register(reg: Register) -> String {
+    format!("r{}", reg)
+}
+
+/// Names the assembler reads as registers, they can't be used for functions, globals and parameters
+fn is_register_name(name: &str) -> bool {
+    let lowercase = name.to_lowercase();
+    ["pc", "acc", "sr", "sp", "fp", "ivt"].contains(&lowercase.as_str())
+        || (lowercase.starts_with('r') && lowercase[1..].chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Register-register branch jumping if the comparison holds
+fn branch(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Eq => "BREQRR",
+        BinaryOp::Ne => "BRNQRR",
+        BinaryOp::Lt => "BRLTRR",
+        BinaryOp::Gt => "BRGTRR",
+        BinaryOp::Le => "BRLTERR",
+        BinaryOp::Ge => "BRGTERR",
+        _ => unreachable!("not a comparison"),
+    }
+}
+
+fn inverse(op: BinaryOp) -> BinaryOp {
+    match op {
+        BinaryOp::Eq => BinaryOp::Ne,
+        BinaryOp::Ne => BinaryOp::Eq,
+        BinaryOp::Lt => BinaryOp::Ge,
+        BinaryOp::Gt => BinaryOp::Le,
+        BinaryOp::Le => BinaryOp::Gt,
+        BinaryOp::Ge => BinaryOp::Lt,
+        _ => unreachable!("not a comparison"),
+    }
+}
+
+/// Value of a constant expression, used for global initializers
+fn const_value(expr: &Expr) -> Option<Word> {
+    match &expr.kind {
+        ExprKind::Number(val) => Some(*val),
+        ExprKind::Unary(UnaryOp::Neg, operand) => Some(const_value(operand)?.wrapping_neg()),
+        ExprKind::Unary(UnaryOp::BitNot, operand) => Some(!const_value(operand)?),
+        ExprKind::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (const_value(lhs)?, const_value(rhs)?);
+            match op {
+                BinaryOp::Add => Some(lhs.wrapping_add(rhs)),
+                BinaryOp::Sub => Some(lhs.wrapping_sub(rhs)),
+                BinaryOp::Mul => Some(lhs.wrapping_mul(rhs)),
+                BinaryOp::Shl => Some(lhs.wrapping_shl(rhs)),
+                BinaryOp::Shr => Some(lhs.wrapping_shr(rhs)),
+                BinaryOp::And => Some(lhs & rhs),
+                BinaryOp::Or => Some(lhs | rhs),
+                BinaryOp::Xor => Some(lhs ^ rhs),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Number of local words a function reserves for the declarations of its body
+fn local_words(stmts: &[Stmt]) -> Word {
+    stmts
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Declare(decl) => decl.ty.words(),
+            Stmt::If(_, then, otherwise) => {
+                local_words(std::slice::from_ref(then))
+                    + otherwise
+                        .as_ref()
+                        .map_or(0, |stmt| local_words(std::slice::from_ref(stmt)))
+            }
+            Stmt::While(_, body) => local_words(std::slice::from_ref(body)),
+            Stmt::Block(stmts) => local_words(stmts),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[derive(Clone, Debug)]
+enum Location {
+    /// Stack slot of the lowest word, slot n is at `fp - 4n`
+    Local(Word),
+    /// Procedure argument, resolved by the assembler to its offset from `fp`
+    Param(String),
+    /// Constant holding the address in the data area
+    Global(String),
+}
+
+#[derive(Clone, Debug)]
+struct Variable {
+    ty: Type,
+    location: Location,
+}
+
+struct Generator<'a> {
+    output: Vec<String>,
+    labels: usize,
+    functions: HashMap<&'a str, &'a Function>,
+    globals: HashMap<String, Variable>,
+
+    // state of the function being generated
+    scopes: Vec<HashMap<String, Variable>>,
+    /// Local slots handed out so far
+    slots: Word,
+    /// Words pushed since the function was entered
+    depth: Word,
+    /// (continue, break) labels of the enclosing loops
+    loops: Vec<(String, String)>,
+    return_type: Type,
+
+    // register allocator, values live in the order they were computed and are released youngest first.
+    // When all registers are taken the oldest value held in a register is spilled onto the stack, so the
+    // spilled values are always the oldest ones and are reloaded in the order the stack returns them
+    free: Vec<Register>,
+    values: Vec<Option<Register>>,
+}
+
+impl<'a> Generator<'a> {
+    fn emit(&mut self, mnemonic: &str, operands: &[&str]) {
+        let line = format!("    {:<8}{}", mnemonic, operands.join(", "));
+        self.output.push(line.trim_end().to_string());
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!("_L{}", self.labels)
+    }
+
+    fn place(&mut self, label: &str) {
+        self.output.push(format!(":{}", label));
+    }
+
+    fn push(&mut self, reg: Register) {
+        self.emit("PUSHR", &[&register(reg)]);
+        self.depth += 1;
+    }
+
+    fn pop(&mut self, reg: Register) {
+        self.emit("POP", &[&register(reg)]);
+        self.depth -= 1;
+    }
+
+    /// Allocates a register for a new value, spilling the oldest value held in a register if none is free
+    fn alloc(&mut self) -> Register {
+        let reg = match self.free.pop() {
+            Some(reg) => reg,
+            None => {
+                let oldest = self.values.iter().position(Option::is_some).unwrap();
+                let reg = self.values[oldest].take().unwrap();
+                self.push(reg);
+                reg
+            }
+        };
+
+        self.values.push(Some(reg));
+        reg
+    }
+
+    /// Allocates the given free register for a new value
+    fn claim(&mut self, reg: Register) {
+        self.free.retain(|free| *free != reg);
+        self.values.push(Some(reg));
+    }
+
+    /// Register of the value, reloading it if it was spilled
+    fn reg(&mut self, value: usize) -> Register {
+        if let Some(reg) = self.values[value] {
+            return reg;
+        }
+
+        // spilled values are older than the ones in registers, so this is the top of the stack
+        let reg = self.free.pop().unwrap();
+        self.pop(reg);
+        self.values[value] = Some(reg);
+        reg
+    }
+
+    fn top(&self) -> usize {
+        self.values.len() - 1
+    }
+
+    /// Releases the youngest value
+    fn release(&mut self) {
+        let value = self.top();
+        let reg = self.reg(value);
+        self.values.pop();
+        self.free.push(reg);
+    }
+
+    /// Spills every value held in a register, so control flow joining afterwards
+    /// finds the stack in the same state on every path
+    fn spill_all(&mut self) {
+        for value in 0..self.values.len() {
+            if let Some(reg) = self.values[value].take() {
+                self.push(reg);
+                self.free.push(reg);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str, line: usize) -> Result<Variable, Error> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.get(name))
+            .cloned()
+            .ok_or_else(|| Error::new(line, format!("Unknown variable: {}", name)))
+    }
+
+    fn declare(&mut self, name: &str, variable: Variable, line: usize) -> Result<(), Error> {
+        if name.starts_with('_') {
+            return Err(Error::new(
+                line,
+                format!("Names starting with '_' are reserved: {}", name),
+            ));
+        }
+
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.insert(name.to_string(), variable).is_some() {
+            return Err(Error::new(line, format!("Duplicate variable: {}", name)));
+        }
+        Ok(())
+    }
+
+    fn check_value(ty: &Type, line: usize) -> Result<(), Error> {
+        match ty {
+            Type::Void => Err(Error::new(line, "Void value used in an expression".to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Offset of a local slot from the current stack pointer
+    fn local_offset(&self, slot: Word) -> String {
+        format!("{}", 4 * (self.depth - slot))
+    }
+
+    /// Pushes the address of an assignable expression, returning the type of the object at that address
+    fn address(&mut self, expr: &Expr) -> Result<Type, Error> {
+        match &expr.kind {
+            ExprKind::Variable(name) => {
+                let variable = self.lookup(name, expr.line)?;
+                let reg = register(self.alloc());
+                match &variable.location {
+                    Location::Local(slot) => {
+                        let offset = self.local_offset(*slot);
+                        self.emit("ADD", &[&offset, "sp"]);
+                        self.emit("MOVRR", &["acc", &reg]);
+                    }
+                    Location::Param(param) => {
+                        self.emit("ADD", &[param, "fp"]);
+                        self.emit("MOVRR", &["acc", &reg]);
+                    }
+                    Location::Global(global) => self.emit("MOVR", &[global, &reg]),
+                }
+                Ok(variable.ty)
+            }
+            ExprKind::Unary(UnaryOp::Deref, pointer) => {
+                let ty = self.expr(pointer)?;
+                ty.pointee()
+                    .cloned()
+                    .ok_or_else(|| Error::new(expr.line, format!("Dereference of non-pointer type {}", ty)))
+            }
+            ExprKind::Index(array, index) => {
+                let ty = self.expr(array)?;
+                let element = ty
+                    .pointee()
+                    .cloned()
+                    .ok_or_else(|| Error::new(expr.line, format!("Index into non-pointer type {}", ty)))?;
+                let index_ty = self.expr(index)?;
+                if index_ty != Type::Int {
+                    return Err(Error::new(expr.line, format!("Index of type {}", index_ty)));
+                }
+
+                self.scale(self.top(), &element);
+                self.binary_instruction("ADDR", true);
+                Ok(element)
+            }
+            _ => Err(Error::new(expr.line, "Expression is not assignable".to_string())),
+        }
+    }
+
+    /// Loads the object of the given type at the address on top, arrays decay into their address
+    fn load(&mut self, ty: Type) -> Type {
+        match ty {
+            Type::Array(element, _) => Type::Pointer(element),
+            ty => {
+                let value = self.top();
+                let reg = register(self.reg(value));
+                self.emit("MOVRPR", &[&reg, &reg]);
+                ty
+            }
+        }
+    }
+
+    /// Multiplies the value by the size of the element type
+    fn scale(&mut self, value: usize, element: &Type) {
+        let reg = register(self.reg(value));
+        let words = element.words();
+        if words == 1 {
+            self.emit("LSF", &[&reg, "2"]);
+        } else {
+            self.emit("MULT", &[&format!("{}", words * 4), &reg]);
+            self.emit("MOVRR", &["acc", &reg]);
+        }
+    }
+
+    /// Combines the two values on top into the older one and releases the younger,
+    /// `to_acc` for instructions storing their result in acc
+    fn binary_instruction(&mut self, mnemonic: &str, to_acc: bool) {
+        let (lhs, rhs) = (self.top() - 1, self.top());
+        let rhs = register(self.reg(rhs));
+        let lhs = register(self.reg(lhs));
+
+        self.emit(mnemonic, &[&lhs, &rhs]);
+        if to_acc {
+            self.emit("MOVRR", &["acc", &lhs]);
+        }
+        self.release();
+    }
+
+    /// Stores the younger of the two values on top at the address held by the older one,
+    /// releasing the address and keeping the value
+    fn store(&mut self) {
+        let (address, value) = (self.top() - 1, self.top());
+        let value = register(self.reg(value));
+        let address = register(self.reg(address));
+
+        // STORER copies from memory, so the value is stored through a copy on the stack
+        self.emit("PUSHR", &[&value]);
+        self.emit("ADD", &["4", "sp"]);
+        self.emit("MOVR", &["4", &value]);
+        self.emit("STORER", &["acc", &value, &address]);
+        self.emit("POP", &[&value]);
+
+        // keep the value in place of the address
+        self.emit("MOVRR", &[&value, &address]);
+        self.release();
+    }
+
+    /// Turns the value on top into 1 if it isn't 0
+    fn normalize(&mut self) {
+        let value = self.top();
+        let reg = register(self.reg(value));
+        let end = self.new_label();
+        self.emit("BREQRW", &[&reg, "0", &format!(":{}", end)]);
+        self.emit("MOVR", &["1", &reg]);
+        self.place(&end);
+    }
+
+    /// Pushes the value of the expression and returns its type
+    fn expr(&mut self, expr: &Expr) -> Result<Type, Error> {
+        match &expr.kind {
+            ExprKind::Number(val) => {
+                let reg = register(self.alloc());
+                self.emit("MOVR", &[&format!("{}", val), &reg]);
+                Ok(Type::Int)
+            }
+            ExprKind::Variable(name) => {
+                let variable = self.lookup(name, expr.line)?;
+                if let Type::Array(..) = variable.ty {
+                    self.address(expr)?;
+                    return Ok(self.load(variable.ty));
+                }
+
+                let reg = register(self.alloc());
+                match &variable.location {
+                    Location::Local(slot) => {
+                        let offset = self.local_offset(*slot);
+                        self.emit("MOVROR", &["sp", &offset, &reg]);
+                    }
+                    Location::Param(param) => self.emit("MOVROR", &["fp", param, &reg]),
+                    Location::Global(global) => self.emit("MOVMR", &[global, &reg]),
+                }
+                Ok(variable.ty)
+            }
+            ExprKind::Unary(UnaryOp::AddressOf, operand) => Ok(Type::Pointer(Box::new(self.address(operand)?))),
+            ExprKind::Unary(UnaryOp::Deref, _) | ExprKind::Index(..) => {
+                let ty = self.address(expr)?;
+                Ok(self.load(ty))
+            }
+            ExprKind::Unary(op, operand) => {
+                let ty = self.expr(operand)?;
+                Self::check_value(&ty, expr.line)?;
+                let value = self.top();
+                let reg = register(self.reg(value));
+
+                match op {
+                    UnaryOp::Neg => {
+                        self.emit("SUBWR", &["0", &reg]);
+                        self.emit("MOVRR", &["acc", &reg]);
+                    }
+                    UnaryOp::BitNot => self.emit("NOT", &[&reg]),
+                    _ => {
+                        let (zero, end) = (self.new_label(), self.new_label());
+                        self.emit("BREQRW", &[&reg, "0", &format!(":{}", zero)]);
+                        self.emit("MOVR", &["0", &reg]);
+                        self.emit("JMP", &[&format!(":{}", end)]);
+                        self.place(&zero);
+                        self.emit("MOVR", &["1", &reg]);
+                        self.place(&end);
+                    }
+                }
+                Ok(Type::Int)
+            }
+            ExprKind::Binary(op @ BinaryOp::LogicalAnd, lhs, rhs)
+            | ExprKind::Binary(op @ BinaryOp::LogicalOr, lhs, rhs) => {
+                self.spill_all();
+                let ty = self.expr(lhs)?;
+                Self::check_value(&ty, lhs.line)?;
+                let value = self.top();
+                let result = self.reg(value);
+
+                // the result of the left side decides unless it is true for && or false for ||
+                let end = self.new_label();
+                let branch = if *op == BinaryOp::LogicalAnd {
+                    "BREQRW"
+                } else {
+                    "BRNQRW"
+                };
+                self.emit(branch, &[&register(result), "0", &format!(":{}", end)]);
+                self.release();
+
+                let ty = self.expr(rhs)?;
+                Self::check_value(&ty, rhs.line)?;
+                let value = self.top();
+                let reg = self.reg(value);
+                if reg != result {
+                    self.emit("MOVRR", &[&register(reg), &register(result)]);
+                }
+                self.release();
+
+                self.place(&end);
+                self.claim(result);
+                self.normalize();
+                Ok(Type::Int)
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let lhs_ty = self.expr(lhs)?;
+                let rhs_ty = self.expr(rhs)?;
+                Self::check_value(&lhs_ty, lhs.line)?;
+                Self::check_value(&rhs_ty, rhs.line)?;
+                self.binary(*op, lhs_ty, rhs_ty, expr.line)
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                let ty = self.expr(rhs)?;
+                Self::check_value(&ty, rhs.line)?;
+
+                if let ExprKind::Variable(name) = &lhs.kind {
+                    if let Variable {
+                        ty: Type::Int | Type::Pointer(_),
+                        location: Location::Global(global),
+                    } = self.lookup(name, lhs.line)?
+                    {
+                        let value = self.top();
+                        let reg = register(self.reg(value));
+                        self.emit("MOVRM", &[&reg, &global]);
+                        return Ok(ty);
+                    }
+                }
+
+                if let Type::Array(..) = self.address(lhs)? {
+                    return Err(Error::new(expr.line, "Arrays can't be assigned".to_string()));
+                }
+                // the address is computed after the value, swap them for store
+                let (value, address) = (self.top() - 1, self.top());
+                self.values.swap(value, address);
+                self.store();
+                Ok(ty)
+            }
+            ExprKind::Call(name, args) => self.call(name, args, expr.line),
+        }
+    }
+
+    /// Applies a binary operator to the two values on top
+    fn binary(&mut self, op: BinaryOp, lhs: Type, rhs: Type, line: usize) -> Result<Type, Error> {
+        let is_pointer = |ty: &Type| ty.pointee().is_some();
+
+        if op.is_comparison() {
+            let (lhs, rhs) = (self.top() - 1, self.top());
+            let rhs = register(self.reg(rhs));
+            let lhs = register(self.reg(lhs));
+
+            let (holds, end) = (self.new_label(), self.new_label());
+            self.emit(branch(op), &[&lhs, &rhs, &format!(":{}", holds)]);
+            self.emit("MOVR", &["0", &lhs]);
+            self.emit("JMP", &[&format!(":{}", end)]);
+            self.place(&holds);
+            self.emit("MOVR", &["1", &lhs]);
+            self.place(&end);
+            self.release();
+            return Ok(Type::Int);
+        }
+
+        // pointer arithmetic moves by whole elements
+        let ty = match (op, is_pointer(&lhs), is_pointer(&rhs)) {
+            (BinaryOp::Add, true, false) | (BinaryOp::Sub, true, false) => {
+                let element = lhs.pointee().unwrap().clone();
+                self.scale(self.top(), &element);
+                Type::Pointer(Box::new(element))
+            }
+            (BinaryOp::Add, false, true) => {
+                let element = rhs.pointee().unwrap().clone();
+                self.scale(self.top() - 1, &element);
+                Type::Pointer(Box::new(element))
+            }
+            (BinaryOp::Sub, true, true) => Type::Int,
+            (_, false, false) => Type::Int,
+            _ => {
+                return Err(Error::new(
+                    line,
+                    format!("Invalid operands {} and {} for {:?}", lhs, rhs, op),
+                ))
+            }
+        };
+
+        match op {
+            BinaryOp::Add => self.binary_instruction("ADDR", true),
+            BinaryOp::Sub => {
+                self.binary_instruction("SUBR", true);
+                if is_pointer(&rhs) {
+                    let value = self.top();
+                    let reg = register(self.reg(value));
+                    self.emit("RSF", &[&reg, "2"]);
+                }
+            }
+            BinaryOp::Mul => self.binary_instruction("MULTR", true),
+            BinaryOp::Div => self.binary_instruction("DIVR", true),
+            BinaryOp::Mod => {
+                let (lhs, rhs) = (self.top() - 1, self.top());
+                let rhs = register(self.reg(rhs));
+                let lhs = register(self.reg(lhs));
+                self.emit("DIVR", &[&lhs, &rhs]);
+                self.emit("MULTR", &["acc", &rhs]);
+                self.emit("SUBR", &[&lhs, "acc"]);
+                self.emit("MOVRR", &["acc", &lhs]);
+                self.release();
+            }
+            BinaryOp::And => self.binary_instruction("ANDR", false),
+            BinaryOp::Or => self.binary_instruction("ORR", false),
+            BinaryOp::Xor => self.binary_instruction("XORR", false),
+            BinaryOp::Shl => self.binary_instruction("LSFR", false),
+            BinaryOp::Shr => self.binary_instruction("RSFR", false),
+            _ => unreachable!("handled by the caller"),
+        }
+
+        Ok(ty)
+    }
+
+    /// Pushes the arguments in reverse and their count, then calls the function.
+    /// Every live value is spilled first so the arguments end up next to each other
+    fn call(&mut self, name: &str, args: &[Expr], line: usize) -> Result<Type, Error> {
+        let function = *self
+            .functions
+            .get(name)
+            .ok_or_else(|| Error::new(line, format!("Unknown function: {}", name)))?;
+        if function.params.len() != args.len() {
+            return Err(Error::new(
+                line,
+                format!(
+                    "Function {} expects {} argument(s) but got {}",
+                    name,
+                    function.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        self.spill_all();
+        for arg in args.iter().rev() {
+            let ty = self.expr(arg)?;
+            Self::check_value(&ty, arg.line)?;
+            let value = self.top();
+            let reg = self.reg(value);
+            self.push(reg);
+            self.release();
+        }
+
+        self.emit("PUSH", &[&format!("{}", args.len())]);
+        self.emit("CALL", &[&format!(":{}", name)]);
+
+        // RET pops the arguments and their count again
+        self.depth -= args.len() as Word;
+        let reg = register(self.alloc());
+        self.emit("MOVRR", &["acc", &reg]);
+        Ok(function.return_type.clone())
+    }
+
+    /// Jumps to the label if the condition is `jump_if`, short-circuiting `&&` and `||`
+    fn condition(&mut self, cond: &Expr, label: &str, jump_if: bool) -> Result<(), Error> {
+        match &cond.kind {
+            ExprKind::Binary(op @ BinaryOp::LogicalAnd, lhs, rhs)
+            | ExprKind::Binary(op @ BinaryOp::LogicalOr, lhs, rhs) => {
+                // && jumps on false and || on true as soon as the left side decides
+                let decides = *op == BinaryOp::LogicalOr;
+                if jump_if == decides {
+                    self.condition(lhs, label, jump_if)?;
+                    self.condition(rhs, label, jump_if)
+                } else {
+                    let skip = self.new_label();
+                    self.condition(lhs, &skip, decides)?;
+                    self.condition(rhs, label, jump_if)?;
+                    self.place(&skip);
+                    Ok(())
+                }
+            }
+            ExprKind::Unary(UnaryOp::Not, operand) => self.condition(operand, label, !jump_if),
+            ExprKind::Binary(op, lhs, rhs) if op.is_comparison() => {
+                let lhs_ty = self.expr(lhs)?;
+                let rhs_ty = self.expr(rhs)?;
+                Self::check_value(&lhs_ty, lhs.line)?;
+                Self::check_value(&rhs_ty, rhs.line)?;
+
+                let (lhs, rhs) = (self.top() - 1, self.top());
+                let rhs = register(self.reg(rhs));
+                let lhs = register(self.reg(lhs));
+                let op = if jump_if { *op } else { inverse(*op) };
+                self.emit(branch(op), &[&lhs, &rhs, &format!(":{}", label)]);
+                self.release();
+                self.release();
+                Ok(())
+            }
+            _ => {
+                let ty = self.expr(cond)?;
+                Self::check_value(&ty, cond.line)?;
+                let value = self.top();
+                let reg = register(self.reg(value));
+                let branch = if jump_if { "BRNQRW" } else { "BREQRW" };
+                self.emit(branch, &[&reg, "0", &format!(":{}", label)]);
+                self.release();
+                Ok(())
+            }
+        }
+    }
+
+    fn declaration(&mut self, decl: &Declaration) -> Result<(), Error> {
+        if decl.ty == Type::Void {
+            return Err(Error::new(decl.line, format!("Variable {} declared void", decl.name)));
+        }
+
+        self.slots += decl.ty.words();
+        let variable = Variable {
+            ty: decl.ty.clone(),
+            location: Location::Local(self.slots - 1),
+        };
+        self.declare(&decl.name, variable, decl.line)?;
+
+        if let Some(init) = &decl.init {
+            if let Type::Array(..) = decl.ty {
+                return Err(Error::new(
+                    decl.line,
+                    "Array initializers are not supported".to_string(),
+                ));
+            }
+
+            let target = Expr {
+                kind: ExprKind::Variable(decl.name.clone()),
+                line: decl.line,
+            };
+            self.expr(&Expr {
+                kind: ExprKind::Assign(Box::new(target), Box::new(init.clone())),
+                line: decl.line,
+            })?;
+            self.release();
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        match stmt {
+            Stmt::Declare(decl) => self.declaration(decl)?,
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+                self.release();
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let (otherwise_label, end) = (self.new_label(), self.new_label());
+                self.condition(cond, &otherwise_label, false)?;
+                self.stmt(then)?;
+
+                match otherwise {
+                    Some(otherwise) => {
+                        self.emit("JMP", &[&format!(":{}", end)]);
+                        self.place(&otherwise_label);
+                        self.stmt(otherwise)?;
+                        self.place(&end);
+                    }
+                    None => self.place(&otherwise_label),
+                }
+            }
+            Stmt::While(cond, body) => {
+                let (start, end) = (self.new_label(), self.new_label());
+                self.place(&start);
+                self.condition(cond, &end, false)?;
+
+                self.loops.push((start.clone(), end.clone()));
+                self.stmt(body)?;
+                self.loops.pop();
+
+                self.emit("JMP", &[&format!(":{}", start)]);
+                self.place(&end);
+            }
+            Stmt::Return(value, line) => {
+                match (value, &self.return_type) {
+                    (Some(_), Type::Void) => {
+                        return Err(Error::new(*line, "Void function returns a value".to_string()))
+                    }
+                    (None, ty) if *ty != Type::Void => {
+                        return Err(Error::new(*line, format!("Missing return value of type {}", ty)))
+                    }
+                    _ => {}
+                }
+
+                if let Some(value) = value {
+                    let ty = self.expr(value)?;
+                    Self::check_value(&ty, *line)?;
+                    let top = self.top();
+                    let reg = register(self.reg(top));
+                    self.emit("MOVRR", &[&reg, "acc"]);
+                    self.release();
+                }
+                self.emit("RET", &[]);
+            }
+            Stmt::Break(line) | Stmt::Continue(line) => {
+                let (start, end) = self
+                    .loops
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| Error::new(*line, "break or continue outside of a loop".to_string()))?;
+                let target = if let Stmt::Break(_) = stmt { end } else { start };
+                self.emit("JMP", &[&format!(":{}", target)]);
+            }
+            Stmt::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                for stmt in stmts {
+                    self.stmt(stmt)?;
+                }
+                self.scopes.pop();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), Error> {
+        let params: Vec<&str> = function.params.iter().map(|(name, _)| name.as_str()).collect();
+        self.output.push(String::new());
+        self.output
+            .push(format!(".proc {}({})", function.name, params.join(", ")));
+
+        self.scopes = vec![HashMap::new()];
+        for (name, ty) in &function.params {
+            if is_register_name(name) {
+                return Err(Error::new(
+                    function.line,
+                    format!("Parameter name is a register: {}", name),
+                ));
+            }
+            let variable = Variable {
+                ty: ty.clone(),
+                location: Location::Param(name.clone()),
+            };
+            self.declare(name, variable, function.line)?;
+        }
+
+        // every local gets its own zeroed slot for the whole function
+        self.slots = 0;
+        self.depth = 0;
+        self.return_type = function.return_type.clone();
+        for _ in 0..local_words(&function.body) {
+            self.emit("PUSH", &["0"]);
+            self.depth += 1;
+        }
+
+        self.stmt(&Stmt::Block(function.body.clone()))?;
+        self.output.push(".endp".to_string());
+        Ok(())
+    }
+
+    /// Program header: origin, global addresses, global initializers and the call of main
+    fn header(&mut self, program: &'a Program) -> Result<(), Error> {
+        self.output.push(format!(".org 0x{:X}", LOAD_ADDRESS));
+
+        let mut address = DATA_ADDRESS;
+        for global in &program.globals {
+            if global.ty == Type::Void {
+                return Err(Error::new(
+                    global.line,
+                    format!("Variable {} declared void", global.name),
+                ));
+            }
+            if is_register_name(&global.name) || global.name.starts_with('_') {
+                return Err(Error::new(
+                    global.line,
+                    format!("Reserved global name: {}", global.name),
+                ));
+            }
+            if self.functions.contains_key(global.name.as_str()) {
+                return Err(Error::new(
+                    global.line,
+                    format!("Duplicate definition: {}", global.name),
+                ));
+            }
+
+            let variable = Variable {
+                ty: global.ty.clone(),
+                location: Location::Global(global.name.clone()),
+            };
+            if self.globals.insert(global.name.clone(), variable).is_some() {
+                return Err(Error::new(global.line, format!("Duplicate variable: {}", global.name)));
+            }
+
+            self.output.push(format!(".const {}, 0x{:X}", global.name, address));
+            address += global.ty.words() * 4;
+        }
+
+        for global in &program.globals {
+            if let Some(init) = &global.init {
+                let val = const_value(init)
+                    .ok_or_else(|| Error::new(global.line, "Global initializer must be a constant".to_string()))?;
+                self.emit("MOVM", &[&format!("{}", val), &global.name]);
+            }
+        }
+
+        match self.functions.get("main") {
+            Some(main) if main.params.is_empty() => {}
+            Some(main) => return Err(Error::new(main.line, "main can't take parameters".to_string())),
+            None => return Err(Error::new(0, "Missing function main".to_string())),
+        }
+        self.emit("INVOKE", &["main"]);
+        self.emit("HALT", &[]);
+        Ok(())
+    }
+}
+
+/// Generates 0xASM for the program, returning the first error
+pub fn generate(program: &Program) -> Result<String, Error> {
+    let mut generator = Generator {
+        output: Vec::new(),
+        labels: 0,
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        slots: 0,
+        depth: 0,
+        loops: Vec::new(),
+        return_type: Type::Void,
+        free: (1..=REGISTER_COUNT).rev().collect(),
+        values: Vec::new(),
+    };
+
+    for function in &program.functions {
+        if is_register_name(&function.name) || function.name.starts_with('_') {
+            return Err(Error::new(
+                function.line,
+                format!("Reserved function name: {}", function.name),
+            ));
+        }
+        if generator.functions.insert(&function.name, function).is_some() {
+            return Err(Error::new(
+                function.line,
+                format!("Duplicate function: {}", function.name),
+            ));
+        }
+    }
+
+    generator.header(program)?;
+    for function in &program.functions {
+        generator.function(function)?;
+    }
+
+    let mut output = generator.output.join("\n");
+    output.push('\n');
+    Ok(output)
+}
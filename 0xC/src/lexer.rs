@@ -0,0 +1,134 @@
+use crate::{Error, Word};
+
+/// Operators and punctuation, longer symbols first so `<=` isn't lexed as `<` and `=`
+const SYMBOLS: &[&str] = &[
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">", "=",
+    "(", ")", "{", "}", "[", "]", ",", ";",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Number(Word),
+    /// Identifiers and keywords
+    Ident(String),
+    Symbol(&'static str),
+    Eof,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Zero-based line of the token
+    pub line: usize,
+}
+
+fn escape(c: char) -> Option<Word> {
+    match c {
+        'n' => Some(b'\n' as Word),
+        't' => Some(b'\t' as Word),
+        'r' => Some(b'\r' as Word),
+        '0' => Some(0),
+        '\\' => Some(b'\\' as Word),
+        '\'' => Some(b'\'' as Word),
+        _ => None,
+    }
+}
+
+/// Splits the source into tokens, the last token is always `Eof`
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let start = line;
+            i += 2;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some('*'), Some('/')) => break,
+                    (Some('\n'), _) => line += 1,
+                    (None, _) => return Err(Error::new(start, "Unterminated comment".to_string())),
+                    _ => {}
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+
+            let text: String = chars[start..i].iter().collect();
+            let lowercase = text.to_lowercase();
+            let value = if let Some(hex) = lowercase.strip_prefix("0x") {
+                Word::from_str_radix(hex, 16)
+            } else if let Some(bin) = lowercase.strip_prefix("0b") {
+                Word::from_str_radix(bin, 2)
+            } else {
+                text.parse()
+            };
+
+            let value = value.map_err(|_| Error::new(line, format!("Invalid number: {}", text)))?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                line,
+            });
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+
+            tokens.push(Token {
+                kind: TokenKind::Ident(chars[start..i].iter().collect()),
+                line,
+            });
+        } else if c == '\'' {
+            let value = match (chars.get(i + 1), chars.get(i + 2), chars.get(i + 3)) {
+                (Some('\\'), Some(&escaped), Some('\'')) => escape(escaped).map(|val| (val, 4)),
+                (Some(&c), Some('\''), _) if c != '\\' && c != '\'' => Some((c as Word, 3)),
+                _ => None,
+            };
+
+            let (value, len) = value.ok_or_else(|| Error::new(line, "Invalid char literal".to_string()))?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                line,
+            });
+            i += len;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| Error::new(line, format!("Unexpected character: {}", c)))?;
+
+            tokens.push(Token {
+                kind: TokenKind::Symbol(symbol),
+                line,
+            });
+            i += symbol.len();
+        }
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+    });
+
+    Ok(tokens)
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+pub type Word = u32;
+
+pub mod ast;
+pub mod codegen;
+pub mod lexer;
+pub mod parser;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    /// Zero-based line of the error
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(line: usize, message: String) -> Self {
+        Error { line, message }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error at line {}: {}", self.line + 1, self.message)
+    }
+}
+
+/// Compiles a source file into 0xASM source
+pub fn compile(source: &str) -> Result<String, Error> {
+    codegen::generate(&parser::parse(source)?)
+}
@@ -0,0 +1,29 @@
+use std::env;
+use std::fs;
+
+use compiler::compile;
+
+fn usage(program: &str) -> Result<(), String> {
+    println!("Usage: {} <input> <output>", program);
+    Err("Invalid arguments".to_string())
+}
+
+fn compile_file(input: &str, output: &str) -> Result<(), String> {
+    let source = fs::read_to_string(input).map_err(|_| format!("Error opening input file: {}", input))?;
+    let assembly = compile(&source).map_err(|err| {
+        println!("{}", err);
+        format!("Failed to compile {}", input)
+    })?;
+
+    fs::write(output, assembly).map_err(|_| format!("Error creating output file: {}", output))
+}
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args[1..] {
+        [input, output] => compile_file(input, output),
+        _ => usage(args[0]),
+    }
+}
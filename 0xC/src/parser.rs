@@ -0,0 +1,392 @@
+use crate::{
+    ast::{BinaryOp, Declaration, Expr, ExprKind, Function, Program, Stmt, Type, UnaryOp},
+    lexer::{tokenize, Token, TokenKind},
+    Error,
+};
+
+/// Binary operators from the lowest to the highest precedence
+const PRECEDENCE: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::LogicalOr)],
+    &[("&&", BinaryOp::LogicalAnd)],
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+    ],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Mod)],
+];
+
+const KEYWORDS: &[&str] = &["int", "void", "if", "else", "while", "return", "break", "continue"];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn line(&self) -> usize {
+        self.peek().line
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek().kind, TokenKind::Symbol(s) if s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(name) if name == keyword)
+    }
+
+    /// Consumes the symbol if it is next
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let found = match &self.peek().kind {
+            TokenKind::Number(val) => val.to_string(),
+            TokenKind::Ident(name) => name.clone(),
+            TokenKind::Symbol(symbol) => symbol.to_string(),
+            TokenKind::Eof => "end of file".to_string(),
+        };
+        Error::new(self.line(), format!("Expected {} but found {}", expected, found))
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", symbol)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match &self.peek().kind {
+            TokenKind::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    /// `int` or `void` followed by any number of `*`
+    fn base_type(&mut self) -> Result<Type, Error> {
+        let mut ty = if self.eat_keyword("int") {
+            Type::Int
+        } else if self.eat_keyword("void") {
+            Type::Void
+        } else {
+            return Err(self.unexpected("a type"));
+        };
+
+        while self.eat("*") {
+            ty = Type::Pointer(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    /// Optional `[length]` behind a variable name
+    fn array_suffix(&mut self, ty: Type) -> Result<Type, Error> {
+        if !self.eat("[") {
+            return Ok(ty);
+        }
+
+        let len = match self.next().kind {
+            TokenKind::Number(len) if len > 0 => len,
+            _ => return Err(Error::new(self.line(), "Expected a positive array length".to_string())),
+        };
+        self.expect("]")?;
+        Ok(Type::Array(Box::new(ty), len))
+    }
+
+    /// Rest of a variable declaration after its base type
+    fn declaration(&mut self, ty: Type) -> Result<Declaration, Error> {
+        let line = self.line();
+        let name = self.ident()?;
+        let ty = self.array_suffix(ty)?;
+        let init = if self.eat("=") { Some(self.expr()?) } else { None };
+        self.expect(";")?;
+
+        Ok(Declaration { name, ty, init, line })
+    }
+
+    fn params(&mut self) -> Result<Vec<(String, Type)>, Error> {
+        let mut params = Vec::new();
+        if self.eat(")") {
+            return Ok(params);
+        }
+        if self.is_keyword("void") && matches!(self.tokens[self.pos + 1].kind, TokenKind::Symbol(")")) {
+            self.pos += 2;
+            return Ok(params);
+        }
+
+        loop {
+            let mut ty = self.base_type()?;
+            let name = self.ident()?;
+            // array parameters are passed as pointers to their first element
+            if self.eat("[") {
+                self.expect("]")?;
+                ty = Type::Pointer(Box::new(ty));
+            }
+            params.push((name, ty));
+
+            if self.eat(")") {
+                return Ok(params);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if self.peek().kind == TokenKind::Eof {
+                return Err(self.unexpected("'}'"));
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, Error> {
+        let line = self.line();
+
+        if self.is_symbol("{") {
+            Ok(Stmt::Block(self.block()?))
+        } else if self.eat(";") {
+            Ok(Stmt::Block(Vec::new()))
+        } else if self.is_keyword("int") || self.is_keyword("void") {
+            let ty = self.base_type()?;
+            Ok(Stmt::Declare(self.declaration(ty)?))
+        } else if self.eat_keyword("if") {
+            self.expect("(")?;
+            let cond = self.expr()?;
+            self.expect(")")?;
+            let then = Box::new(self.stmt()?);
+            let otherwise = if self.eat_keyword("else") {
+                Some(Box::new(self.stmt()?))
+            } else {
+                None
+            };
+            Ok(Stmt::If(cond, then, otherwise))
+        } else if self.eat_keyword("while") {
+            self.expect("(")?;
+            let cond = self.expr()?;
+            self.expect(")")?;
+            Ok(Stmt::While(cond, Box::new(self.stmt()?)))
+        } else if self.eat_keyword("return") {
+            let value = if self.is_symbol(";") { None } else { Some(self.expr()?) };
+            self.expect(";")?;
+            Ok(Stmt::Return(value, line))
+        } else if self.eat_keyword("break") {
+            self.expect(";")?;
+            Ok(Stmt::Break(line))
+        } else if self.eat_keyword("continue") {
+            self.expect(";")?;
+            Ok(Stmt::Continue(line))
+        } else {
+            let expr = self.expr()?;
+            self.expect(";")?;
+            Ok(Stmt::Expr(expr))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let line = self.line();
+        let lhs = self.binary(0)?;
+
+        if self.eat("=") {
+            let rhs = self.expr()?;
+            return Ok(Expr {
+                kind: ExprKind::Assign(Box::new(lhs), Box::new(rhs)),
+                line,
+            });
+        }
+        Ok(lhs)
+    }
+
+    /// Left associative binary operators of the given precedence level and above
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        loop {
+            let line = self.line();
+            let op = PRECEDENCE[level]
+                .iter()
+                .find(|(symbol, _)| self.is_symbol(symbol))
+                .map(|(_, op)| *op);
+
+            match op {
+                Some(op) => {
+                    self.pos += 1;
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr {
+                        kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+                        line,
+                    };
+                }
+                None => return Ok(lhs),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let line = self.line();
+        let op = [
+            ("-", UnaryOp::Neg),
+            ("!", UnaryOp::Not),
+            ("~", UnaryOp::BitNot),
+            ("*", UnaryOp::Deref),
+            ("&", UnaryOp::AddressOf),
+        ]
+        .iter()
+        .find(|(symbol, _)| self.is_symbol(symbol))
+        .map(|(_, op)| *op);
+
+        match op {
+            Some(op) => {
+                self.pos += 1;
+                Ok(Expr {
+                    kind: ExprKind::Unary(op, Box::new(self.unary()?)),
+                    line,
+                })
+            }
+            None => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        while self.is_symbol("[") {
+            let line = self.line();
+            self.pos += 1;
+            let index = self.expr()?;
+            self.expect("]")?;
+            expr = Expr {
+                kind: ExprKind::Index(Box::new(expr), Box::new(index)),
+                line,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let line = self.line();
+
+        if let TokenKind::Number(val) = self.peek().kind {
+            self.pos += 1;
+            return Ok(Expr {
+                kind: ExprKind::Number(val),
+                line,
+            });
+        }
+
+        if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        let name = self.ident().map_err(|_| self.unexpected("an expression"))?;
+        if !self.eat("(") {
+            return Ok(Expr {
+                kind: ExprKind::Variable(name),
+                line,
+            });
+        }
+
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        Ok(Expr {
+            kind: ExprKind::Call(name, args),
+            line,
+        })
+    }
+
+    fn program(&mut self) -> Result<Program, Error> {
+        let mut program = Program::default();
+
+        while self.peek().kind != TokenKind::Eof {
+            let line = self.line();
+            let ty = self.base_type()?;
+
+            let is_function = matches!(
+                (
+                    &self.peek().kind,
+                    self.tokens.get(self.pos + 1).map(|token| &token.kind)
+                ),
+                (TokenKind::Ident(_), Some(TokenKind::Symbol("(")))
+            );
+            if !is_function {
+                program.globals.push(self.declaration(ty)?);
+                continue;
+            }
+
+            let name = self.ident()?;
+            self.expect("(")?;
+            let params = self.params()?;
+            let body = self.block()?;
+
+            program.functions.push(Function {
+                name,
+                return_type: ty,
+                params,
+                body,
+                line,
+            });
+        }
+
+        Ok(program)
+    }
+}
+
+/// Parses a whole source file, stopping at the first error
+pub fn parse(source: &str) -> Result<Program, Error> {
+    let tokens = tokenize(source)?;
+    Parser { tokens, pos: 0 }.program()
+}
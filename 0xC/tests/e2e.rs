@@ -0,0 +1,230 @@
+use asm::{assembler::assemble_source, formatter::format_source, registers::register_address};
+use compiler::{codegen::DATA_ADDRESS, compile};
+use vm::{
+    cpu::CPU,
+    machine::{MachineBuilder, SCREEN_ADDRESS, SCREEN_END},
+    memory::Memory,
+};
+
+/// Compiles the program, checks the generated source is formatted and runs it on the standard
+/// 0xVM machine until HALT. Plain memory takes the place of the Screen
+fn run(source: &str) -> CPU {
+    let output = compile(source).unwrap();
+    assert_eq!(format_source(&output).unwrap(), output);
+
+    let assembly = assemble_source(&output).unwrap();
+    let mut machine = MachineBuilder::standard()
+        .device(Box::new(Memory::new(SCREEN_END - SCREEN_ADDRESS)), SCREEN_ADDRESS, SCREEN_END)
+        .image(assembly.image)
+        .build();
+    machine.run().unwrap();
+    machine.cpu
+}
+
+/// Return value of main
+fn result(source: &str) -> u32 {
+    run(source).get_reg(register_address("acc").unwrap())
+}
+
+fn error(source: &str) -> String {
+    compile(source).unwrap_err().to_string()
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(result("int main() { return 1 + 2 * 3 - 8 / 2; }"), 3);
+    assert_eq!(result("int main() { return (17 % 5) << 4 | 3 ^ 1; }"), 0x22);
+    assert_eq!(result("int main() { return -1; }"), 0xFFFF_FFFF);
+    assert_eq!(result("int main() { return ~0xF0 & 0xFF; }"), 0x0F);
+    assert_eq!(
+        result("int main() { return !0 + !7 + (3 < 4) + (4 <= 3) + (2 != 2); }"),
+        2
+    );
+    assert_eq!(result("int main() { return 'a' + 0x10 + 0b1; }"), 0x72);
+}
+
+#[test]
+fn control_flow() {
+    let source = "
+int main() {
+    int sum = 0;
+    int i = 0;
+    while (1) {
+        i = i + 1;
+        if (i > 10) break;
+        if (i % 2 == 0) continue;
+        sum = sum + i;
+    }
+    if (sum == 25 && i == 11) return sum; else return 0;
+}";
+    assert_eq!(result(source), 25);
+
+    let source = "
+int main() {
+    int x = 3;
+    if (x < 2 || x > 5 || !(x == 3)) return 1;
+    else if (x >= 3 && x <= 3) return 2;
+    return 3;
+}";
+    assert_eq!(result(source), 2);
+}
+
+#[test]
+fn functions() {
+    let source = "
+int fib(int n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+
+int fact(int n) {
+    if (n == 0) return 1;
+    return n * fact(n - 1);
+}
+
+int sub(int a, int b) { return a - b; }
+
+int main() {
+    return fib(10) * 1000 + fact(5) + sub(10, sub(8, 3));
+}";
+    assert_eq!(result(source), 55 * 1000 + 120 + 5);
+}
+
+#[test]
+fn pointers_and_arrays() {
+    let source = "
+void fill(int *values, int len) {
+    int i = 0;
+    while (i < len) {
+        values[i] = i * i;
+        i = i + 1;
+    }
+}
+
+int sum(int values[], int len) {
+    int total = 0;
+    int *end = values + len;
+    while (values < end) {
+        total = total + *values;
+        values = values + 1;
+    }
+    return total;
+}
+
+int main() {
+    int squares[5];
+    int x = 7;
+    int *p = &x;
+    *p = *p + 1;
+    fill(squares, 5);
+    return sum(squares, 5) * 100 + x + (&squares[4] - squares);
+}";
+    assert_eq!(result(source), 30 * 100 + 8 + 4);
+}
+
+#[test]
+fn globals() {
+    let source = "
+int counter = 40 + 2;
+int table[4];
+int *cursor;
+
+void count() {
+    counter = counter + 1;
+}
+
+int main() {
+    count();
+    count();
+    cursor = table;
+    cursor[2] = counter;
+    table[3] = 7;
+    return table[2] + cursor[3];
+}";
+    let cpu = run(source);
+    assert_eq!(cpu.get_reg(register_address("acc").unwrap()), 51);
+    assert_eq!(cpu.memory_mapper.get_word(DATA_ADDRESS).unwrap(), 44);
+    assert_eq!(cpu.memory_mapper.get_word(DATA_ADDRESS + 12).unwrap(), 44);
+    assert_eq!(cpu.memory_mapper.get_word(DATA_ADDRESS + 20).unwrap(), DATA_ADDRESS + 4);
+}
+
+#[test]
+fn short_circuit() {
+    let source = "
+int calls;
+
+int touch(int value) {
+    calls = calls + 1;
+    return value;
+}
+
+int main() {
+    int a = touch(0) && touch(1);
+    int b = touch(2) || touch(3);
+    int c = touch(4) && touch(5);
+    return calls * 100 + a * 10 + b + c;
+}";
+    assert_eq!(result(source), 4 * 100 + 2);
+}
+
+#[test]
+fn register_spilling() {
+    // more live values than registers, and calls in the middle of the expression
+    let source = "
+int id(int x) { return x; }
+
+int main() {
+    int a = 1;
+    return a + (2 + (3 + (4 + (5 + (6 + (7 + (8 + (9 + (10 + id(11) * (12 - a))))))))));
+}";
+    assert_eq!(result(source), 55 + 11 * 11);
+
+    let source = "
+int main() {
+    int v[3];
+    v[0] = 1; v[1] = 2; v[2] = 3;
+    return v[0] * (v[1] + v[2] * (v[0] + v[1] * (v[2] + v[0] * (v[1] + v[2] * (v[0] + v[1])))));
+}";
+    assert_eq!(result(source), 89);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        error("int main() { return x; }"),
+        "Error at line 1: Unknown variable: x"
+    );
+    assert_eq!(
+        error("int main() {\n  return f(1);\n}"),
+        "Error at line 2: Unknown function: f"
+    );
+    assert_eq!(
+        error("int f(int a) { return a; }\nint main() { return f(); }"),
+        "Error at line 2: Function f expects 1 argument(s) but got 0"
+    );
+    assert_eq!(
+        error("int main() { break; }"),
+        "Error at line 1: break or continue outside of a loop"
+    );
+    assert_eq!(
+        error("int main() { return 1 }"),
+        "Error at line 1: Expected ';' but found }"
+    );
+    assert_eq!(
+        error("int r1;\nint main() { return 0; }"),
+        "Error at line 1: Reserved global name: r1"
+    );
+    assert_eq!(
+        error("int main() { return 0; }\nint r1() { return 1; }"),
+        "Error at line 2: Reserved function name: r1"
+    );
+    assert_eq!(
+        error("void f() {}\nint main() { return f(); }"),
+        "Error at line 2: Void value used in an expression"
+    );
+    assert_eq!(error("int f() { return 0; }"), "Error at line 1: Missing function main");
+    assert_eq!(
+        error("int main() { int a[2]; a = 0; return 0; }"),
+        "Error at line 1: Arrays can't be assigned"
+    );
+}
@@ -1,8 +1,9 @@
 use std::io::{Stdout, Write};
 
 use crate::{
-    device::Device,
+    device::{Device, Dma, Interrupts},
     memory::{Byte, Memory, MemoryMapper, Word},
+    trap::{Trap, VmError},
 };
 use macros::reg;
 
@@ -13,14 +14,21 @@ macro_rules! generate_execute {
         match $instr {
             0xFF => $self.halt_signal = true,
             0x00 => {},
-            $($($op => $instr_func($self),)*)*
-            _ => panic!("[CPU] No such instruction: '0x{:02X}'", $instr)
+            $($($op => $instr_func($self)?,)*)*
+            _ => return Err(Trap::IllegalInstruction($instr))
         }
     };
 }
 
+/// Bit of the status register enabling hardware interrupts
+pub const FLAG_INTERRUPTS: Byte = 2;
+
 pub struct CPU {
     pub memory_mapper: MemoryMapper,
+    /// IRQ lines raised by devices, taken between instructions while interrupts are enabled
+    pub interrupts: Interrupts,
+    /// DMA channels moving the bytes of their transfers between instructions
+    pub dma: Vec<Dma>,
     registers: Memory,
     stackframe_size: Word,
     halt_signal: bool,
@@ -39,6 +47,8 @@ impl CPU {
     pub fn new(memory_mapper: MemoryMapper, pc: Word) -> Self {
         let mut cpu = CPU {
             memory_mapper,
+            interrupts: Interrupts::new(),
+            dma: Vec::new(),
             registers: Memory::new((crate::REGISTER_COUNT * 4) as u32),
             stackframe_size: 0,
             halt_signal: false,
@@ -89,102 +99,151 @@ impl CPU {
         self.get_reg(reg!("sr")) & (1u32.wrapping_shl(n as Word)) != 0
     }
 
+    /// Sets or clears the n-th bit of the status register
+    #[inline]
+    pub fn set_status_flag(&mut self, n: Byte, set: bool) {
+        let sr = self.get_reg(reg!("sr"));
+        let flag = 1u32.wrapping_shl(n as Word);
+        self.set_reg(reg!("sr"), if set { sr | flag } else { sr & !flag });
+    }
+
     /// Gets the val of the register with the given addr.
     #[inline]
     pub fn get_reg(&self, addr: Word) -> Word {
-        self.registers.get_word(addr)
+        self.registers.get_word(addr).expect("[CPU] No such register")
     }
 
     /// Sets the val of the register with the given addr.
     #[inline]
     pub fn set_reg(&mut self, addr: Word, val: Word) {
-        self.registers.set_word(addr, val);
+        self.registers.set_word(addr, val).expect("[CPU] No such register");
+    }
+
+    /// Gets the val of a register addressed by an operand, which may be outside of the register file
+    #[inline]
+    pub fn try_get_reg(&self, addr: Word) -> Result<Word, Trap> {
+        self.registers.get_word(addr).map_err(|_| Trap::InvalidRegister(addr))
+    }
+
+    /// Sets the val of a register addressed by an operand, which may be outside of the register file
+    #[inline]
+    pub fn try_set_reg(&mut self, addr: Word, val: Word) -> Result<(), Trap> {
+        self.registers.set_word(addr, val).map_err(|_| Trap::InvalidRegister(addr))
     }
 
     /// Fetches the next byte from memory and increments the program counter.
-    pub fn fetch_byte(&mut self) -> Byte {
+    pub fn fetch_byte(&mut self) -> Result<Byte, Trap> {
         let next_instr_addr = self.get_reg(reg!("pc"));
-        self.set_reg(reg!("pc"), next_instr_addr + 1);
+        self.set_reg(reg!("pc"), next_instr_addr.wrapping_add(1));
 
         self.memory_mapper.get_byte(next_instr_addr)
     }
 
     /// Fetches the next word from memory and increments the program counter.
-    pub fn fetch_word(&mut self) -> Word {
+    pub fn fetch_word(&mut self) -> Result<Word, Trap> {
         let next_instr_addr = self.get_reg(reg!("pc"));
-        self.set_reg(reg!("pc"), next_instr_addr + 4);
+        self.set_reg(reg!("pc"), next_instr_addr.wrapping_add(4));
 
         self.memory_mapper.get_word(next_instr_addr)
     }
 
     /// Pushes onto stack and increments stackframe size
-    pub fn push(&mut self, val: Word) {
+    pub fn push(&mut self, val: Word) -> Result<(), Trap> {
         let sp_addr = self.get_reg(reg!("sp"));
 
-        if sp_addr - 4 < self.stack_start - self.stack_size {
-            panic!("[CPU] Stack overflow");
-        }
+        let next_sp_addr = match sp_addr.checked_sub(4) {
+            Some(addr) if addr >= self.stack_start.wrapping_sub(self.stack_size) => addr,
+            _ => return Err(Trap::StackOverflow),
+        };
 
-        self.memory_mapper.set_word(sp_addr, val);
-        self.set_reg(reg!("sp"), sp_addr - 4);
+        self.memory_mapper.set_word(sp_addr, val)?;
+        self.set_reg(reg!("sp"), next_sp_addr);
 
         self.stackframe_size += 4;
+        Ok(())
     }
 
     /// Pops from the stack and decrements stackframe size
-    pub fn pop(&mut self) -> Word {
-        let next_sp_addr = self.get_reg(reg!("sp")) + 4;
+    pub fn pop(&mut self) -> Result<Word, Trap> {
+        // a stack pointer at the end of the address space has nothing left to pop
+        let next_sp_addr = self.get_reg(reg!("sp")).checked_add(4).ok_or(Trap::StackUnderflow)?;
 
-        if next_sp_addr > self.stack_start - 3 {
-            panic!("[CPU] Stack underflow");
+        if next_sp_addr > self.stack_start.saturating_sub(3) {
+            return Err(Trap::StackUnderflow);
         }
 
         self.set_reg(reg!("sp"), next_sp_addr);
 
-        self.stackframe_size -= 4;
+        self.stackframe_size = self.stackframe_size.wrapping_sub(4);
 
-        return self.memory_mapper.get_word(next_sp_addr);
+        self.memory_mapper.get_word(next_sp_addr)
     }
 
     /// Push state onto stack after CALL
-    pub fn push_state(&mut self) {
+    pub fn push_state(&mut self) -> Result<(), Trap> {
         for i in 0..8 {
-            self.push(self.get_reg(i * 4));
+            self.push(self.get_reg(i * 4))?;
         }
 
-        self.push(self.get_reg(reg!("pc")));
-        self.push(self.stackframe_size + 4);
+        self.push(self.get_reg(reg!("pc")))?;
+        self.push(self.stackframe_size + 4)?;
 
         self.set_reg(reg!("fp"), self.get_reg(reg!("sp")));
         self.stackframe_size = 0;
+        Ok(())
+    }
+
+    /// Enters the handler of the vector: pushes acc and sr as the arguments of a `push_state` frame,
+    /// disables interrupts and jumps to the handler address found in the vector table at ivt
+    pub fn interrupt(&mut self, vector: Word) -> Result<(), Trap> {
+        let entry = self.get_reg(reg!("ivt")).wrapping_add(vector.wrapping_mul(4));
+        let handler = self.memory_mapper.get_word(entry)?;
+
+        self.push(self.get_reg(reg!("acc")))?;
+        self.push(self.get_reg(reg!("sr")))?;
+        self.push(2)?;
+        self.push_state()?;
+
+        self.set_status_flag(FLAG_INTERRUPTS, false);
+        self.set_reg(reg!("pc"), handler);
+        Ok(())
     }
 
-    /// Pop state from stack after RET
-    pub fn pop_state(&mut self) {
+    /// Pop state from stack after RET, returns the arguments that were pushed before the call
+    pub fn pop_state(&mut self) -> Result<Vec<Word>, Trap> {
         let fp_addr = self.get_reg(reg!("fp"));
         self.set_reg(reg!("sp"), fp_addr);
 
         // bugfix where the stackframe is 0 but we need to pop the stackframe size
-        self.stackframe_size += 4;
-        self.stackframe_size = self.pop();
+        self.stackframe_size = self.stackframe_size.wrapping_add(4);
+        self.stackframe_size = self.pop()?;
 
-        let pc_addr = self.pop();
+        let pc_addr = self.pop()?;
         self.set_reg(reg!("pc"), pc_addr);
 
         for i in (0..8).rev() {
-            let gp_reg_val = self.pop();
+            let gp_reg_val = self.pop()?;
             self.set_reg(i * 4, gp_reg_val);
         }
 
-        let arg_count = self.pop();
+        let arg_count = self.pop()?;
+        let mut args = Vec::new();
         for _ in 0..arg_count {
-            self.pop();
+            args.push(self.pop()?);
         }
 
-        self.set_reg(reg!("fp"), fp_addr + self.stackframe_size);
+        // the stored stackframe size includes its own slot, the caller's frame
+        // starts right above the values it pushed before the call. A corrupt frame
+        // traps instead of moving fp out of the address space
+        let stackframe_size = self.stackframe_size.checked_sub(4).ok_or(Trap::StackUnderflow)?;
+        let sp_addr = self.get_reg(reg!("sp"));
+        let fp_addr = sp_addr.checked_add(stackframe_size).ok_or(Trap::StackUnderflow)?;
+        self.stackframe_size = stackframe_size;
+        self.set_reg(reg!("fp"), fp_addr);
+        Ok(args)
     }
 
-    fn execute(&mut self, instr: Byte) {
+    fn execute(&mut self, instr: Byte) -> Result<(), Trap> {
         generate_execute!(
             self,
             instr,
@@ -209,6 +268,8 @@ impl CPU {
             ],
             // sub routine instructions
             [(0x01, JMP), (0x02, CALL), (0x03, CALLR), (0x04, RET)],
+            // interrupt instructions
+            [(0x06, INT), (0x07, IRET), (0x08, EI), (0x09, DI)],
             // arithmetic instructions
             [
                 (0x20, ADD),
@@ -272,6 +333,7 @@ impl CPU {
                 (0x49, BRGTERR)
             ]
         );
+        Ok(())
     }
 
     /// Prints debug output with offset
@@ -279,7 +341,7 @@ impl CPU {
         // move curser next to the screen device output,
         // print output and flush the output buffer
         stdout
-            .write(format!("{}\x1b[0K", output).as_bytes())
+            .write_all(format!("{}\x1b[0K", output).as_bytes())
             .expect("[VM] Debugger display error");
 
         stdout.flush().expect("[VM] Error flushing stdout");
@@ -327,7 +389,8 @@ impl CPU {
         let mut mem_snapshot: Vec<Byte> = Vec::new();
         let max_addr = self._debug_memory_pos + 16 * 4;
         for i in self._debug_memory_pos..max_addr {
-            mem_snapshot.push(self.memory_mapper.get_byte(i));
+            // unmapped addresses are shown as 0
+            mem_snapshot.push(self.memory_mapper.get_byte(i).unwrap_or(0));
         }
 
         let mut output = String::new();
@@ -362,15 +425,49 @@ impl CPU {
         self.debug_print(stdout, output);
     }
 
-    /// Progresses the program
-    fn step(&mut self) {
-        let instr = self.fetch_byte();
-        self.execute(instr);
+    /// Progresses the program, a trap leaves the pc behind the part of the instruction fetched so far.
+    /// Entering the handler of a pending IRQ takes the place of the next instruction, either way the
+    /// devices are ticked and the DMA channels move their next burst once
+    pub fn step(&mut self) -> Result<(), VmError> {
+        let result = self.execute_next();
+        self.memory_mapper.tick();
+        for dma in self.dma.iter() {
+            dma.tick(&mut self.memory_mapper);
+        }
+        result
+    }
+
+    fn execute_next(&mut self) -> Result<(), VmError> {
+        let pc = self.get_reg(reg!("pc"));
+        if self.get_status_flag(FLAG_INTERRUPTS) {
+            if let Some(line) = self.interrupts.take() {
+                return self.interrupt(line).map_err(|trap| VmError::new(trap, pc, None));
+            }
+        }
+
+        let instr = self.fetch_byte().map_err(|trap| VmError::new(trap, pc, None))?;
+
+        self.execute(instr).map_err(|trap| VmError::new(trap, pc, Some(instr)))
+    }
+
+    /// Checks if a HALT instruction was executed
+    pub fn is_halted(&self) -> bool {
+        self.halt_signal
+    }
+
+    /// Clears the halt signal so execution can continue
+    pub fn resume(&mut self) {
+        self.halt_signal = false;
+    }
+
+    /// Error of running without a stack
+    fn stack_not_set(&self) -> VmError {
+        VmError::new(Trap::StackNotSet, self.get_reg(reg!("pc")), None)
     }
 
-    pub fn run_debug(&mut self, mut offset: Word) {
+    pub fn run_debug(&mut self, mut offset: Word) -> Result<(), VmError> {
         if !self.stack_set {
-            panic!("[VM] Stack not set");
+            return Err(self.stack_not_set());
         }
 
         // adjust that each char is printed with a space between
@@ -382,7 +479,7 @@ impl CPU {
             self._debug_register_cache[i] = *v;
         }
         for i in 0..16 * 4 {
-            self._debug_memory_cache[i] = self.memory_mapper.get_byte(i as Word);
+            self._debug_memory_cache[i] = self.memory_mapper.get_byte(i as Word).unwrap_or(0);
         }
 
         // cache stdout instance
@@ -390,7 +487,7 @@ impl CPU {
 
         // clear screen before starting
         stdout
-            .write(format!("\x1b[2J").as_bytes())
+            .write_all(format!("\x1b[2J").as_bytes())
             .expect("[VM] Debugger display error");
         stdout.flush().expect("[VM] Error flushing stdout");
 
@@ -410,22 +507,24 @@ impl CPU {
                     self.view_memory_at(&mut stdout, offset, false);
                 }
                 Err(_) => {
-                    self.step();
+                    self.step()?;
                     self.debug_registers(&mut stdout, offset, true);
                     self.view_memory_at(&mut stdout, offset, true);
                 }
             }
             std::thread::sleep(std::time::Duration::from_millis(500));
         }
+        Ok(())
     }
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), VmError> {
         if !self.stack_set {
-            panic!("[VM] Stack not set");
+            return Err(self.stack_not_set());
         }
 
         while !self.halt_signal {
-            self.step();
+            self.step()?;
         }
+        Ok(())
     }
 }
@@ -1,8 +1,17 @@
 use macros::reg;
 
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 macro_rules! instr {
+    ($cpu:ident, $val1:ident, checked_div, $val2:ident) => {
+        // a division by zero traps instead of updating the accumulator
+        let res = $val1.checked_div($val2).ok_or(Trap::DivisionByZero)?;
+
+        $cpu.set_reg(reg!("acc"), res);
+
+        $cpu.update_sr($val1, res);
+    };
+
     ($cpu:ident, $val1:ident, $f:ident, $val2:ident) => {
         // calculate the result with given function $f, update accumulator with result
         // and update status register
@@ -15,42 +24,42 @@ macro_rules! instr {
 
     ($cpu:ident, wr, $f:ident) => {
         // fetch word and register
-        let val = $cpu.fetch_word();
+        let val = $cpu.fetch_word()?;
 
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
 
 		instr!($cpu, val, $f, r_val);
     };
 
     ($cpu:ident, rr, $f:ident) => {
         // fetch registers
-        let r1_addr = $cpu.fetch_word();
-        let r2_addr = $cpu.fetch_word();
+        let r1_addr = $cpu.fetch_word()?;
+        let r2_addr = $cpu.fetch_word()?;
 
-        let r1_val = $cpu.get_reg(r1_addr);
-        let r2_val = $cpu.get_reg(r2_addr);
+        let r1_val = $cpu.try_get_reg(r1_addr)?;
+        let r2_val = $cpu.try_get_reg(r2_addr)?;
 
 		instr!($cpu, r1_val, $f, r2_val);
     };
 
     ($cpu:ident, rw, $f:ident) => {
         // fetch register and word
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
 
-        let val = $cpu.fetch_word();
+        let val = $cpu.fetch_word()?;
 
 		instr!($cpu, r_val, $f, val);
     };
 
     ($cpu:ident, cc, $f:ident) => {
         // increment or decrement register
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
         let acc = r_val.$f(1);
 
-        $cpu.set_reg(r_addr, acc);
+        $cpu.try_set_reg(r_addr, acc)?;
 
         $cpu.update_sr(r_val, acc);
     };
@@ -60,97 +69,109 @@ macro_rules! instr {
 /// Add 0x1234 to register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn ADD(cpu: &mut CPU) {
+pub fn ADD(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, wr, wrapping_add);
+    Ok(())
 }
 
 /// ## ADDR r1, r2
 /// Add register r1 and register r2 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn ADDR(cpu: &mut CPU) {
+pub fn ADDR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, wrapping_add);
+    Ok(())
 }
 
 /// ## SUB r1, 0x1234
 /// Subtract 0x1234 from register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn SUB(cpu: &mut CPU) {
+pub fn SUB(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, wrapping_sub);
+    Ok(())
 }
 
 /// ## SUBWR 0x1234, r1
 /// Subtract register r1 from 0x1234 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn SUBWR(cpu: &mut CPU) {
+pub fn SUBWR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, wr, wrapping_sub);
+    Ok(())
 }
 
 /// ## SUBR r1, r2
 /// Subtract register r2 from register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn SUBR(cpu: &mut CPU) {
+pub fn SUBR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, wrapping_sub);
+    Ok(())
 }
 
 /// ## MULT 0x1234, r1
 /// Multiply register r1 by 0x1234 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn MULT(cpu: &mut CPU) {
+pub fn MULT(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, wr, wrapping_mul);
+    Ok(())
 }
 
 /// ## MULTR r1, r2
 /// Multiply register r2 by register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn MULTR(cpu: &mut CPU) {
+pub fn MULTR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, wrapping_mul);
+    Ok(())
 }
 
 /// ## DIV r1, 0x1234
 /// Divide register r1 by 0x1234 and store the result in acc
-/// #### Panics if the divisor is 0
+/// #### Traps with `DivisionByZero` if the divisor is 0
 #[inline]
 #[allow(non_snake_case)]
-pub fn DIV(cpu: &mut CPU) {
-    instr!(cpu, rw, wrapping_div);
+pub fn DIV(cpu: &mut CPU) -> Result<(), Trap> {
+    instr!(cpu, rw, checked_div);
+    Ok(())
 }
 
 /// ## DIVWR 0x1234, r1
 /// Divide 0x1234 by register r1 and store the result in acc
-/// #### Panics if the divisor is 0
+/// #### Traps with `DivisionByZero` if the divisor is 0
 #[inline]
 #[allow(non_snake_case)]
-pub fn DIVWR(cpu: &mut CPU) {
-    instr!(cpu, wr, wrapping_div);
+pub fn DIVWR(cpu: &mut CPU) -> Result<(), Trap> {
+    instr!(cpu, wr, checked_div);
+    Ok(())
 }
 
 /// ## DIVR r1, r2
 /// Divide register r2 by register r1 and store the result in acc
-/// #### Panics if the divisor is 0
+/// #### Traps with `DivisionByZero` if the divisor is 0
 #[inline]
 #[allow(non_snake_case)]
-pub fn DIVR(cpu: &mut CPU) {
-    instr!(cpu, rr, wrapping_div);
+pub fn DIVR(cpu: &mut CPU) -> Result<(), Trap> {
+    instr!(cpu, rr, checked_div);
+    Ok(())
 }
 
 /// ## INC r1
 /// Increment register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn INC(cpu: &mut CPU) {
+pub fn INC(cpu: &mut CPU) -> Result<(), Trap> {
 	instr!(cpu, cc, wrapping_add);
+	Ok(())
 }
 
 /// ## DEC r1
 /// Decrement register r1 and store the result in acc
 #[inline]
 #[allow(non_snake_case)]
-pub fn DEC(cpu: &mut CPU) {
+pub fn DEC(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, cc, wrapping_sub);
+    Ok(())
 }
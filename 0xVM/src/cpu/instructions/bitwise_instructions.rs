@@ -1,4 +1,4 @@
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 macro_rules! instr_helper {
     ($cpu:ident, $val1:ident, $f:ident, $val2:ident, $destination:ident) => {
@@ -6,7 +6,7 @@ macro_rules! instr_helper {
         // and update status register
         let res = $val1.$f($val2);
 
-        $cpu.set_reg($destination, res);
+        $cpu.try_set_reg($destination, res)?;
 
         $cpu.update_sr($val1, res);
     };
@@ -16,29 +16,29 @@ macro_rules! instr_helper {
         // and update status register
         let res = $val1 $op $val2;
 
-        $cpu.set_reg($destination, res);
+        $cpu.try_set_reg($destination, res)?;
 
         $cpu.update_sr($val1, res);
     };
 
 	(rw, $cpu:ident) => {{
 		// fetch register value
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
 
         // fetch literal value
-        let value = $cpu.fetch_word();
+        let value = $cpu.fetch_word()?;
 
 		(r_addr, r_val, value)
 	}};
 
 	(rr, $cpu:ident) => {{
 		// fetch register values
-		let r1_addr = $cpu.fetch_word();
-        let r2_addr = $cpu.fetch_word();
+		let r1_addr = $cpu.fetch_word()?;
+        let r2_addr = $cpu.fetch_word()?;
 
-        let r1_val = $cpu.get_reg(r1_addr);
-        let r2_val = $cpu.get_reg(r2_addr);
+        let r1_val = $cpu.try_get_reg(r1_addr)?;
+        let r2_val = $cpu.try_get_reg(r2_addr)?;
 
 		(r1_addr, r2_addr, r1_val, r2_val)
 	}};
@@ -76,124 +76,139 @@ macro_rules! instr {
 /// Shift register r1 left by 0x4
 #[inline]
 #[allow(non_snake_case)]
-pub fn LSF(cpu: &mut CPU) {
+pub fn LSF(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, wrapping_shl);
+    Ok(())
 }
 
 /// ## LSFR r1, r2
 /// Shift register r1 left by register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn LSFR(cpu: &mut CPU) {
+pub fn LSFR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, wrapping_shl);
+    Ok(())
 }
 
 /// ## RSF r1, 0x4
 /// Shift register r1 right by 0x4
 #[inline]
 #[allow(non_snake_case)]
-pub fn RSF(cpu: &mut CPU) {
+pub fn RSF(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, wrapping_shr);
+    Ok(())
 }
 
 /// ## RSFR r1, r2
 /// Shift register r1 right by register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn RSFR(cpu: &mut CPU) {
+pub fn RSFR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, wrapping_shr);
+    Ok(())
 }
 
 /// ## WLSF r1, 0x4
 /// Shift register r1 left by 0x4 wrapping around
 #[inline]
 #[allow(non_snake_case)]
-pub fn WLSF(cpu: &mut CPU) {
+pub fn WLSF(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, rotate_left);
+    Ok(())
 }
 
 /// ## WLSFR r1, r2
 /// Shift register r1 left by register r2 wrapping around
 #[inline]
 #[allow(non_snake_case)]
-pub fn WLSFR(cpu: &mut CPU) {
+pub fn WLSFR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, rotate_left);
+    Ok(())
 }
 
 /// ## WRSF r1, 0x4
 /// Shift register r1 right by 0x4 wrapping around
 #[inline]
 #[allow(non_snake_case)]
-pub fn WRSF(cpu: &mut CPU) {
+pub fn WRSF(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, rotate_right);
+    Ok(())
 }
 
 /// ## WRSFR r1, r2
 /// Shift register r1 right by register r2 wrapping around
 #[inline]
 #[allow(non_snake_case)]
-pub fn WRSFR(cpu: &mut CPU) {
+pub fn WRSFR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, rotate_right);
+    Ok(())
 }
 
 /// ## AND r1, 0x4
 /// Bitwise AND register r1 with 0x4
 #[inline]
 #[allow(non_snake_case)]
-pub fn AND(cpu: &mut CPU) {
+pub fn AND(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, &);
+    Ok(())
 }
 
 /// ## ANDR r1, r2
 /// Bitwise AND register r1 with register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn ANDR(cpu: &mut CPU) {
+pub fn ANDR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, &);
+    Ok(())
 }
 
 /// ## OR r1, 0x4
 /// Bitwise OR register r1 with 0x4
 #[inline]
 #[allow(non_snake_case)]
-pub fn OR(cpu: &mut CPU) {
+pub fn OR(cpu: &mut CPU) -> Result<(), Trap> {
 	instr!(cpu, rw, |);
+	Ok(())
 }
 
 /// ## ORR r1, r2
 /// Bitwise OR register r1 with register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn ORR(cpu: &mut CPU) {
+pub fn ORR(cpu: &mut CPU) -> Result<(), Trap> {
 	instr!(cpu, rr, |);
+	Ok(())
 }
 
 /// ## XOR r1, 0x4
 /// Bitwise XOR register r1 with 0x4
 #[inline]
 #[allow(non_snake_case)]
-pub fn XOR(cpu: &mut CPU) {
+pub fn XOR(cpu: &mut CPU) -> Result<(), Trap> {
 	instr!(cpu, rw, ^);
+	Ok(())
 }
 
 /// ## XORR r1, r2
 /// Bitwise XOR register r1 with register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn XORR(cpu: &mut CPU) {
+pub fn XORR(cpu: &mut CPU) -> Result<(), Trap> {
 	instr!(cpu, rr, ^);
+	Ok(())
 }
 
 /// ## NOT r1
 /// Bitwise NOT register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn NOT(cpu: &mut CPU) {
-    let r_addr = cpu.fetch_word();
-    let register_val = cpu.get_reg(r_addr);
+pub fn NOT(cpu: &mut CPU) -> Result<(), Trap> {
+    let r_addr = cpu.fetch_word()?;
+    let register_val = cpu.try_get_reg(r_addr)?;
     let res = !register_val;
 
-    cpu.set_reg(r_addr, res);
+    cpu.try_set_reg(r_addr, res)?;
 
     cpu.update_sr(register_val, res);
+    Ok(())
 }
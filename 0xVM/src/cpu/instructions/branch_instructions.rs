@@ -1,11 +1,11 @@
 use macros::reg;
 
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 macro_rules! instr {
     ($cpu:ident, w, $op:tt) => {
-        let val = $cpu.fetch_word();
-        let addr = $cpu.fetch_word();
+        let val = $cpu.fetch_word()?;
+        let addr = $cpu.fetch_word()?;
 
         if $cpu.get_reg(reg!("acc")) $op val {
             $cpu.set_reg(reg!("pc"), addr);
@@ -13,10 +13,10 @@ macro_rules! instr {
     };
 
     ($cpu:ident, r, $op:tt) => {
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
 
-        let addr = $cpu.fetch_word();
+        let addr = $cpu.fetch_word()?;
 
         if $cpu.get_reg(reg!("acc")) $op r_val {
             $cpu.set_reg(reg!("pc"), addr);
@@ -24,12 +24,12 @@ macro_rules! instr {
     };
 
     ($cpu:ident, rw, $op:tt) => {
-        let r_addr = $cpu.fetch_word();
-        let r_val = $cpu.get_reg(r_addr);
+        let r_addr = $cpu.fetch_word()?;
+        let r_val = $cpu.try_get_reg(r_addr)?;
 
-        let val = $cpu.fetch_word();
+        let val = $cpu.fetch_word()?;
 
-        let addr = $cpu.fetch_word();
+        let addr = $cpu.fetch_word()?;
 
         if r_val $op val {
             $cpu.set_reg(reg!("pc"), addr);
@@ -37,13 +37,13 @@ macro_rules! instr {
     };
 
     ($cpu:ident, rr, $op:tt) => {
-        let r1_addr = $cpu.fetch_word();
-        let r1_val = $cpu.get_reg(r1_addr);
+        let r1_addr = $cpu.fetch_word()?;
+        let r1_val = $cpu.try_get_reg(r1_addr)?;
 
-        let r2_addr = $cpu.fetch_word();
-        let r2_val = $cpu.get_reg(r2_addr);
+        let r2_addr = $cpu.fetch_word()?;
+        let r2_val = $cpu.try_get_reg(r2_addr)?;
 
-        let addr = $cpu.fetch_word();
+        let addr = $cpu.fetch_word()?;
 
         if r1_val $op r2_val {
             $cpu.set_reg(reg!("pc"), addr);
@@ -55,214 +55,240 @@ macro_rules! instr {
 /// If the flag Z is set, jump to 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRBS(cpu: &mut CPU) {
-    let flag = cpu.fetch_byte();
-    let addr = cpu.fetch_word();
+pub fn BRBS(cpu: &mut CPU) -> Result<(), Trap> {
+    let flag = cpu.fetch_byte()?;
+    let addr = cpu.fetch_word()?;
     if cpu.get_status_flag(flag) {
         cpu.set_reg(reg!("pc"), addr);
     }
+    Ok(())
 }
 
 /// ## BRBC FLAG_Z, 0xAF
 /// If the flag Z is clear, jump to 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRBC(cpu: &mut CPU) {
-    let flag = cpu.fetch_byte();
-    let addr = cpu.fetch_word();
+pub fn BRBC(cpu: &mut CPU) -> Result<(), Trap> {
+    let flag = cpu.fetch_byte()?;
+    let addr = cpu.fetch_word()?;
     if !cpu.get_status_flag(flag) {
         cpu.set_reg(reg!("pc"), addr);
     }
+    Ok(())
 }
 
 /// ## BREQ 0x1234, 0x5
 /// Jump to 0x5 if acc does equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BREQ(cpu: &mut CPU) {
+pub fn BREQ(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, ==);
+    Ok(())
 }
 
 /// ## BREQR r1, 0x5
 /// Jump to 0x5 if acc does equal register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BREQR(cpu: &mut CPU) {
+pub fn BREQR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, ==);
+    Ok(())
 }
 
 /// ## BREQRW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 does equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BREQRW(cpu: &mut CPU) {
+pub fn BREQRW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, ==);
+    Ok(())
 }
 
 /// ## BREQRR r1, r2, 0x5
 /// Jump to 0x5 if register r1 does equal register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BREQRR(cpu: &mut CPU) {
+pub fn BREQRR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, ==);
+    Ok(())
 }
 
 /// ## BRNQ 0x1234, 0x5
 /// Jump to 0x5 if acc does not equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRNQ(cpu: &mut CPU) {
+pub fn BRNQ(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, !=);
+    Ok(())
 }
 
 /// ## BRNQR r1, 0x5
 /// Jump to 0x5 if acc does not equal register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRNQR(cpu: &mut CPU) {
+pub fn BRNQR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, !=);
+    Ok(())
 }
 
 /// ## BRNQRW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 does not equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRNQRW(cpu: &mut CPU) {
+pub fn BRNQRW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, !=);
+    Ok(())
 }
 
 /// ## BRNQRR r1, r2, 0x5
 /// Jump to 0x5 if register r1 does not equal register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRNQRR(cpu: &mut CPU) {
+pub fn BRNQRR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, !=);
+    Ok(())
 }
 
 /// ## BRLT 0x1234, 0x5
 /// Jump to 0x5 if acc is less than 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLT(cpu: &mut CPU) {
+pub fn BRLT(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, <);
+    Ok(())
 }
 
 /// ## BRLTR r1, 0x5
 /// Jump to 0x5 if acc is less than register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTR(cpu: &mut CPU) {
+pub fn BRLTR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, <);
+    Ok(())
 }
 
 /// ## BRLTRW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 is less than 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTRW(cpu: &mut CPU) {
+pub fn BRLTRW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, <);
+    Ok(())
 }
 
 /// ## BRLTRR r1, r2, 0x5
 /// Jump to 0x5 if register r1 is less than register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTRR(cpu: &mut CPU) {
+pub fn BRLTRR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, <);
+    Ok(())
 }
 
 /// ## BRGT 0x1234, 0x5
 /// Jump to 0x5 if acc is greater than 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGT(cpu: &mut CPU) {
+pub fn BRGT(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, >);
+    Ok(())
 }
 
 /// ## BRGTR r1, 0x5
 /// Jump to 0x5 if acc is greater than register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTR(cpu: &mut CPU) {
+pub fn BRGTR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, >);
+    Ok(())
 }
 
 /// ## BRGTRW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 is greater than 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTRW(cpu: &mut CPU) {
+pub fn BRGTRW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, >);
+    Ok(())
 }
 
 /// ## BRGTRR r1, r2, 0x5
 /// Jump to 0x5 if register r1 is greater than register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTRR(cpu: &mut CPU) {
+pub fn BRGTRR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, >);
+    Ok(())
 }
 
 /// ## BRLTE 0x1234, 0x5
 /// Jump to 0x5 if acc is less than or equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTE(cpu: &mut CPU) {
+pub fn BRLTE(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, <=);
+    Ok(())
 }
 
 /// ## BRLTER r1, 0x5
 /// Jump to 0x5 if acc is less than or equal register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTER(cpu: &mut CPU) {
+pub fn BRLTER(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, <=);
+    Ok(())
 }
 
 /// ## BRLTERW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 is less than or equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTERW(cpu: &mut CPU) {
+pub fn BRLTERW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, <=);
+    Ok(())
 }
 
 /// ## BRLTERR r1, r2, 0x5
 /// Jump to 0x5 if register r1 is less than register or equal r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRLTERR(cpu: &mut CPU) {
+pub fn BRLTERR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, <=);
+    Ok(())
 }
 
 /// ## BRGTE 0x1234, 0x5
 /// Jump to 0x5 if acc is greater than or equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTE(cpu: &mut CPU) {
+pub fn BRGTE(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, w, >=);
+    Ok(())
 }
 
 /// ## BRGTER r1, 0x5
 /// Jump to 0x5 if acc is greater than register or equal r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTER(cpu: &mut CPU) {
+pub fn BRGTER(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, r, >=);
+    Ok(())
 }
 
 /// ## BRGTERW r1, 0x1234, 0x5
 /// Jump to 0x5 if register r1 is greater than or equal 0x1234
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTERW(cpu: &mut CPU) {
+pub fn BRGTERW(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rw, >=);
+    Ok(())
 }
 
 /// ## BRGTERR r1, r2, 0x5
 /// Jump to 0x5 if register r1 is greater than or equal register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn BRGTERR(cpu: &mut CPU) {
+pub fn BRGTERR(cpu: &mut CPU) -> Result<(), Trap> {
     instr!(cpu, rr, >=);
+    Ok(())
 }
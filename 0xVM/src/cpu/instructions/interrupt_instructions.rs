@@ -0,0 +1,51 @@
+use macros::reg;
+
+use crate::{
+	cpu::{CPU, FLAG_INTERRUPTS},
+	trap::Trap,
+};
+
+/// ## INT 0x3
+/// Enter the handler of interrupt vector 0x3, regardless of whether interrupts are enabled
+#[inline]
+#[allow(non_snake_case)]
+pub fn INT(cpu: &mut CPU) -> Result<(), Trap> {
+	let vector = cpu.fetch_word()?;
+
+	cpu.interrupt(vector)?;
+	Ok(())
+}
+
+/// ## IRET
+/// Return from an interrupt handler, restoring the registers, acc and sr of the interrupted code
+#[inline]
+#[allow(non_snake_case)]
+pub fn IRET(cpu: &mut CPU) -> Result<(), Trap> {
+	let args = cpu.pop_state()?;
+
+	if let Some(sr) = args.first() {
+		cpu.set_reg(reg!("sr"), *sr);
+	}
+	if let Some(acc) = args.get(1) {
+		cpu.set_reg(reg!("acc"), *acc);
+	}
+	Ok(())
+}
+
+/// ## EI
+/// Enable hardware interrupts
+#[inline]
+#[allow(non_snake_case)]
+pub fn EI(cpu: &mut CPU) -> Result<(), Trap> {
+	cpu.set_status_flag(FLAG_INTERRUPTS, true);
+	Ok(())
+}
+
+/// ## DI
+/// Disable hardware interrupts
+#[inline]
+#[allow(non_snake_case)]
+pub fn DI(cpu: &mut CPU) -> Result<(), Trap> {
+	cpu.set_status_flag(FLAG_INTERRUPTS, false);
+	Ok(())
+}
@@ -4,3 +4,4 @@ mod arithmetic_instructions; pub use arithmetic_instructions::*;
 mod branch_instructions; pub use branch_instructions::*;
 mod subroutine_instructions; pub use subroutine_instructions::*;
 mod bitwise_instructions; pub use bitwise_instructions::*;
+mod interrupt_instructions; pub use interrupt_instructions::*;
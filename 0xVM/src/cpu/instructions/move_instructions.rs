@@ -1,104 +1,111 @@
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 /// ## MOVR 0x1234, r1
 /// Move 0x1234 into register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVR(cpu: &mut CPU) {
-    let val = cpu.fetch_word();
-    let r_addr = cpu.fetch_word();
-    cpu.set_reg(r_addr, val);
+pub fn MOVR(cpu: &mut CPU) -> Result<(), Trap> {
+    let val = cpu.fetch_word()?;
+    let r_addr = cpu.fetch_word()?;
+    cpu.try_set_reg(r_addr, val)?;
+    Ok(())
 }
 
 /// ## MOVM 0x1234, 0xAF
 /// Move 0x1234 into memory at 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVM(cpu: &mut CPU) {
-    let val = cpu.fetch_word();
-    let m_addr = cpu.fetch_word();
-    cpu.memory_mapper.set_word(m_addr, val);
+pub fn MOVM(cpu: &mut CPU) -> Result<(), Trap> {
+    let val = cpu.fetch_word()?;
+    let m_addr = cpu.fetch_word()?;
+    cpu.memory_mapper.set_word(m_addr, val)?;
+    Ok(())
 }
 
 /// ## MOVRR r1, r2
 /// Move register r1 into register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVRR(cpu: &mut CPU) {
-    let r1_addr = cpu.fetch_word();
-    let r2_addr = cpu.fetch_word();
-    cpu.set_reg(r2_addr, cpu.get_reg(r1_addr));
+pub fn MOVRR(cpu: &mut CPU) -> Result<(), Trap> {
+    let r1_addr = cpu.fetch_word()?;
+    let r2_addr = cpu.fetch_word()?;
+    cpu.try_set_reg(r2_addr, cpu.try_get_reg(r1_addr)?)?;
+    Ok(())
 }
 
 /// ## MOVRM r1, 0xAF
 /// Move register r1 into memory ar 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVRM(cpu: &mut CPU) {
-    let r_addr = cpu.fetch_word();
-    let m_addr = cpu.fetch_word();
-    cpu.memory_mapper.set_word(m_addr, cpu.get_reg(r_addr));
+pub fn MOVRM(cpu: &mut CPU) -> Result<(), Trap> {
+    let r_addr = cpu.fetch_word()?;
+    let m_addr = cpu.fetch_word()?;
+    cpu.memory_mapper.set_word(m_addr, cpu.try_get_reg(r_addr)?)?;
+    Ok(())
 }
 
 /// ## MOVMR 0xAF, r1
 /// Move memory at 0xAF into register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVMR(cpu: &mut CPU) {
-    let m_addr = cpu.fetch_word();
-    let r_addr = cpu.fetch_word();
-    cpu.set_reg(r_addr, cpu.memory_mapper.get_word(m_addr));
+pub fn MOVMR(cpu: &mut CPU) -> Result<(), Trap> {
+    let m_addr = cpu.fetch_word()?;
+    let r_addr = cpu.fetch_word()?;
+    cpu.try_set_reg(r_addr, cpu.memory_mapper.get_word(m_addr)?)?;
+    Ok(())
 }
 
 /// ## MOVRPR r1, r2
 /// Move data pointed at by register r1 into register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVRPR(cpu: &mut CPU) {
-    let r1_addr = cpu.fetch_word();
-    let r2_addr = cpu.fetch_word();
-    let data_addr = cpu.get_reg(r1_addr);
+pub fn MOVRPR(cpu: &mut CPU) -> Result<(), Trap> {
+    let r1_addr = cpu.fetch_word()?;
+    let r2_addr = cpu.fetch_word()?;
+    let data_addr = cpu.try_get_reg(r1_addr)?;
 
-    cpu.set_reg(r2_addr, cpu.memory_mapper.get_word(data_addr));
+    cpu.try_set_reg(r2_addr, cpu.memory_mapper.get_word(data_addr)?)?;
+    Ok(())
 }
 
 /// ## MOVROR r1, 0x2, r2
 /// Move data pointed at by register r1 plus an offset 0x2 into register r2
 #[inline]
 #[allow(non_snake_case)]
-pub fn MOVROR(cpu: &mut CPU) {
-    let r1_addr = cpu.fetch_word();
-    let offset = cpu.fetch_word();
-    let r2_addr = cpu.fetch_word();
-    let data_addr = cpu.get_reg(r1_addr) + offset;
-
-    cpu.set_reg(r2_addr, cpu.memory_mapper.get_word(data_addr));
+pub fn MOVROR(cpu: &mut CPU) -> Result<(), Trap> {
+    let r1_addr = cpu.fetch_word()?;
+    let offset = cpu.fetch_word()?;
+    let r2_addr = cpu.fetch_word()?;
+    let data_addr = cpu.try_get_reg(r1_addr)? + offset;
+
+    cpu.try_set_reg(r2_addr, cpu.memory_mapper.get_word(data_addr)?)?;
+    Ok(())
 }
 
 macro_rules! instr {
     (l, $cpu:ident) => {{
-        let addr_ptr = $cpu.fetch_word();
-        let addr = $cpu.get_reg(addr_ptr);
+        let addr_ptr = $cpu.fetch_word()?;
+        let addr = $cpu.try_get_reg(addr_ptr)?;
 
         (addr, instr!(size, $cpu))
     }};
 
     (s, $cpu:ident) => {{
         let size = instr!(size, $cpu);
-        let dest_ptr = $cpu.fetch_word();
-        let dest = $cpu.get_reg(dest_ptr);
+        let dest_ptr = $cpu.fetch_word()?;
+        let dest = $cpu.try_get_reg(dest_ptr)?;
 
         (size, dest)
     }};
 
     (op, $cpu:ident, $addr:ident, $size:ident, $dest:ident) => {
-        let temp = $cpu.memory_mapper.get_range($addr, $size);
-        $cpu.memory_mapper.set_range($dest, temp);
+        let temp = $cpu.memory_mapper.get_range($addr, $size)?;
+        $cpu.memory_mapper.set_range($dest, temp)?;
     };
 
     (size, $cpu:ident) => {{
-        let size_reg = $cpu.fetch_word();
-        $cpu.get_reg(size_reg)
+        let size_reg = $cpu.fetch_word()?;
+        $cpu.try_get_reg(size_reg)?
     }};
 }
 
@@ -106,9 +113,9 @@ macro_rules! instr {
 /// Load R2 bytes from device at R1* to memory at 0x1238-0x1238 + R2
 #[inline]
 #[allow(non_snake_case)]
-pub fn LOAD(cpu: &mut CPU) {
+pub fn LOAD(cpu: &mut CPU) -> Result<(), Trap> {
     let (addr, size) = instr!(l, cpu);
-    let dest = cpu.fetch_word();
+    let dest = cpu.fetch_word()?;
 
     instr!(op, cpu, addr, size, dest);
     /*
@@ -121,16 +128,17 @@ pub fn LOAD(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(addr, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
 
 /// ## LOADR R1, R2, R3
 /// Load R2 bytes from device at R1* to memory at R3*-R3* + R2
 #[inline]
 #[allow(non_snake_case)]
-pub fn LOADR(cpu: &mut CPU) {
+pub fn LOADR(cpu: &mut CPU) -> Result<(), Trap> {
     let (addr, size) = instr!(l, cpu);
-    let dest_ptr = cpu.fetch_word();
-    let dest = cpu.get_reg(dest_ptr);
+    let dest_ptr = cpu.fetch_word()?;
+    let dest = cpu.try_get_reg(dest_ptr)?;
 
     instr!(op, cpu, addr, size, dest);
     /*
@@ -144,16 +152,17 @@ pub fn LOADR(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(addr, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
 
 /// ## LOADM R1, R2, 0x1238
 /// Load R2 bytes from device at R1* to memory at 0x1238*-0x1238* + R2
 #[inline]
 #[allow(non_snake_case)]
-pub fn LOADM(cpu: &mut CPU) {
+pub fn LOADM(cpu: &mut CPU) -> Result<(), Trap> {
     let (addr, size) = instr!(l, cpu);
-    let dest_ptr = cpu.fetch_word();
-    let dest = cpu.memory_mapper.get_word(dest_ptr);
+    let dest_ptr = cpu.fetch_word()?;
+    let dest = cpu.memory_mapper.get_word(dest_ptr)?;
 
     instr!(op, cpu, addr, size, dest);
     /*
@@ -167,14 +176,15 @@ pub fn LOADM(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(addr, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
 
 /// ## STORE 0x1238, R2, R1
 /// Store R2 bytes from memory at 0x1238-0x1238 + R2 to device at R1*
 #[inline]
 #[allow(non_snake_case)]
-pub fn STORE(cpu: &mut CPU) {
-    let src = cpu.fetch_word();
+pub fn STORE(cpu: &mut CPU) -> Result<(), Trap> {
+    let src = cpu.fetch_word()?;
     let (size, dest) = instr!(s, cpu);
 
     instr!(op, cpu, src, size, dest);
@@ -188,15 +198,16 @@ pub fn STORE(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(src, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
 
 /// ## STORER R3, R2, R1
 /// Store R2 bytes from memory at R3*-R3* + R2 to device at R1*
 #[inline]
 #[allow(non_snake_case)]
-pub fn STORER(cpu: &mut CPU) {
-    let src_ptr = cpu.fetch_word();
-    let src = cpu.get_reg(src_ptr);
+pub fn STORER(cpu: &mut CPU) -> Result<(), Trap> {
+    let src_ptr = cpu.fetch_word()?;
+    let src = cpu.try_get_reg(src_ptr)?;
     let (size, dest) = instr!(s, cpu);
 
     instr!(op, cpu, src, size, dest);
@@ -211,15 +222,16 @@ pub fn STORER(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(src, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
 
 /// ## STOREM 0x1238, R2, R1
 /// Store R2 bytes from memory at 0x1238*-0x1238* + R2 to device at R1*
 #[inline]
 #[allow(non_snake_case)]
-pub fn STOREM(cpu: &mut CPU) {
-    let src_ptr = cpu.fetch_word();
-    let src = cpu.memory_mapper.get_word(src_ptr);
+pub fn STOREM(cpu: &mut CPU) -> Result<(), Trap> {
+    let src_ptr = cpu.fetch_word()?;
+    let src = cpu.memory_mapper.get_word(src_ptr)?;
     let (size, dest) = instr!(s, cpu);
 
     instr!(op, cpu, src, size, dest);
@@ -234,4 +246,5 @@ pub fn STOREM(cpu: &mut CPU) {
     let temp = cpu.memory_mapper.get_range(src, size);
     cpu.memory_mapper.set_range(dest, temp);
     */
+    Ok(())
 }
@@ -1,32 +1,35 @@
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 /// ## POP r1
 /// Pop val from stack into register r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn POP(cpu: &mut CPU) {
-    let r_addr = cpu.fetch_word();
-    let val = cpu.pop();
-    cpu.set_reg(r_addr, val);
+pub fn POP(cpu: &mut CPU) -> Result<(), Trap> {
+    let r_addr = cpu.fetch_word()?;
+    let val = cpu.pop()?;
+    cpu.try_set_reg(r_addr, val)?;
+    Ok(())
 }
 
 /// ## PUSH 0x1234 
 /// Push 0x1234 onto the stack
 #[inline]
 #[allow(non_snake_case)]
-pub fn PUSH(cpu: &mut CPU) {
-    let val = cpu.fetch_word();
+pub fn PUSH(cpu: &mut CPU) -> Result<(), Trap> {
+    let val = cpu.fetch_word()?;
 
-    cpu.push(val);
+    cpu.push(val)?;
+    Ok(())
 }
 
 /// ## PUSHR r1 
 /// Push register r1 onto stack
 #[inline]
 #[allow(non_snake_case)]
-pub fn PUSHR(cpu: &mut CPU) {
-	let r_addr = cpu.fetch_word();
-	let val = cpu.get_reg(r_addr);
+pub fn PUSHR(cpu: &mut CPU) -> Result<(), Trap> {
+	let r_addr = cpu.fetch_word()?;
+	let val = cpu.try_get_reg(r_addr)?;
 
-	cpu.push(val);
+	cpu.push(val)?;
+	Ok(())
 }
\ No newline at end of file
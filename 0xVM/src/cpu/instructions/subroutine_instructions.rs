@@ -1,46 +1,50 @@
 use macros::reg;
 
-use crate::cpu::CPU;
+use crate::{cpu::CPU, trap::Trap};
 
 /// ## JMP 0xAF
 /// Jumps to addr 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn JMP(cpu: &mut CPU) {
-	let addr = cpu.fetch_word();
+pub fn JMP(cpu: &mut CPU) -> Result<(), Trap> {
+	let addr = cpu.fetch_word()?;
 
 	cpu.set_reg(reg!("pc"), addr);
+	Ok(())
 }
 
 /// ## CALL 0xAF 
 /// Call subroutine at 0xAF
 #[inline]
 #[allow(non_snake_case)]
-pub fn CALL(cpu: &mut CPU) {
-	let addr = cpu.fetch_word();
+pub fn CALL(cpu: &mut CPU) -> Result<(), Trap> {
+	let addr = cpu.fetch_word()?;
 
-	cpu.push_state();
+	cpu.push_state()?;
 
 	cpu.set_reg(reg!("pc"), addr);
+	Ok(())
 }
 
 /// ## CALLR r1 
 /// Call subroutine at r1
 #[inline]
 #[allow(non_snake_case)]
-pub fn CALLR(cpu: &mut CPU) {
-	let r_addr = cpu.fetch_word();
-	let addr = cpu.get_reg(r_addr);
+pub fn CALLR(cpu: &mut CPU) -> Result<(), Trap> {
+	let r_addr = cpu.fetch_word()?;
+	let addr = cpu.try_get_reg(r_addr)?;
 
-	cpu.push_state();
+	cpu.push_state()?;
 
 	cpu.set_reg(reg!("pc"), addr);
+	Ok(())
 }
 
 /// ## RET 
 /// Return from subroutine
 #[inline]
 #[allow(non_snake_case)]
-pub fn RET(cpu: &mut CPU) {
-	cpu.pop_state();
+pub fn RET(cpu: &mut CPU) -> Result<(), Trap> {
+	cpu.pop_state()?;
+	Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+
+/// Instructions that make up a second of a recording timed by `CastClock::Instructions`
+pub const CAST_INSTRUCTIONS_PER_SECOND: u64 = 1_000_000;
+
+/// What the timestamps of a recording count
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CastClock {
+    /// Time passed on the host since the recording started
+    #[default]
+    Wall,
+    /// Executed instructions, `CAST_INSTRUCTIONS_PER_SECOND` of them make up a second, so
+    /// recordings of a program are the same on every host
+    Instructions,
+}
+
+impl CastClock {
+    /// Clock named `wall` or `instructions`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wall" => Some(CastClock::Wall),
+            "instructions" => Some(CastClock::Instructions),
+            _ => None,
+        }
+    }
+}
+
+/// Writes terminal output to an asciinema v2 cast file: a JSON header followed by a
+/// `[seconds, "o", data]` line per output event
+pub struct CastRecorder {
+    file: BufWriter<File>,
+    clock: CastClock,
+    start: Instant,
+}
+
+impl CastRecorder {
+    /// Creates the cast file for a terminal of `columns` by `rows`
+    pub fn create<P: AsRef<Path>>(path: P, columns: usize, rows: usize, clock: CastClock) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        writeln!(
+            file,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}",
+            columns, rows, timestamp
+        )?;
+        Ok(CastRecorder {
+            file,
+            clock,
+            start: Instant::now(),
+        })
+    }
+
+    /// Records the output, `instructions` counts the instructions executed so far. NUL bytes are
+    /// dropped and invalid UTF-8 is replaced
+    pub fn output(&mut self, instructions: u64, data: &[u8]) -> io::Result<()> {
+        let seconds = match self.clock {
+            CastClock::Wall => self.start.elapsed().as_secs_f64(),
+            CastClock::Instructions => instructions as f64 / CAST_INSTRUCTIONS_PER_SECOND as f64,
+        };
+        let data: Vec<u8> = data.iter().copied().filter(|byte| *byte != 0).collect();
+        let data = serde_json::to_string(&String::from_utf8_lossy(&data)).map_err(io::Error::other)?;
+        writeln!(self.file, "[{:.6}, \"o\", {}]", seconds, data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[derive(Deserialize)]
+struct CastHeader {
+    version: u32,
+    width: usize,
+    height: usize,
+}
+
+/// A recording read back from an asciinema v2 cast file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cast {
+    pub width: usize,
+    pub height: usize,
+    /// Seconds since the start of the recording and the output at that time, input and other
+    /// events are skipped
+    pub events: Vec<(f64, String)>,
+}
+
+impl Cast {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        let header = lines.next().ok_or_else(|| invalid("empty cast file".to_string()))??;
+        let header: CastHeader =
+            serde_json::from_str(&header).map_err(|err| invalid(format!("invalid cast header: {}", err)))?;
+        if header.version != 2 {
+            return Err(invalid(format!("unsupported cast version {}", header.version)));
+        }
+
+        let mut events = Vec::new();
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (time, kind, data): (f64, String, String) =
+                serde_json::from_str(&line).map_err(|err| invalid(format!("invalid event {}: {}", n + 1, err)))?;
+            if kind == "o" {
+                events.push((time, data));
+            }
+        }
+
+        Ok(Cast {
+            width: header.width,
+            height: header.height,
+            events,
+        })
+    }
+
+    /// Writes the output events to the terminal as they were recorded, `speed` 2 replays twice as
+    /// fast and 0 without any pauses. A pause too long to wait for fails with `InvalidData`
+    pub fn play<W: Write>(&self, output: &mut W, speed: f64) -> io::Result<()> {
+        let mut last = 0.0;
+        for (time, data) in &self.events {
+            if speed > 0.0 && *time > last {
+                let pause = Duration::try_from_secs_f64((time - last) / speed).map_err(|_| {
+                    let message = format!("can't pause {} seconds at speed {}", time - last, speed);
+                    io::Error::new(io::ErrorKind::InvalidData, message)
+                })?;
+                thread::sleep(pause);
+            }
+            last = last.max(*time);
+            output.write_all(data.as_bytes())?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
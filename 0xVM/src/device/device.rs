@@ -1,24 +1,37 @@
-use crate::memory::{Byte, Word};
+use crate::{
+    memory::{Byte, Word},
+    trap::Trap,
+};
 
+/// A device mapped into the address space, addresses are relative to the start of its region.
+/// Accesses a device doesn't implement trap with `Trap::Unsupported`
 pub trait Device {
-    fn get_word(&self, _: Word) -> Word {
-        panic!("[DEVICE] Device '{}' didn't implement 'get_word()'", std::any::type_name_of_val(self));
+    fn get_word(&self, _: Word) -> Result<Word, Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "get_word"))
     }
-    fn set_word(&mut self, _: Word, _: Word) -> () {
-        panic!("[DEVICE] Device '{}' didn't implement 'set_word()'", std::any::type_name_of_val(self));
+    fn set_word(&mut self, _: Word, _: Word) -> Result<(), Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "set_word"))
     }
 
-    fn get_byte(&self, _: Word) -> Byte {
-        panic!("[DEVICE] Device '{}' didn't implement 'get_byte()'", std::any::type_name_of_val(self));
+    fn get_byte(&self, _: Word) -> Result<Byte, Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "get_byte"))
     }
-    fn set_byte(&mut self, _: Word, _: Byte) -> () {
-        panic!("[DEVICE] Device '{}' didn't implement 'set_byte()'", std::any::type_name_of_val(self));
+    fn set_byte(&mut self, _: Word, _: Byte) -> Result<(), Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "set_byte"))
     }
 
-    fn get_range(&self, _: Word, _: Word) -> Vec<Byte> {
-        panic!("[DEVICE] Device '{}' didn't implement 'get_range()'", std::any::type_name_of_val(self));
+    fn get_range(&self, _: Word, _: Word) -> Result<Vec<Byte>, Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "get_range"))
     }
-    fn set_range(&mut self, _: Word, _: Vec<Byte>) -> () {
-        panic!("[DEVICE] Device '{}' didn't implement 'set_range()'", std::any::type_name_of_val(self));
+    fn set_range(&mut self, _: Word, _: Vec<Byte>) -> Result<(), Trap> {
+        Err(Trap::Unsupported(std::any::type_name_of_val(self), "set_range"))
+    }
+
+    /// Called after every executed instruction, for devices that keep time
+    fn tick(&mut self) {}
+
+    /// Writes buffered data back to the host, e.g. when the machine exits
+    fn flush(&mut self) -> Result<(), Trap> {
+        Ok(())
     }
 }
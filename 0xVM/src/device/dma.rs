@@ -0,0 +1,171 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    memory::{MemoryMapper, Word},
+    trap::Trap,
+};
+
+use super::{Device, IrqLine};
+
+/// Bytes the DMA controller's register block takes
+pub const DMA_REGISTERS: Word = 0x1C;
+
+/// Control register bit starting a transfer of `length` bytes, writing control without it aborts
+/// the transfer in progress
+pub const DMA_START: Word = 1;
+/// Control register bit raising the controller's IRQ line when the transfer ends
+pub const DMA_INTERRUPT: Word = 1 << 1;
+/// Control register bit keeping the source address, for reading a device's data register
+pub const DMA_FIXED_SOURCE: Word = 1 << 2;
+/// Control register bit keeping the destination address, for writing a device's data register
+pub const DMA_FIXED_DESTINATION: Word = 1 << 3;
+
+/// Status register bit set while a transfer is in progress
+pub const DMA_BUSY: Word = 1;
+/// Status register bit set once all bytes of a transfer were moved
+pub const DMA_DONE: Word = 1 << 1;
+/// Status register bit set when a transfer stopped on an access the source or destination rejected
+pub const DMA_ERROR: Word = 1 << 2;
+
+#[derive(Default)]
+struct DmaState {
+    source: Word,
+    destination: Word,
+    length: Word,
+    control: Word,
+    burst: Word,
+    status: Word,
+    remaining: Word,
+    irq: Option<IrqLine>,
+}
+
+impl DmaState {
+    fn finish(&mut self, status: Word) {
+        self.status = (self.status & !DMA_BUSY) | status;
+        if self.control & DMA_INTERRUPT != 0 {
+            if let Some(irq) = &self.irq {
+                irq.raise();
+            }
+        }
+    }
+}
+
+/// Transfer state shared by the DMA controller's registers and the CPU, which moves the bytes
+/// between instructions
+#[derive(Clone, Default)]
+pub struct Dma {
+    state: Rc<RefCell<DmaState>>,
+}
+
+impl Dma {
+    /// Channel that is only polled through its status register
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Channel raising the IRQ line when a transfer ends while `DMA_INTERRUPT` is set
+    pub fn with_irq(irq: IrqLine) -> Self {
+        let dma = Self::new();
+        dma.state.borrow_mut().irq = Some(irq);
+        dma
+    }
+
+    /// Moves the next burst of the transfer in progress from source to destination
+    pub fn tick(&self, memory_mapper: &mut MemoryMapper) {
+        let (source, destination, size) = {
+            let state = self.state.borrow();
+            if state.status & DMA_BUSY == 0 {
+                return;
+            }
+            let size = match state.burst {
+                0 => state.remaining,
+                burst => burst.min(state.remaining),
+            };
+            (state.source, state.destination, size)
+        };
+
+        // the state isn't borrowed while bytes move, a transfer touching the controller's own registers
+        // is rejected by the controller like any other region without range accesses
+        let moved = match size {
+            0 => Ok(()),
+            _ => memory_mapper
+                .get_range(source, size)
+                .and_then(|bytes| memory_mapper.set_range(destination, bytes)),
+        };
+
+        let mut state = self.state.borrow_mut();
+        if moved.is_err() {
+            return state.finish(DMA_ERROR);
+        }
+        if state.control & DMA_FIXED_SOURCE == 0 {
+            state.source = state.source.wrapping_add(size);
+        }
+        if state.control & DMA_FIXED_DESTINATION == 0 {
+            state.destination = state.destination.wrapping_add(size);
+        }
+        state.remaining -= size;
+        if state.remaining == 0 {
+            state.finish(DMA_DONE);
+        }
+    }
+}
+
+/// DMA controller moving bytes between any mapped regions, the registers are words:
+/// - 0x00 source address, 0x04 destination address, both advance as bytes are moved unless fixed
+/// - 0x08 number of bytes to move
+/// - 0x0C control, see `DMA_START`, `DMA_INTERRUPT`, `DMA_FIXED_SOURCE` and `DMA_FIXED_DESTINATION`
+/// - 0x10 burst, the bytes moved after every instruction, 0 moves the whole transfer at once
+/// - 0x14 status, see `DMA_BUSY`, `DMA_DONE` and `DMA_ERROR`, writing a word clears the done and
+///   error bits set in it
+/// - 0x18 bytes left to move
+///
+/// The registers don't support range accesses, so a transfer from or to them ends with `DMA_ERROR`
+/// before any byte moves
+pub struct DmaController {
+    dma: Dma,
+}
+
+impl DmaController {
+    pub fn new(dma: Dma) -> Self {
+        DmaController { dma }
+    }
+}
+
+impl Device for DmaController {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        let state = self.dma.state.borrow();
+        match addr {
+            0x00 => Ok(state.source),
+            0x04 => Ok(state.destination),
+            0x08 => Ok(state.length),
+            0x0C => Ok(state.control),
+            0x10 => Ok(state.burst),
+            0x14 => Ok(state.status),
+            0x18 => Ok(state.remaining),
+            _ => Err(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        let mut state = self.dma.state.borrow_mut();
+        match addr {
+            0x00 => state.source = value,
+            0x04 => state.destination = value,
+            0x08 => state.length = value,
+            0x0C => {
+                state.control = value;
+                if value & DMA_START != 0 {
+                    state.remaining = state.length;
+                    state.status = DMA_BUSY;
+                } else {
+                    state.remaining = 0;
+                    state.status &= !DMA_BUSY;
+                }
+            }
+            0x10 => state.burst = value,
+            0x14 => state.status &= !(value & (DMA_DONE | DMA_ERROR)),
+            _ => return Err(Trap::InvalidAddress(addr)),
+        }
+        Ok(())
+    }
+}
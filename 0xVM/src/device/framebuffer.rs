@@ -0,0 +1,249 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    memory::{Byte, Word},
+    trap::Trap,
+};
+
+use super::Device;
+
+/// Control register bit ending a frame, which is written out every `every` frames
+pub const FRAMEBUFFER_PRESENT: Word = 1;
+/// Control register bit writing the current frame out right away
+pub const FRAMEBUFFER_DUMP: Word = 1 << 1;
+/// Offset of the palette, 256 entries of red, green, blue and alpha bytes
+pub const FRAMEBUFFER_PALETTE: Word = 0x20;
+/// Offset of the pixels, row by row from the top left corner
+pub const FRAMEBUFFER_PIXELS: Word = FRAMEBUFFER_PALETTE + 256 * 4;
+
+/// How the framebuffer stores a pixel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PixelFormat {
+    /// A byte indexing the palette
+    #[default]
+    Indexed,
+    /// Red, green, blue and alpha bytes
+    Rgba,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> Word {
+        match self {
+            PixelFormat::Indexed => 1,
+            PixelFormat::Rgba => 4,
+        }
+    }
+}
+
+/// File format frames are written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary PPM, the alpha channel is dropped
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Format named by the extension of the path, `.ppm` or `.png`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Pixel framebuffer written out as image files, the registers are words:
+/// - 0x00 width and 0x04 height in pixels
+/// - 0x08 bytes per pixel, 1 for `PixelFormat::Indexed` and 4 for `PixelFormat::Rgba`
+/// - 0x0C control, see `FRAMEBUFFER_PRESENT` and `FRAMEBUFFER_DUMP`
+/// - 0x10 number of presented frames
+///
+/// The palette follows at `FRAMEBUFFER_PALETTE` and the pixels at `FRAMEBUFFER_PIXELS`
+pub struct Framebuffer {
+    pub width: Word,
+    pub height: Word,
+    pub format: PixelFormat,
+    palette: Vec<Byte>,
+    pixels: Vec<Byte>,
+    frames: Word,
+    output: Option<(String, ImageFormat)>,
+    every: Word,
+}
+
+impl Framebuffer {
+    /// Black framebuffer with a grayscale palette, frames aren't written anywhere
+    pub fn new(width: Word, height: Word, format: PixelFormat) -> Self {
+        let palette = (0..=255).flat_map(|shade| [shade, shade, shade, 0xFF]).collect();
+        let size = width as usize * height as usize * format.bytes_per_pixel() as usize;
+        Framebuffer {
+            width,
+            height,
+            format,
+            palette,
+            pixels: vec![0; size],
+            frames: 0,
+            output: None,
+            every: 0,
+        }
+    }
+
+    /// Writes frames to the `.ppm` or `.png` file at path, `{frame}` in the path is replaced by the
+    /// number of presented frames. The last frame is written when the framebuffer is flushed
+    pub fn output(mut self, path: &str) -> io::Result<Self> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no image format for '{}', use .ppm or .png", path),
+            )
+        })?;
+        self.output = Some((path.to_string(), format));
+        Ok(self)
+    }
+
+    /// Writes every n-th presented frame, 0 only writes frames on demand and when flushed
+    pub fn every(mut self, frames: Word) -> Self {
+        self.every = frames;
+        self
+    }
+
+    /// Bytes the registers, the palette and the pixels take
+    pub fn size(&self) -> Word {
+        FRAMEBUFFER_PIXELS + self.pixels.len() as Word
+    }
+
+    /// Red, green, blue and alpha bytes of every pixel
+    pub fn rgba(&self) -> Vec<Byte> {
+        match self.format {
+            PixelFormat::Rgba => self.pixels.clone(),
+            PixelFormat::Indexed => self
+                .pixels
+                .iter()
+                .flat_map(|index| &self.palette[*index as usize * 4..*index as usize * 4 + 4])
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Writes the current frame to the file in the format named by its extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let format = ImageFormat::from_path(&path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no image format, use .ppm or .png"))?;
+        let mut file = BufWriter::new(File::create(path)?);
+        let rgba = self.rgba();
+
+        match format {
+            ImageFormat::Ppm => {
+                write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+                for pixel in rgba.chunks(4) {
+                    file.write_all(&pixel[..3])?;
+                }
+                file.flush()
+            }
+            ImageFormat::Png => {
+                let mut encoder = png::Encoder::new(file, self.width, self.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut writer = encoder.write_header().map_err(io::Error::other)?;
+                writer.write_image_data(&rgba).map_err(io::Error::other)
+            }
+        }
+    }
+
+    fn dump(&self) -> io::Result<()> {
+        match &self.output {
+            Some((path, _)) => self.save(path.replace("{frame}", &format!("{:06}", self.frames))),
+            None => Ok(()),
+        }
+    }
+
+    /// Palette or pixel bytes `addr..addr + size`, `None` for the registers or past the end
+    fn bytes(&self, addr: Word, size: Word) -> Option<&[Byte]> {
+        let (buffer, offset) = if addr >= FRAMEBUFFER_PIXELS {
+            (&self.pixels, addr - FRAMEBUFFER_PIXELS)
+        } else {
+            (&self.palette, addr.checked_sub(FRAMEBUFFER_PALETTE)?)
+        };
+        buffer.get(offset as usize..offset as usize + size as usize)
+    }
+
+    fn bytes_mut(&mut self, addr: Word, size: usize) -> Option<&mut [Byte]> {
+        let (buffer, offset) = if addr >= FRAMEBUFFER_PIXELS {
+            (&mut self.pixels, addr - FRAMEBUFFER_PIXELS)
+        } else {
+            (&mut self.palette, addr.checked_sub(FRAMEBUFFER_PALETTE)?)
+        };
+        buffer.get_mut(offset as usize..offset as usize + size)
+    }
+}
+
+impl Device for Framebuffer {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        match addr {
+            0x00 => Ok(self.width),
+            0x04 => Ok(self.height),
+            0x08 => Ok(self.format.bytes_per_pixel()),
+            0x10 => Ok(self.frames),
+            _ => self
+                .bytes(addr, 4)
+                .map(|bytes| Word::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        if addr != 0x0C {
+            let bytes = self.bytes_mut(addr, 4).ok_or(Trap::InvalidAddress(addr))?;
+            bytes.copy_from_slice(&value.to_le_bytes());
+            return Ok(());
+        }
+
+        let mut dump = value & FRAMEBUFFER_DUMP != 0;
+        if value & FRAMEBUFFER_PRESENT != 0 {
+            self.frames = self.frames.wrapping_add(1);
+            dump |= self.every != 0 && self.frames.is_multiple_of(self.every);
+        }
+        if dump {
+            self.dump()
+                .map_err(|err| Trap::DeviceFault(format!("[Framebuffer] Failed to write frame: {}", err)))?;
+        }
+        Ok(())
+    }
+
+    fn get_byte(&self, addr: Word) -> Result<Byte, Trap> {
+        self.bytes(addr, 1)
+            .map(|bytes| bytes[0])
+            .ok_or(Trap::InvalidAddress(addr))
+    }
+
+    fn set_byte(&mut self, addr: Word, value: Byte) -> Result<(), Trap> {
+        let bytes = self.bytes_mut(addr, 1).ok_or(Trap::InvalidAddress(addr))?;
+        bytes[0] = value;
+        Ok(())
+    }
+
+    fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+        self.bytes(addr, size)
+            .map(|bytes| bytes.to_vec())
+            .ok_or(Trap::InvalidAddress(addr))
+    }
+
+    fn set_range(&mut self, addr: Word, data: Vec<Byte>) -> Result<(), Trap> {
+        let bytes = self.bytes_mut(addr, data.len()).ok_or(Trap::InvalidAddress(addr))?;
+        bytes.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Trap> {
+        self.dump()
+            .map_err(|err| Trap::DeviceFault(format!("[Framebuffer] Failed to write frame: {}", err)))
+    }
+}
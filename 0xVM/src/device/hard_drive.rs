@@ -1,23 +1,112 @@
-use crate::memory::{Byte, Word};
+use std::{
+	cell::{Cell, RefCell},
+	collections::{BTreeSet, VecDeque},
+	fs::{File, OpenOptions},
+	io::{self, Read, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	memory::{Byte, Word},
+	trap::Trap,
+};
 
 use super::Device;
 
+/// Bytes the controller's register block takes
+pub const HARD_DRIVE_REGISTERS: Word = 0x20;
+
+/// Command reading `count` sectors from `sector` on, the data register returns their bytes
+pub const HARD_DRIVE_READ: Word = 1;
+/// Command writing `count` sectors from `sector` on, once all their bytes went into the data register
+pub const HARD_DRIVE_WRITE: Word = 2;
+/// Command writing changed sectors back into the disk image
+pub const HARD_DRIVE_FLUSH: Word = 3;
+/// Command returning the sector size, the number of sectors and the flags `HARD_DRIVE_IMAGE` and
+/// `HARD_DRIVE_READ_ONLY` as three words through the data register
+pub const HARD_DRIVE_IDENTIFY: Word = 4;
+
+/// Status register bit set while no transfer is in progress
+pub const HARD_DRIVE_READY: Word = 1;
+/// Status register bit set while the data register has bytes to read or waits for bytes to write
+pub const HARD_DRIVE_DATA: Word = 1 << 1;
+/// Status register bit set while the error register holds an error code
+pub const HARD_DRIVE_ERROR: Word = 1 << 2;
+
+/// Error code of an unknown command
+pub const HARD_DRIVE_ERR_COMMAND: Word = 1;
+/// Error code of a transfer of no sectors or past the last sector
+pub const HARD_DRIVE_ERR_SECTOR: Word = 2;
+/// Error code of a write to a read-only disk image
+pub const HARD_DRIVE_ERR_READ_ONLY: Word = 3;
+/// Error code of a data register access without a transfer or past its end
+pub const HARD_DRIVE_ERR_DATA: Word = 4;
+/// Error code of a flush the host failed
+pub const HARD_DRIVE_ERR_IO: Word = 5;
+
+/// Identify flag of a drive backed by a disk image
+pub const HARD_DRIVE_IMAGE: Word = 1;
+/// Identify flag of a drive that can't be written
+pub const HARD_DRIVE_READ_ONLY: Word = 1 << 1;
+
+/// How `HardDrive::open` uses the disk image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageMode {
+	/// Creates a zeroed image, an existing file is left alone and fails the open
+	Create,
+	/// Writes sectors back into the image on flush
+	ReadWrite,
+	/// Write commands fail with `HARD_DRIVE_ERR_READ_ONLY` in the error register
+	ReadOnly,
+	/// Leaves the image untouched and keeps written sectors in the overlay file, which is read
+	/// back the next time the image is opened with it
+	CopyOnWrite(PathBuf),
+}
+
+struct DiskImage {
+	file: Option<File>,
+	mode: ImageMode,
+	/// Sectors written since the last flush
+	dirty: BTreeSet<Word>,
+	/// Sectors kept in the copy-on-write overlay
+	overlay: BTreeSet<Word>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+	Idle,
+	/// The buffer holds the bytes left to read
+	Read,
+	/// The buffer collects the bytes of `count` sectors from `sector` on
+	Write { sector: Word, count: Word },
+}
+
+/// Hard drive controller, the registers are words:
+/// - 0x00 first sector of a transfer
+/// - 0x04 number of sectors to transfer
+/// - 0x08 command, writing `HARD_DRIVE_READ`, `HARD_DRIVE_WRITE`, `HARD_DRIVE_FLUSH` or
+///   `HARD_DRIVE_IDENTIFY` runs it and aborts a transfer in progress
+/// - 0x0C status, see `HARD_DRIVE_READY`, `HARD_DRIVE_DATA` and `HARD_DRIVE_ERROR`
+/// - 0x10 code of the first error since the last command, writing it clears the code
+/// - 0x14 sector size in bytes
+/// - 0x18 number of sectors on the drive
+/// - 0x1C data, a word or a range of bytes of the transfer at a time
 pub struct HardDrive {
 	pub sector_size: Word,
 	pub sector_count: Word,
 
-	addr: Word,
+	sector: Word,
+	count: Word,
+	transfer: Cell<Transfer>,
+	buffer: RefCell<VecDeque<Byte>>,
+	error: Cell<Word>,
 	data: Vec<Byte>,
+	image: Option<DiskImage>,
 }
 
 impl HardDrive {
 	pub fn new(sector_size: Word, sector_count: Word) -> Self {
-		Self {
-			sector_size,
-			sector_count,
-			addr: 0,
-			data: vec![0; sector_size as usize * sector_count as usize],
-		}
+		Self::from(Vec::new(), sector_size, sector_count)
 	}
 
 	pub fn from(mut data: Vec<Byte>, sector_size: Word, sector_count: Word) -> Self {
@@ -35,42 +124,295 @@ impl HardDrive {
         Self {
 			sector_size,
 			sector_count,
-			addr: 0,
+			sector: 0,
+			count: 0,
+			transfer: Cell::new(Transfer::Idle),
+			buffer: RefCell::new(VecDeque::new()),
+			error: Cell::new(0),
 			data,
+			image: None,
 		}
     }
+
+	/// Drive backed by the disk image at path, which must hold exactly `sector_size * sector_count`
+	/// bytes. A copy-on-write overlay holds records of a little endian sector number followed by
+	/// the sector's data
+	pub fn open<P: AsRef<Path>>(path: P, sector_size: Word, sector_count: Word, mode: ImageMode) -> io::Result<Self> {
+		let size = sector_size as u64 * sector_count as u64;
+		let mut file = match mode {
+			ImageMode::Create => {
+				let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+				file.set_len(size)?;
+				file
+			}
+			ImageMode::ReadWrite => OpenOptions::new().read(true).write(true).open(&path)?,
+			ImageMode::ReadOnly | ImageMode::CopyOnWrite(_) => File::open(&path)?,
+		};
+
+		let len = file.metadata()?.len();
+		if len != size {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"disk image is {} bytes but {} sectors of {} bytes take {}",
+					len, sector_count, sector_size, size
+				),
+			));
+		}
+		let mut data = Vec::with_capacity(size as usize);
+		file.read_to_end(&mut data)?;
+
+		let mut overlay = BTreeSet::new();
+		if let ImageMode::CopyOnWrite(overlay_path) = &mode {
+			match std::fs::read(overlay_path) {
+				Ok(records) => {
+					for record in records.chunks(4 + sector_size as usize) {
+						let sector = record
+							.get(..4)
+							.map(|bytes| Word::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+							.filter(|sector| *sector < sector_count && record.len() == 4 + sector_size as usize)
+							.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "corrupt copy-on-write overlay"))?;
+
+						let start = (sector * sector_size) as usize;
+						data[start..start + sector_size as usize].copy_from_slice(&record[4..]);
+						overlay.insert(sector);
+					}
+				}
+				Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+				Err(err) => return Err(err),
+			}
+		}
+
+		let file = match mode {
+			ImageMode::Create | ImageMode::ReadWrite => Some(file),
+			_ => None,
+		};
+		let mut drive = Self::from(data, sector_size, sector_count);
+		drive.image = Some(DiskImage {
+			file,
+			mode,
+			dirty: BTreeSet::new(),
+			overlay,
+		});
+		Ok(drive)
+	}
+
+	/// Writes the sectors written since the last flush back into the image or its overlay
+	pub fn flush(&mut self) -> io::Result<()> {
+		let image = match &mut self.image {
+			Some(image) if !image.dirty.is_empty() => image,
+			_ => return Ok(()),
+		};
+		let sector_size = self.sector_size as usize;
+		let data = &self.data;
+		let sector = |n: Word| &data[n as usize * sector_size..(n as usize + 1) * sector_size];
+
+		match &image.mode {
+			ImageMode::CopyOnWrite(overlay_path) => {
+				image.overlay.extend(image.dirty.iter());
+				let mut records = Vec::with_capacity(image.overlay.len() * (4 + sector_size));
+				for n in image.overlay.iter() {
+					records.extend_from_slice(&n.to_le_bytes());
+					records.extend_from_slice(sector(*n));
+				}
+				std::fs::write(overlay_path, records)?;
+			}
+			_ => {
+				if let Some(file) = &mut image.file {
+					for n in image.dirty.iter() {
+						file.seek(SeekFrom::Start(*n as u64 * sector_size as u64))?;
+						file.write_all(sector(*n))?;
+					}
+					file.sync_data()?;
+				}
+			}
+		}
+
+		image.dirty.clear();
+		Ok(())
+	}
 }
 
-impl Device for HardDrive {
-	fn set_word(&mut self, _: Word, val: Word) -> () {
-		self.addr = val;
+impl Drop for HardDrive {
+	fn drop(&mut self) {
+		let _ = HardDrive::flush(self);
+	}
+}
+
+impl HardDrive {
+	fn is_read_only(&self) -> bool {
+		matches!(&self.image, Some(DiskImage { mode: ImageMode::ReadOnly, .. }))
 	}
 
-	fn get_word(&self, _: Word) -> Word {
-		self.addr
+	/// Bytes of the sectors `sector..sector + count`, `None` if there are none or they run past the last sector
+	fn sectors(&self, sector: Word, count: Word) -> Option<std::ops::Range<usize>> {
+		let end = sector.checked_add(count).filter(|end| count > 0 && *end <= self.sector_count)?;
+		Some(sector as usize * self.sector_size as usize..end as usize * self.sector_size as usize)
 	}
 
-	fn get_range(&self, _: Word, _: Word) -> Vec<Byte> {
-		let addr = self.addr * self.sector_size;
-		self.data
-            .get(addr as usize..addr as usize + self.sector_size as usize)
-            .expect(format!("[HardDrive] get_range: No such sector '0x{:08X}-{:08X}'", addr, addr + self.sector_size).as_str())
-            .to_vec()
+	/// Aborts the transfer, the first error is kept until the next command or until it is cleared
+	fn fail(&self, error: Word) {
+		if self.error.get() == 0 {
+			self.error.set(error);
+		}
+		self.transfer.set(Transfer::Idle);
+		self.buffer.borrow_mut().clear();
 	}
 
-	fn set_range(&mut self, _: Word, data: Vec<Byte>) {
-		let addr = self.addr * self.sector_size;
+	fn command(&mut self, command: Word) {
+		self.error.set(0);
+		self.transfer.set(Transfer::Idle);
+		self.buffer.borrow_mut().clear();
+		match command {
+			HARD_DRIVE_READ => match self.sectors(self.sector, self.count) {
+				Some(range) => {
+					self.buffer.borrow_mut().extend(&self.data[range]);
+					self.transfer.set(Transfer::Read);
+				}
+				None => self.fail(HARD_DRIVE_ERR_SECTOR),
+			},
+			HARD_DRIVE_WRITE if self.sectors(self.sector, self.count).is_none() => self.fail(HARD_DRIVE_ERR_SECTOR),
+			HARD_DRIVE_WRITE if self.is_read_only() => self.fail(HARD_DRIVE_ERR_READ_ONLY),
+			HARD_DRIVE_WRITE => self.transfer.set(Transfer::Write {
+				sector: self.sector,
+				count: self.count,
+			}),
+			HARD_DRIVE_FLUSH => {
+				if self.flush().is_err() {
+					self.fail(HARD_DRIVE_ERR_IO);
+				}
+			}
+			HARD_DRIVE_IDENTIFY => {
+				let mut flags = 0;
+				if self.image.is_some() {
+					flags |= HARD_DRIVE_IMAGE;
+				}
+				if self.is_read_only() {
+					flags |= HARD_DRIVE_READ_ONLY;
+				}
+				let mut buffer = self.buffer.borrow_mut();
+				for word in [self.sector_size, self.sector_count, flags] {
+					buffer.extend(word.to_le_bytes());
+				}
+				self.transfer.set(Transfer::Read);
+			}
+			_ => self.fail(HARD_DRIVE_ERR_COMMAND),
+		}
+	}
 
-		if data.len() != self.sector_size as usize {
-			panic!("[HardDrive] set_range: Data size mismatch, expected '{}' but got '{}'", self.sector_size, data.len());
+	/// Takes up to `size` bytes of a read, asking for more bytes than the transfer has left fails it
+	/// and returns only the bytes left
+	fn read_data(&self, size: usize) -> Vec<Byte> {
+		if self.transfer.get() != Transfer::Read {
+			self.fail(HARD_DRIVE_ERR_DATA);
+			return Vec::new();
 		}
 
-        if addr + self.sector_size > self.data.len() as Word {
-            panic!("[HardDrive] set_range: No such sector '0x{:08X}-{:08X}'", addr, addr + data.len() as Word);
-        }
+		let mut buffer = self.buffer.borrow_mut();
+		let available = size.min(buffer.len());
+		let bytes: Vec<Byte> = buffer.drain(..available).collect();
+		let done = buffer.is_empty();
+		drop(buffer);
 
-        for (i, byte) in data.iter().enumerate() {
-            self.data[addr as usize + i] = *byte;
-        }
-    }
-}
\ No newline at end of file
+		if bytes.len() < size {
+			self.fail(HARD_DRIVE_ERR_DATA);
+		} else if done {
+			self.transfer.set(Transfer::Idle);
+		}
+		bytes
+	}
+
+	/// Adds bytes to a write, which writes its sectors once all their bytes arrived
+	fn write_data(&mut self, bytes: &[Byte]) {
+		let (sector, count) = match self.transfer.get() {
+			Transfer::Write { sector, count } => (sector, count),
+			_ => return self.fail(HARD_DRIVE_ERR_DATA),
+		};
+		let range = self.sectors(sector, count).expect("checked by the write command");
+
+		let mut buffer = self.buffer.borrow_mut();
+		if buffer.len() + bytes.len() > range.len() {
+			drop(buffer);
+			return self.fail(HARD_DRIVE_ERR_DATA);
+		}
+		buffer.extend(bytes);
+		if buffer.len() < range.len() {
+			return;
+		}
+
+		for (target, byte) in self.data[range].iter_mut().zip(buffer.drain(..)) {
+			*target = byte;
+		}
+		drop(buffer);
+		if let Some(image) = &mut self.image {
+			image.dirty.extend(sector..sector + count);
+		}
+		self.transfer.set(Transfer::Idle);
+	}
+}
+
+impl Device for HardDrive {
+	fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+		match addr {
+			0x00 => Ok(self.sector),
+			0x04 => Ok(self.count),
+			0x0C => {
+				let mut status = 0;
+				if self.transfer.get() == Transfer::Idle {
+					status |= HARD_DRIVE_READY;
+				} else {
+					status |= HARD_DRIVE_DATA;
+				}
+				if self.error.get() != 0 {
+					status |= HARD_DRIVE_ERROR;
+				}
+				Ok(status)
+			}
+			0x10 => Ok(self.error.get()),
+			0x14 => Ok(self.sector_size),
+			0x18 => Ok(self.sector_count),
+			0x1C => {
+				// missing bytes read as 0
+				let mut bytes = [0; 4];
+				for (byte, data) in bytes.iter_mut().zip(self.read_data(4)) {
+					*byte = data;
+				}
+				Ok(Word::from_le_bytes(bytes))
+			}
+			_ => Err(Trap::InvalidAddress(addr)),
+		}
+	}
+
+	fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+		match addr {
+			0x00 => self.sector = value,
+			0x04 => self.count = value,
+			0x08 => self.command(value),
+			0x10 => self.error.set(0),
+			0x1C => self.write_data(&value.to_le_bytes()),
+			_ => return Err(Trap::InvalidAddress(addr)),
+		}
+		Ok(())
+	}
+
+	fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+		match addr {
+			0x1C => Ok(self.read_data(size as usize)),
+			_ => Err(Trap::InvalidAddress(addr)),
+		}
+	}
+
+	fn set_range(&mut self, addr: Word, data: Vec<Byte>) -> Result<(), Trap> {
+		match addr {
+			0x1C => {
+				self.write_data(&data);
+				Ok(())
+			}
+			_ => Err(Trap::InvalidAddress(addr)),
+		}
+	}
+
+	fn flush(&mut self) -> Result<(), Trap> {
+		HardDrive::flush(self).map_err(|err| Trap::DeviceFault(format!("[HardDrive] flush: {}", err)))
+	}
+}
@@ -0,0 +1,104 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::Read,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::memory::Byte;
+
+enum Source {
+    Script(VecDeque<Byte>),
+    Reader(Receiver<Byte>),
+    Closed,
+}
+
+/// Bytes received by an input device: a FIFO of `size` bytes filled from a script or a reader,
+/// further input waits in the source until the program reads
+pub(crate) struct InputFifo {
+    source: Source,
+    fifo: RefCell<VecDeque<Byte>>,
+    size: usize,
+}
+
+impl InputFifo {
+    pub(crate) fn closed(size: usize) -> Self {
+        InputFifo {
+            source: Source::Closed,
+            fifo: RefCell::new(VecDeque::new()),
+            size,
+        }
+    }
+
+    pub(crate) fn scripted(bytes: &[Byte], size: usize) -> Self {
+        InputFifo {
+            source: Source::Script(bytes.iter().copied().collect()),
+            ..Self::closed(size)
+        }
+    }
+
+    /// Reads on a thread of its own, so the program keeps running while no input is available
+    pub(crate) fn reader<R: Read + Send + 'static>(mut reader: R, size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            // a terminal returns every key as soon as it is pressed
+            while let Ok(n @ 1..) = reader.read(&mut buffer) {
+                if buffer[..n].iter().any(|byte| sender.send(*byte).is_err()) {
+                    break;
+                }
+            }
+        });
+
+        InputFifo {
+            source: Source::Reader(receiver),
+            ..Self::closed(size)
+        }
+    }
+
+    fn next_input(&mut self) -> Option<Byte> {
+        let next = match &mut self.source {
+            Source::Script(bytes) => bytes.pop_front().ok_or(true),
+            Source::Reader(receiver) => receiver.try_recv().map_err(|err| err == TryRecvError::Disconnected),
+            Source::Closed => return None,
+        };
+
+        match next {
+            Ok(byte) => Some(byte),
+            Err(closed) => {
+                if closed {
+                    self.source = Source::Closed;
+                }
+                None
+            }
+        }
+    }
+
+    /// Moves available input into the FIFO, returns whether any arrived
+    pub(crate) fn fill(&mut self) -> bool {
+        let mut arrived = false;
+        while self.fifo.borrow().len() < self.size {
+            match self.next_input() {
+                Some(byte) => self.fifo.borrow_mut().push_back(byte),
+                None => break,
+            }
+            arrived = true;
+        }
+        arrived
+    }
+
+    /// Oldest byte in the FIFO, 0 if it is empty
+    pub(crate) fn pop(&self) -> Byte {
+        self.fifo.borrow_mut().pop_front().unwrap_or(0)
+    }
+
+    pub(crate) fn is_ready(&self) -> bool {
+        !self.fifo.borrow().is_empty()
+    }
+
+    /// Whether the input ended and the FIFO is empty
+    pub(crate) fn is_closed(&self) -> bool {
+        !self.is_ready() && matches!(self.source, Source::Closed)
+    }
+}
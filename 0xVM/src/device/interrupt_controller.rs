@@ -0,0 +1,106 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{memory::Word, trap::Trap};
+
+use super::Device;
+
+/// Number of IRQ lines, one bit each in the pending and mask registers
+pub const IRQ_LINES: Word = 32;
+/// Bytes the interrupt controller's register block takes
+pub const INTERRUPT_CONTROLLER_REGISTERS: Word = 0x8;
+
+#[derive(Default)]
+struct InterruptState {
+    pending: Word,
+    mask: Word,
+}
+
+/// IRQ state shared by the CPU, the interrupt controller and the devices raising interrupts
+#[derive(Clone, Default)]
+pub struct Interrupts {
+    state: Rc<RefCell<InterruptState>>,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a device raises the IRQ line with
+    pub fn line(&self, line: Word) -> IrqLine {
+        assert!(line < IRQ_LINES, "[INTERRUPTS] No such IRQ line: '{}'", line);
+
+        IrqLine {
+            interrupts: self.clone(),
+            line,
+        }
+    }
+
+    pub fn raise(&self, line: Word) {
+        self.state.borrow_mut().pending |= 1 << line;
+    }
+
+    pub fn pending(&self) -> Word {
+        self.state.borrow().pending
+    }
+
+    /// Lowest pending line that isn't masked out, its pending bit is cleared as the CPU takes it
+    pub fn take(&self) -> Option<Word> {
+        let mut state = self.state.borrow_mut();
+        let active = state.pending & state.mask;
+        if active == 0 {
+            return None;
+        }
+
+        let line = active.trailing_zeros();
+        state.pending &= !(1 << line);
+        Some(line)
+    }
+}
+
+/// A single IRQ line of the interrupt controller
+#[derive(Clone)]
+pub struct IrqLine {
+    interrupts: Interrupts,
+    pub line: Word,
+}
+
+impl IrqLine {
+    pub fn raise(&self) {
+        self.interrupts.raise(self.line);
+    }
+}
+
+/// Programmable interrupt controller, the registers are words:
+/// - 0x0 pending lines, writing a word clears the lines set in it
+/// - 0x4 mask of the lines that interrupt the CPU, all lines are masked out at start
+pub struct InterruptController {
+    interrupts: Interrupts,
+}
+
+impl InterruptController {
+    pub fn new(interrupts: Interrupts) -> Self {
+        InterruptController { interrupts }
+    }
+}
+
+impl Device for InterruptController {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        let state = self.interrupts.state.borrow();
+        match addr {
+            0x0 => Ok(state.pending),
+            0x4 => Ok(state.mask),
+            _ => Err(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        let mut state = self.interrupts.state.borrow_mut();
+        match addr {
+            0x0 => state.pending &= !value,
+            0x4 => state.mask = value,
+            _ => return Err(Trap::InvalidAddress(addr)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+};
+
+use crate::{
+    memory::{Byte, Word},
+    trap::Trap,
+};
+
+use super::{input::InputFifo, Device, IrqLine};
+
+/// Bytes the keyboard's register block takes
+pub const KEYBOARD_REGISTERS: Word = 0x8;
+/// Bytes the FIFO holds, further input waits in the source until the program reads
+pub const KEYBOARD_FIFO_SIZE: usize = 64;
+/// Status register bit set while the FIFO holds bytes
+pub const KEYBOARD_READY: Word = 1;
+/// Status register bit set once the input ended and the FIFO is empty
+pub const KEYBOARD_CLOSED: Word = 1 << 1;
+
+/// Input device buffering bytes from a terminal, a pipe, a file or a script, the registers are words:
+/// - 0x0 status, see `KEYBOARD_READY` and `KEYBOARD_CLOSED`
+/// - 0x4 data, reading it takes the oldest byte out of the FIFO, 0 if it is empty
+pub struct Keyboard {
+    input: InputFifo,
+    irq: Option<IrqLine>,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keyboard {
+    /// Keyboard without any input
+    pub fn new() -> Self {
+        Keyboard {
+            input: InputFifo::closed(KEYBOARD_FIFO_SIZE),
+            irq: None,
+        }
+    }
+
+    /// Keyboard typing the bytes, e.g. to drive a program from a test
+    pub fn scripted(bytes: &[Byte]) -> Self {
+        Keyboard {
+            input: InputFifo::scripted(bytes, KEYBOARD_FIFO_SIZE),
+            irq: None,
+        }
+    }
+
+    /// Keyboard reading from stdin, a file or any other reader on a thread of its own, so the
+    /// program keeps running while no input is available
+    pub fn reader<R: Read + Send + 'static>(reader: R) -> Self {
+        Keyboard {
+            input: InputFifo::reader(reader, KEYBOARD_FIFO_SIZE),
+            irq: None,
+        }
+    }
+
+    /// Reads the input named by the binding: `none`, `stdin` or `file:<path>`
+    pub fn connect(binding: &str) -> io::Result<Self> {
+        match binding.split_once(':') {
+            None if binding == "none" => Ok(Self::new()),
+            None if binding == "stdin" => Ok(Self::reader(io::stdin())),
+            Some(("file", path)) => Ok(Self::reader(File::open(path)?)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no such keyboard input: '{}'", binding),
+            )),
+        }
+    }
+
+    /// Raises the IRQ line whenever bytes arrive in the FIFO
+    pub fn with_irq(mut self, irq: IrqLine) -> Self {
+        self.irq = Some(irq);
+        self
+    }
+
+    fn status(&self) -> Word {
+        if self.input.is_ready() {
+            KEYBOARD_READY
+        } else if self.input.is_closed() {
+            KEYBOARD_CLOSED
+        } else {
+            0
+        }
+    }
+}
+
+impl Device for Keyboard {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        match addr {
+            0x0 => Ok(self.status()),
+            0x4 => Ok(self.input.pop() as Word),
+            _ => Err(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.input.fill() {
+            if let Some(irq) = &self.irq {
+                irq.raise();
+            }
+        }
+    }
+}
+
+/// Terminal settings of stdin from before `RawTerminal::enable`, kept where the panic hook and the
+/// SIGINT handler can restore them
+#[cfg(unix)]
+static SAVED_TERMIOS: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+
+/// Puts the terminal on stdin into raw mode until dropped: keys are passed on as they are pressed
+/// without being echoed, Ctrl-C still interrupts the VM. The terminal is also restored when the VM
+/// panics or is interrupted by Ctrl-C
+#[cfg(unix)]
+pub struct RawTerminal {
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    pub fn enable() -> io::Result<Self> {
+        use std::mem::MaybeUninit;
+
+        // SAFETY: the termios struct outlives the calls writing it and is only read once tcgetattr
+        // filled it in, the SIGINT handler only calls async-signal-safe functions
+        unsafe {
+            let mut termios = MaybeUninit::<libc::termios>::uninit();
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let saved = termios.assume_init();
+
+            let mut raw = saved;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+
+            if SAVED_TERMIOS.set(saved).is_ok() {
+                let hook = std::panic::take_hook();
+                std::panic::set_hook(Box::new(move |info| {
+                    restore_terminal();
+                    hook(info)
+                }));
+                libc::signal(libc::SIGINT, interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t);
+            }
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(RawTerminal { saved })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // SAFETY: restores settings tcgetattr returned for the same fd
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn restore_terminal() {
+    if let Some(saved) = SAVED_TERMIOS.get() {
+        // SAFETY: restores settings tcgetattr returned for the same fd
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+        }
+    }
+}
+
+/// Restores the terminal and dies of SIGINT the way the VM would have without the handler
+#[cfg(unix)]
+extern "C" fn interrupted(signal: libc::c_int) {
+    restore_terminal();
+    // SAFETY: signal and raise are async-signal-safe
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
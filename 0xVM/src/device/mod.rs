@@ -1,3 +1,13 @@
 mod device; pub use device::*;
 mod screen; pub use screen::*;
-mod hard_drive; pub use hard_drive::*;
\ No newline at end of file
+mod screen_buffer; pub use screen_buffer::*;
+mod virtual_terminal; pub use virtual_terminal::*;
+mod cast; pub use cast::*;
+mod hard_drive; pub use hard_drive::*;
+mod interrupt_controller; pub use interrupt_controller::*;
+mod timer; pub use timer::*;
+mod input;
+mod keyboard; pub use keyboard::*;
+mod uart; pub use uart::*;
+mod dma; pub use dma::*;
+mod framebuffer; pub use framebuffer::*;
\ No newline at end of file
@@ -1,141 +1,382 @@
-use std::io::{self, Write};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
 
-use crate::memory::{Byte, HalfWord, Word};
+use crate::{
+    memory::{Byte, HalfWord, Word},
+    trap::Trap,
+};
 
-use super::Device;
+use super::{CastClock, CastRecorder, Cell, Device, ScreenBuffer, VirtualTerminal};
 
+/// Clears the whole screen
+pub const SCREEN_CLEAR: Word = 0xF4_80_80_80;
+/// Resets all text attributes and colours
+pub const SCREEN_RESET: Word = 0xF4_80_80_81;
+pub const SCREEN_BOLD: Word = 0xF4_80_80_82;
+pub const SCREEN_NO_BOLD: Word = 0xF4_80_80_83;
+pub const SCREEN_UNDERLINE: Word = 0xF4_80_80_84;
+pub const SCREEN_NO_UNDERLINE: Word = 0xF4_80_80_85;
+pub const SCREEN_BLINK: Word = 0xF4_80_80_86;
+pub const SCREEN_NO_BLINK: Word = 0xF4_80_80_87;
+pub const SCREEN_ITALICS: Word = 0xF4_80_80_88;
+pub const SCREEN_NO_ITALICS: Word = 0xF4_80_80_89;
+pub const SCREEN_STRIKETHROUGH: Word = 0xF4_80_80_8A;
+pub const SCREEN_NO_STRIKETHROUGH: Word = 0xF4_80_80_8B;
+pub const SCREEN_SHOW_CURSOR: Word = 0xF4_80_80_8C;
+pub const SCREEN_HIDE_CURSOR: Word = 0xF4_80_80_8D;
+/// Clears the row of the last drawn cell
+pub const SCREEN_CLEAR_LINE: Word = 0xF4_80_80_8E;
+/// Clears the row of the last drawn cell from the cursor to its end
+pub const SCREEN_CLEAR_LINE_END: Word = 0xF4_80_80_8F;
+/// Lets the whole screen scroll again after `SCREEN_SCROLL_REGION`
+pub const SCREEN_NO_SCROLL_REGION: Word = 0xF4_80_80_90;
+/// Scrolls the scroll region up a row
+pub const SCREEN_SCROLL_UP: Word = 0xF4_80_80_91;
+/// Scrolls the scroll region down a row
+pub const SCREEN_SCROLL_DOWN: Word = 0xF4_80_80_92;
+pub const SCREEN_DEFAULT_FOREGROUND: Word = 0xF4_80_80_93;
+pub const SCREEN_DEFAULT_BACKGROUND: Word = 0xF4_80_80_94;
+
+/// Foreground from the 16 colour palette, the colour 0-15 goes in the low byte
+pub const SCREEN_FOREGROUND: Word = 0xF4_81_00_00;
+/// Foreground from the 256 colour palette, the colour goes in the low byte
+pub const SCREEN_FOREGROUND_256: Word = 0xF4_82_00_00;
+/// Background from the 16 colour palette, the colour 0-15 goes in the low byte
+pub const SCREEN_BACKGROUND: Word = 0xF4_83_00_00;
+/// Background from the 256 colour palette, the colour goes in the low byte
+pub const SCREEN_BACKGROUND_256: Word = 0xF4_84_00_00;
+/// Moves the cursor to a cell, the column goes in the second byte and the row in the low byte
+pub const SCREEN_MOVE_CURSOR: Word = 0xF4_85_00_00;
+/// Limits scrolling to a band of rows, the top row goes in the second byte and the bottom row
+/// in the low byte
+pub const SCREEN_SCROLL_REGION: Word = 0xF4_86_00_00;
+
+/// Every code by name, the assembler knows them as constants
+pub const SCREEN_CODES: &[(&str, Word)] = &[
+    ("SCREEN_CLEAR", SCREEN_CLEAR),
+    ("SCREEN_RESET", SCREEN_RESET),
+    ("SCREEN_BOLD", SCREEN_BOLD),
+    ("SCREEN_NO_BOLD", SCREEN_NO_BOLD),
+    ("SCREEN_UNDERLINE", SCREEN_UNDERLINE),
+    ("SCREEN_NO_UNDERLINE", SCREEN_NO_UNDERLINE),
+    ("SCREEN_BLINK", SCREEN_BLINK),
+    ("SCREEN_NO_BLINK", SCREEN_NO_BLINK),
+    ("SCREEN_ITALICS", SCREEN_ITALICS),
+    ("SCREEN_NO_ITALICS", SCREEN_NO_ITALICS),
+    ("SCREEN_STRIKETHROUGH", SCREEN_STRIKETHROUGH),
+    ("SCREEN_NO_STRIKETHROUGH", SCREEN_NO_STRIKETHROUGH),
+    ("SCREEN_SHOW_CURSOR", SCREEN_SHOW_CURSOR),
+    ("SCREEN_HIDE_CURSOR", SCREEN_HIDE_CURSOR),
+    ("SCREEN_CLEAR_LINE", SCREEN_CLEAR_LINE),
+    ("SCREEN_CLEAR_LINE_END", SCREEN_CLEAR_LINE_END),
+    ("SCREEN_NO_SCROLL_REGION", SCREEN_NO_SCROLL_REGION),
+    ("SCREEN_SCROLL_UP", SCREEN_SCROLL_UP),
+    ("SCREEN_SCROLL_DOWN", SCREEN_SCROLL_DOWN),
+    ("SCREEN_DEFAULT_FOREGROUND", SCREEN_DEFAULT_FOREGROUND),
+    ("SCREEN_DEFAULT_BACKGROUND", SCREEN_DEFAULT_BACKGROUND),
+    ("SCREEN_FOREGROUND", SCREEN_FOREGROUND),
+    ("SCREEN_FOREGROUND_256", SCREEN_FOREGROUND_256),
+    ("SCREEN_BACKGROUND", SCREEN_BACKGROUND),
+    ("SCREEN_BACKGROUND_256", SCREEN_BACKGROUND_256),
+    ("SCREEN_MOVE_CURSOR", SCREEN_MOVE_CURSOR),
+    ("SCREEN_SCROLL_REGION", SCREEN_SCROLL_REGION),
+];
+
+/// Escape sequence the code stands for, `None` for words drawn at a cell
+pub fn screen_escape(code: Word) -> Option<String> {
+    let parameter = code & 0xFF;
+    let second = (code >> 8) & 0xFF;
+    let escape = match code {
+        SCREEN_CLEAR => "\x1b[2J".to_string(),
+        SCREEN_RESET => "\x1b[0m".to_string(),
+        SCREEN_BOLD => "\x1b[1m".to_string(),
+        SCREEN_NO_BOLD => "\x1b[22m".to_string(),
+        SCREEN_UNDERLINE => "\x1b[4m".to_string(),
+        SCREEN_NO_UNDERLINE => "\x1b[24m".to_string(),
+        SCREEN_BLINK => "\x1b[5m".to_string(),
+        SCREEN_NO_BLINK => "\x1b[25m".to_string(),
+        SCREEN_ITALICS => "\x1b[3m".to_string(),
+        SCREEN_NO_ITALICS => "\x1b[23m".to_string(),
+        SCREEN_STRIKETHROUGH => "\x1b[9m".to_string(),
+        SCREEN_NO_STRIKETHROUGH => "\x1b[29m".to_string(),
+        SCREEN_SHOW_CURSOR => "\x1b[?25h".to_string(),
+        SCREEN_HIDE_CURSOR => "\x1b[?25l".to_string(),
+        SCREEN_CLEAR_LINE => "\x1b[2K".to_string(),
+        SCREEN_CLEAR_LINE_END => "\x1b[K".to_string(),
+        SCREEN_NO_SCROLL_REGION => "\x1b[r".to_string(),
+        SCREEN_SCROLL_UP => "\x1b[S".to_string(),
+        SCREEN_SCROLL_DOWN => "\x1b[T".to_string(),
+        SCREEN_DEFAULT_FOREGROUND => "\x1b[39m".to_string(),
+        SCREEN_DEFAULT_BACKGROUND => "\x1b[49m".to_string(),
+        _ => match code & 0xFFFF_0000 {
+            SCREEN_FOREGROUND => format!("\x1b[{}m", colour(30, parameter)),
+            SCREEN_FOREGROUND_256 => format!("\x1b[38;5;{}m", parameter),
+            SCREEN_BACKGROUND => format!("\x1b[{}m", colour(40, parameter)),
+            SCREEN_BACKGROUND_256 => format!("\x1b[48;5;{}m", parameter),
+            SCREEN_MOVE_CURSOR => format!("\x1b[{};{}H", parameter + 1, (second + 1) * 2),
+            SCREEN_SCROLL_REGION => format!("\x1b[{};{}r", second + 1, parameter + 1),
+            _ => return None,
+        },
+    };
+    Some(escape)
+}
+
+/// SGR parameter of a 16 colour palette entry, the bright colours 8-15 start 60 above the base
+fn colour(base: Word, colour: Word) -> Word {
+    match colour & 0xF {
+        colour @ 0..=7 => base + colour,
+        colour => base + 60 + colour - 8,
+    }
+}
+
+/// Text screen drawing a UTF-8 character, given as a little endian word, at the cell a word is
+/// written to. Words that are one of the `SCREEN_CODES` are turned into escape sequences instead.
+///
+/// The cells are kept in a `ScreenBuffer`: reading address n gives the character of cell n, the
+/// addresses behind the cells give their attributes, see `SCREEN_ATTRIBUTE_*`
+///
+/// In TTY mode characters are written to the register at `Screen::tty_register`, which draws them
+/// at the cursor and moves it on. `\n`, `\r`, `\x08` and `\t` move the cursor and the scroll
+/// region scrolls up once a line is done at its bottom. Reading the register gives the cursor as
+/// `column << 8 | row`, like the parameter of `SCREEN_MOVE_CURSOR`, so the cursor can only
+/// address Screens of up to 255 columns and rows
+///
+/// The escape sequences go to the terminal unless another output is set, see `Screen::connect`,
+/// and can be recorded to a cast file, see `Screen::record`
 pub struct Screen {
     pub width: HalfWord,
     pub height: HalfWord,
+    buffer: ScreenBuffer,
+    output: Box<dyn Write>,
+    /// Virtual terminal whose text is printed when the Screen is flushed
+    report: Option<VirtualTerminal>,
+    recorder: Option<CastRecorder>,
+    /// Instructions executed since the Screen was mapped
+    instructions: u64,
 }
 
-#[allow(dead_code)]
 impl Screen {
+    /// Screen drawing on the terminal through stdout, panics if it has no cells
     pub fn new(width: HalfWord, height: HalfWord) -> Self {
-        Screen { width, height }
-    }
-
-    fn write(&self, data: &[u8]) {
-        match io::stdout().write(data) {
-            Ok(_) => {}
-            Err(_) => panic!("[VM] Failed to write to stdout"),
+        assert!(width > 0 && height > 0, "[Screen] A Screen needs at least one cell");
+        Screen {
+            width,
+            height,
+            buffer: ScreenBuffer::new(width, height),
+            output: Box::new(io::stdout()),
+            report: None,
+            recorder: None,
+            instructions: 0,
         }
     }
 
-    #[inline]
-    fn move_to(&self, x: Word, y: Word) {
-        self.write(format!("\x1b[{};{}H", y, x).as_bytes());
+    /// Writes the escape sequences to the sink instead of the terminal
+    pub fn output<W: Write + 'static>(mut self, writer: W) -> Self {
+        self.output = Box::new(writer);
+        self
     }
 
-    /// Code: 0xF4 80 80 80
-    #[inline]
-    fn clear_screen(&self) {
-        self.write(format!("\x1b[2J").as_bytes());
+    /// Drops the escape sequences, the cells can still be read through `Screen::buffer`
+    pub fn headless(self) -> Self {
+        self.output(io::sink())
     }
 
-    /// Code: 0xF4 80 80 81
-    #[inline]
-    fn reset(&self) {
-        self.write(format!("\x1b[0m").as_bytes());
+    /// Draws on a virtual terminal of the Screen's size, the handle reads what it shows
+    pub fn virtual_terminal(self) -> (Self, VirtualTerminal) {
+        let terminal = VirtualTerminal::new(self.width as usize * 2, self.height as usize);
+        (self.output(terminal.clone()), terminal)
     }
 
-    /// Code: 0xF4 80 80 82
-    #[inline]
-    fn set_bold(&self) {
-        self.write(format!("\x1b[1m").as_bytes());
+    /// Draws on the host output named by the binding: `terminal`, `none`, `stderr`,
+    /// `file:<path>` (the escape sequences are written into the file) or `virtual` (a virtual
+    /// terminal whose text is printed to stdout when the Screen is flushed)
+    pub fn connect(self, binding: &str) -> io::Result<Self> {
+        match binding.split_once(':') {
+            None if binding == "terminal" => Ok(self.output(io::stdout())),
+            None if binding == "none" => Ok(self.headless()),
+            None if binding == "stderr" => Ok(self.output(io::stderr())),
+            Some(("file", path)) => Ok(self.output(File::create(path)?)),
+            None if binding == "virtual" => {
+                let (mut screen, terminal) = self.virtual_terminal();
+                screen.report = Some(terminal);
+                Ok(screen)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no such Screen output: '{}'", binding),
+            )),
+        }
     }
 
-    /// Code: 0xF4 80 80 83
-    #[inline]
-    fn unset_bold(&self) {
-        self.write(format!("\x1b[22m").as_bytes());
+    /// Records the escape sequences to an asciinema cast file as well, timed by the clock
+    pub fn record<P: AsRef<Path>>(mut self, path: P, clock: CastClock) -> io::Result<Self> {
+        let recorder = CastRecorder::create(path, self.width as usize * 2, self.height as usize, clock)?;
+        self.recorder = Some(recorder);
+        Ok(self)
     }
 
-    /// Code: 0xF4 80 80 84
-    #[inline]
-    fn set_underline(&self) {
-        self.write(format!("\x1b[4m").as_bytes());
+    /// Address of the TTY register behind the characters and attributes of the cells, the Screen
+    /// is mapped to at least `tty_register() + 4` addresses
+    pub fn tty_register(&self) -> Word {
+        self.buffer.len() * 2
     }
 
-    /// Code: 0xF4 80 80 85
-    #[inline]
-    fn unset_underline(&self) {
-        self.write(format!("\x1b[24m").as_bytes());
+    /// Handle on the cells that stays valid once the Screen is mapped
+    pub fn buffer(&self) -> ScreenBuffer {
+        self.buffer.clone()
     }
 
-    /// Code: 0xF4 80 80 86
-    #[inline]
-    fn set_blink(&self) {
-        self.write(format!("\x1b[5m").as_bytes());
+    fn write(&mut self, data: &[u8]) -> Result<(), Trap> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .output(self.instructions, data)
+                .map_err(|err| Trap::DeviceFault(format!("[Screen] Failed to record: {}", err)))?;
+        }
+        self.output
+            .write_all(data)
+            .map_err(|err| Trap::DeviceFault(format!("[Screen] Failed to write: {}", err)))
     }
 
-    /// Code: 0xF4 80 80 87
     #[inline]
-    fn unset_blink(&self) {
-        self.write(format!("\x1b[25m").as_bytes());
+    fn move_to(&mut self, x: Word, y: Word) -> Result<(), Trap> {
+        self.write(format!("\x1b[{};{}H", y, x).as_bytes())
     }
 
-    /// Code: 0xF4 80 80 88
-    #[inline]
-    fn set_italics(&self) {
-        self.write(format!("\x1b[3m").as_bytes());
+    fn cell(&self, addr: Word) -> Result<Cell, Trap> {
+        self.buffer.get(addr).ok_or(Trap::InvalidAddress(addr))
     }
 
-    /// Code: 0xF4 80 80 89
-    #[inline]
-    fn unset_italics(&self) {
-        self.write(format!("\x1b[23m").as_bytes());
+    fn draw(&mut self, addr: Word, word: Word) -> Result<(), Trap> {
+        self.buffer.draw(addr, word);
+
+        let x = (addr % self.width as Word) + 1;
+        let y = (addr / self.width as Word) + 1;
+        self.move_to(x * 2, y)?;
+
+        self.write(&word.to_le_bytes())
     }
 
-    /// Code: 0xF4 80 80 8A
-    #[inline]
-    fn set_strikethrough(&self) {
-        self.write(format!("\x1b[9m").as_bytes());
+    /// Draws the character at the cursor or moves the cursor for `\n`, `\r`, `\x08` and `\t`
+    fn tty(&mut self, word: Word) -> Result<(), Trap> {
+        let width = self.width as Word;
+        let (x, y) = self.buffer.cursor();
+        match word {
+            _ if self.buffer.is_empty() => {}
+            0 => {}
+            0x0A => self.new_line()?,
+            0x0D => self.buffer.move_cursor(0, y),
+            0x08 => self.buffer.move_cursor(x.saturating_sub(1).min(width - 1), y),
+            0x09 if (x / 8 + 1) * 8 >= width => self.new_line()?,
+            0x09 => self.buffer.move_cursor((x / 8 + 1) * 8, y),
+            _ if x >= width => {
+                self.new_line()?;
+                self.tty(word)?;
+            }
+            _ => {
+                if let Some(addr) = y.checked_mul(width).map(|row| row + x) {
+                    if addr < self.buffer.len() {
+                        self.draw(addr, word)?;
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
-    /// Code: 0xF4 80 80 8B
-    #[inline]
-    fn unset_strikethrough(&self) {
-        self.write(format!("\x1b[29m").as_bytes());
+    /// Moves the cursor to the start of the next row, scrolls up at the bottom of the scroll region
+    fn new_line(&mut self) -> Result<(), Trap> {
+        let (_, y) = self.buffer.cursor();
+        let (top, bottom) = self.buffer.region();
+        if y == bottom {
+            // the terminal scrolls the same rows as the buffer, even if no region was set
+            self.write(format!("\x1b[{};{}r\x1b[S", top + 1, bottom + 1).as_bytes())?;
+            self.buffer.apply(SCREEN_SCROLL_UP);
+            self.buffer.move_cursor(0, y);
+        } else {
+            self.buffer
+                .move_cursor(0, (y + 1).min((self.height as Word).saturating_sub(1)));
+        }
+        Ok(())
     }
 }
 
-#[allow(dead_code)]
 impl Device for Screen {
     #[inline]
-    fn get_byte(&self, _: Word) -> Byte {
-        0
+    fn get_byte(&self, addr: Word) -> Result<Byte, Trap> {
+        self.get_word(addr).map(|word| word as Byte)
     }
 
-    #[inline]
-    fn get_word(&self, _: Word) -> Word {
-        0
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        if addr == self.tty_register() {
+            let (x, y) = self.buffer.cursor();
+            return Ok(x << 8 | y);
+        }
+        match addr.checked_sub(self.buffer.len()) {
+            Some(attribute) => self.cell(attribute).map(|cell| cell.attributes),
+            None => self.cell(addr).map(|cell| cell.character),
+        }
     }
 
-    #[inline]
-    fn set_byte(&mut self, _: Word, _: Byte) {}
+    /// Bytes are only written to the TTY register, elsewhere they are ignored
+    fn set_byte(&mut self, addr: Word, value: Byte) -> Result<(), Trap> {
+        match addr == self.tty_register() {
+            true => self.tty(value as Word),
+            false => Ok(()),
+        }
+    }
 
-    fn set_word(&mut self, addr: Word, word: Word) {
-        match word {
-            0xF4_80_80_80 => self.clear_screen(),
-            0xF4_80_80_81 => self.reset(),
-            0xF4_80_80_82 => self.set_bold(),
-            0xF4_80_80_83 => self.unset_bold(),
-            0xF4_80_80_84 => self.set_underline(),
-            0xF4_80_80_85 => self.unset_underline(),
-            0xF4_80_80_86 => self.set_blink(),
-            0xF4_80_80_87 => self.unset_blink(),
-            0xF4_80_80_88 => self.set_italics(),
-            0xF4_80_80_89 => self.unset_italics(),
-            0xF4_80_80_8A => self.set_strikethrough(),
-            0xF4_80_80_8B => self.unset_strikethrough(),
-            _ => {
-                let x = ((addr & 0x0000FFFF) % self.width as Word) + 1;
-                let y = ((addr & 0x0000FFFF) / self.height as Word) + 1;
-                self.move_to(x * 2, y);
+    /// Writes the UTF-8 text to the TTY register
+    fn set_range(&mut self, addr: Word, data: Vec<Byte>) -> Result<(), Trap> {
+        if addr != self.tty_register() {
+            return Err(Trap::InvalidAddress(addr));
+        }
+        for char in String::from_utf8_lossy(&data).chars() {
+            let mut bytes = [0; 4];
+            char.encode_utf8(&mut bytes);
+            self.tty(Word::from_le_bytes(bytes))?;
+        }
+        Ok(())
+    }
 
-                self.write(&word.to_le_bytes());
+    /// Low bytes of the words at `addr..addr + size`, the text of a run of ASCII cells
+    fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+        (addr..addr + size).map(|addr| self.get_byte(addr)).collect()
+    }
+
+    fn set_word(&mut self, addr: Word, word: Word) -> Result<(), Trap> {
+        match screen_escape(word) {
+            Some(escape) => {
+                self.buffer.apply(word);
+                self.write(escape.as_bytes())?;
+            }
+            None if addr == self.tty_register() => self.tty(word)?,
+            None => {
+                self.cell(addr)?;
+                self.draw(addr, word)?;
             }
         }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        self.instructions += 1;
+    }
+
+    fn flush(&mut self) -> Result<(), Trap> {
+        self.output
+            .flush()
+            .map_err(|err| Trap::DeviceFault(format!("[Screen] Failed to write: {}", err)))?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .flush()
+                .map_err(|err| Trap::DeviceFault(format!("[Screen] Failed to record: {}", err)))?;
+        }
+        if let Some(terminal) = &self.report {
+            println!("{}", terminal.text());
+        }
+        Ok(())
     }
 }
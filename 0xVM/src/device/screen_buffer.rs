@@ -0,0 +1,207 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::memory::{HalfWord, Word};
+
+use super::{
+    SCREEN_BACKGROUND, SCREEN_BACKGROUND_256, SCREEN_BLINK, SCREEN_BOLD, SCREEN_CLEAR, SCREEN_CLEAR_LINE,
+    SCREEN_CLEAR_LINE_END, SCREEN_DEFAULT_BACKGROUND, SCREEN_DEFAULT_FOREGROUND, SCREEN_FOREGROUND,
+    SCREEN_FOREGROUND_256, SCREEN_ITALICS, SCREEN_MOVE_CURSOR, SCREEN_NO_BLINK, SCREEN_NO_BOLD, SCREEN_NO_ITALICS,
+    SCREEN_NO_SCROLL_REGION, SCREEN_NO_STRIKETHROUGH, SCREEN_NO_UNDERLINE, SCREEN_RESET, SCREEN_SCROLL_DOWN,
+    SCREEN_SCROLL_REGION, SCREEN_SCROLL_UP, SCREEN_STRIKETHROUGH, SCREEN_UNDERLINE,
+};
+
+/// Attribute bit set when the cell has a foreground colour, the low byte holds its palette index
+pub const SCREEN_ATTRIBUTE_FOREGROUND: Word = 1 << 16;
+/// Attribute bit set when the cell has a background colour, the second byte holds its palette index
+pub const SCREEN_ATTRIBUTE_BACKGROUND: Word = 1 << 17;
+pub const SCREEN_ATTRIBUTE_BOLD: Word = 1 << 18;
+pub const SCREEN_ATTRIBUTE_UNDERLINE: Word = 1 << 19;
+pub const SCREEN_ATTRIBUTE_BLINK: Word = 1 << 20;
+pub const SCREEN_ATTRIBUTE_ITALICS: Word = 1 << 21;
+pub const SCREEN_ATTRIBUTE_STRIKETHROUGH: Word = 1 << 22;
+
+/// A cell of the Screen, `character` is the word that was drawn and `attributes` the text
+/// attributes and colours it was drawn with, see `SCREEN_ATTRIBUTE_*`. Colours of the 16 colour
+/// palette are the first 16 entries of the 256 colour palette
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub character: Word,
+    pub attributes: Word,
+}
+
+impl Cell {
+    /// The UTF-8 character of the cell, a space for empty cells and `�` for invalid ones
+    pub fn char(&self) -> char {
+        let bytes = self.character.to_le_bytes();
+        let len = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+        match std::str::from_utf8(&bytes[..len]) {
+            Ok("") => ' ',
+            Ok(text) => text.chars().next().unwrap(),
+            Err(_) => char::REPLACEMENT_CHARACTER,
+        }
+    }
+}
+
+struct BufferState {
+    width: Word,
+    height: Word,
+    cells: Vec<Cell>,
+    /// Attributes the next cell is drawn with
+    pen: Word,
+    /// Cell the cursor is at as column and row
+    cursor: (Word, Word),
+    /// First and last row that scroll
+    region: (Word, Word),
+}
+
+impl BufferState {
+    fn row(&mut self, row: Word) -> &mut [Cell] {
+        let start = (row * self.width) as usize;
+        &mut self.cells[start..start + self.width as usize]
+    }
+}
+
+/// Cells of a Screen shared with the host, so embedders and tests can read what was drawn
+#[derive(Clone)]
+pub struct ScreenBuffer {
+    state: Rc<RefCell<BufferState>>,
+}
+
+impl ScreenBuffer {
+    pub fn new(width: HalfWord, height: HalfWord) -> Self {
+        let (width, height) = (width as Word, height as Word);
+        ScreenBuffer {
+            state: Rc::new(RefCell::new(BufferState {
+                width,
+                height,
+                cells: vec![Cell::default(); (width * height) as usize],
+                pen: 0,
+                cursor: (0, 0),
+                region: (0, height.saturating_sub(1)),
+            })),
+        }
+    }
+
+    /// Number of cells, the characters take the addresses `0..len` and the attributes follow
+    pub fn len(&self) -> Word {
+        self.state.borrow().cells.len() as Word
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cell at the column and row, `None` outside the screen
+    pub fn cell(&self, x: Word, y: Word) -> Option<Cell> {
+        let state = self.state.borrow();
+        if x >= state.width {
+            return None;
+        }
+        state.cells.get((y * state.width + x) as usize).copied()
+    }
+
+    /// Cell by its index, row by row from the top left corner
+    pub fn get(&self, index: Word) -> Option<Cell> {
+        self.state.borrow().cells.get(index as usize).copied()
+    }
+
+    /// Characters of every row without trailing spaces, one line per row
+    pub fn text(&self) -> String {
+        let state = self.state.borrow();
+        state
+            .cells
+            .chunks(state.width.max(1) as usize)
+            .map(|row| row.iter().map(Cell::char).collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Column and row of the cell the cursor is at, the column is the width behind the last cell of
+    /// a row
+    pub fn cursor(&self) -> (Word, Word) {
+        self.state.borrow().cursor
+    }
+
+    pub(crate) fn move_cursor(&self, x: Word, y: Word) {
+        self.state.borrow_mut().cursor = (x, y);
+    }
+
+    /// First and last row that scroll
+    pub(crate) fn region(&self) -> (Word, Word) {
+        self.state.borrow().region
+    }
+
+    /// Draws the character with the current attributes and moves the cursor behind it
+    pub(crate) fn draw(&self, index: Word, character: Word) {
+        let mut state = self.state.borrow_mut();
+        let pen = state.pen;
+        state.cells[index as usize] = Cell {
+            character,
+            attributes: pen,
+        };
+        state.cursor = (index % state.width + 1, index / state.width);
+    }
+
+    /// Applies a control code the way the terminal does
+    pub(crate) fn apply(&self, code: Word) {
+        let mut state = self.state.borrow_mut();
+        let parameter = code & 0xFF;
+        let second = (code >> 8) & 0xFF;
+        let (x, y) = state.cursor;
+        match code {
+            SCREEN_CLEAR => state.cells.fill(Cell::default()),
+            SCREEN_RESET => state.pen = 0,
+            SCREEN_BOLD => state.pen |= SCREEN_ATTRIBUTE_BOLD,
+            SCREEN_NO_BOLD => state.pen &= !SCREEN_ATTRIBUTE_BOLD,
+            SCREEN_UNDERLINE => state.pen |= SCREEN_ATTRIBUTE_UNDERLINE,
+            SCREEN_NO_UNDERLINE => state.pen &= !SCREEN_ATTRIBUTE_UNDERLINE,
+            SCREEN_BLINK => state.pen |= SCREEN_ATTRIBUTE_BLINK,
+            SCREEN_NO_BLINK => state.pen &= !SCREEN_ATTRIBUTE_BLINK,
+            SCREEN_ITALICS => state.pen |= SCREEN_ATTRIBUTE_ITALICS,
+            SCREEN_NO_ITALICS => state.pen &= !SCREEN_ATTRIBUTE_ITALICS,
+            SCREEN_STRIKETHROUGH => state.pen |= SCREEN_ATTRIBUTE_STRIKETHROUGH,
+            SCREEN_NO_STRIKETHROUGH => state.pen &= !SCREEN_ATTRIBUTE_STRIKETHROUGH,
+            SCREEN_CLEAR_LINE if y < state.height => state.row(y).fill(Cell::default()),
+            SCREEN_CLEAR_LINE_END if y < state.height => {
+                let x = x.min(state.width) as usize;
+                state.row(y)[x..].fill(Cell::default())
+            }
+            SCREEN_NO_SCROLL_REGION => {
+                state.region = (0, state.height.saturating_sub(1));
+                state.cursor = (0, 0);
+            }
+            // a buffer without rows has nothing to scroll
+            SCREEN_SCROLL_UP | SCREEN_SCROLL_DOWN if !state.cells.is_empty() => {
+                let (top, bottom) = state.region;
+                let width = state.width as usize;
+                let rows = &mut state.cells[top as usize * width..(bottom as usize + 1) * width];
+                if code == SCREEN_SCROLL_UP {
+                    rows.rotate_left(width);
+                    let end = rows.len() - width;
+                    rows[end..].fill(Cell::default());
+                } else {
+                    rows.rotate_right(width);
+                    rows[..width].fill(Cell::default());
+                }
+            }
+            SCREEN_DEFAULT_FOREGROUND => state.pen &= !(SCREEN_ATTRIBUTE_FOREGROUND | 0xFF),
+            SCREEN_DEFAULT_BACKGROUND => state.pen &= !(SCREEN_ATTRIBUTE_BACKGROUND | 0xFF00),
+            _ => match code & 0xFFFF_0000 {
+                SCREEN_FOREGROUND => state.pen = (state.pen & !0xFF) | SCREEN_ATTRIBUTE_FOREGROUND | (parameter & 0xF),
+                SCREEN_FOREGROUND_256 => state.pen = (state.pen & !0xFF) | SCREEN_ATTRIBUTE_FOREGROUND | parameter,
+                SCREEN_BACKGROUND => {
+                    state.pen = (state.pen & !0xFF00) | SCREEN_ATTRIBUTE_BACKGROUND | (parameter & 0xF) << 8
+                }
+                SCREEN_BACKGROUND_256 => {
+                    state.pen = (state.pen & !0xFF00) | SCREEN_ATTRIBUTE_BACKGROUND | parameter << 8
+                }
+                SCREEN_MOVE_CURSOR => state.cursor = (second, parameter),
+                SCREEN_SCROLL_REGION if second <= parameter && parameter < state.height => {
+                    state.region = (second, parameter);
+                    state.cursor = (0, 0);
+                }
+                _ => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{memory::Word, trap::Trap};
+
+use super::{Device, IrqLine};
+
+/// Bytes the timer's register block takes
+pub const TIMER_REGISTERS: Word = 0x10;
+
+/// Control register bit starting the timer, setting it loads the count from the reload value
+pub const TIMER_ENABLE: Word = 1;
+/// Control register bit reloading the count on expiry instead of stopping the timer
+pub const TIMER_PERIODIC: Word = 1 << 1;
+/// Control register bit raising the timer's IRQ line on expiry
+pub const TIMER_INTERRUPT: Word = 1 << 2;
+/// Status register bit set on expiry
+pub const TIMER_EXPIRED: Word = 1;
+
+/// Timer counting down once per executed instruction, the registers are words:
+/// - 0x0 control, see `TIMER_ENABLE`, `TIMER_PERIODIC` and `TIMER_INTERRUPT`
+/// - 0x4 reload value
+/// - 0x8 current count
+/// - 0xC status, see `TIMER_EXPIRED`, writing a word clears the bits set in it
+pub struct Timer {
+    control: Word,
+    reload: Word,
+    count: Word,
+    status: Word,
+    irq: Option<IrqLine>,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timer {
+    /// Timer that is only polled through its status register
+    pub fn new() -> Self {
+        Timer {
+            control: 0,
+            reload: 0,
+            count: 0,
+            status: 0,
+            irq: None,
+        }
+    }
+
+    /// Timer raising the IRQ line on expiry while `TIMER_INTERRUPT` is set
+    pub fn with_irq(irq: IrqLine) -> Self {
+        Timer {
+            irq: Some(irq),
+            ..Self::new()
+        }
+    }
+
+    fn expire(&mut self) {
+        self.status |= TIMER_EXPIRED;
+        if self.control & TIMER_INTERRUPT != 0 {
+            if let Some(irq) = &self.irq {
+                irq.raise();
+            }
+        }
+
+        if self.control & TIMER_PERIODIC != 0 {
+            self.count = self.reload;
+        } else {
+            self.control &= !TIMER_ENABLE;
+        }
+    }
+}
+
+impl Device for Timer {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        match addr {
+            0x0 => Ok(self.control),
+            0x4 => Ok(self.reload),
+            0x8 => Ok(self.count),
+            0xC => Ok(self.status),
+            _ => Err(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        match addr {
+            0x0 => {
+                // only starting the timer loads the count, changing the other bits of a running
+                // timer keeps counting down
+                if value & TIMER_ENABLE != 0 && self.control & TIMER_ENABLE == 0 {
+                    self.count = self.reload;
+                }
+                self.control = value;
+            }
+            0x4 => self.reload = value,
+            0x8 => self.count = value,
+            0xC => self.status &= !value,
+            _ => return Err(Trap::InvalidAddress(addr)),
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        if self.control & TIMER_ENABLE == 0 {
+            return;
+        }
+
+        self.count = self.count.saturating_sub(1);
+        if self.count == 0 {
+            self.expire();
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    memory::{Byte, Word},
+    trap::Trap,
+};
+
+use super::{input::InputFifo, Device, IrqLine};
+
+/// Bytes the UART's register block takes
+pub const UART_REGISTERS: Word = 0xC;
+/// Bytes the receive FIFO holds, further input waits in the source until the program reads
+pub const UART_FIFO_SIZE: usize = 64;
+/// Status register bit set while the receive FIFO holds bytes
+pub const UART_RX_READY: Word = 1;
+/// Status register bit set once the input ended and the receive FIFO is empty
+pub const UART_RX_CLOSED: Word = 1 << 1;
+/// Status register bit set while bytes can be transmitted
+pub const UART_TX_READY: Word = 1 << 2;
+
+/// Serial port passing bytes to and from a host stream, the registers are words:
+/// - 0x0 status, see `UART_RX_READY`, `UART_RX_CLOSED` and `UART_TX_READY`
+/// - 0x4 RX data, reading it takes the oldest received byte out of the FIFO, 0 if it is empty
+/// - 0x8 TX data, writing it transmits the low byte of the word
+pub struct Uart {
+    rx: InputFifo,
+    tx: Option<Box<dyn Write>>,
+    irq: Option<IrqLine>,
+}
+
+impl Default for Uart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uart {
+    /// Unconnected UART, nothing is received and transmitted bytes are dropped
+    pub fn new() -> Self {
+        Uart {
+            rx: InputFifo::closed(UART_FIFO_SIZE),
+            tx: None,
+            irq: None,
+        }
+    }
+
+    /// Receives from the reader on a thread of its own, so the program keeps running while no
+    /// input is available
+    pub fn rx<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.rx = InputFifo::reader(reader, UART_FIFO_SIZE);
+        self
+    }
+
+    /// Receives the bytes, e.g. to drive a program from a test
+    pub fn rx_scripted(mut self, bytes: &[Byte]) -> Self {
+        self.rx = InputFifo::scripted(bytes, UART_FIFO_SIZE);
+        self
+    }
+
+    /// Transmits to the writer, every byte is flushed right away
+    pub fn tx<W: Write + 'static>(mut self, writer: W) -> Self {
+        self.tx = Some(Box::new(writer));
+        self
+    }
+
+    /// Raises the IRQ line whenever bytes arrive in the receive FIFO
+    pub fn with_irq(mut self, irq: IrqLine) -> Self {
+        self.irq = Some(irq);
+        self
+    }
+
+    /// Receives stdin and transmits to stdout, so programs work as filters
+    pub fn stdio() -> Self {
+        Self::new().rx(io::stdin()).tx(io::stdout())
+    }
+
+    /// Transmits into the file, which is created or truncated, nothing is received
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new().tx(File::create(path)?))
+    }
+
+    /// Connects to the Unix domain socket a test harness or terminal program listens at
+    #[cfg(unix)]
+    pub fn unix_socket<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(Self::new().rx(stream.try_clone()?).tx(stream))
+    }
+
+    /// Connects to a new pseudo-terminal in raw mode, returns the path of its slave side which
+    /// terminal programs open
+    #[cfg(target_os = "linux")]
+    pub fn pty() -> io::Result<(Self, String)> {
+        let (master, slave, path) = open_pty()?;
+        let uart = Self::new().rx(PtyMaster {
+            master: master.try_clone()?,
+            _slave: slave,
+        });
+        Ok((uart.tx(master), path))
+    }
+
+    /// Connects to the host stream named by the binding: `none`, `stdio`, `file:<path>`, which is
+    /// output-only, `unix:<path>` or `pty`, which prints the path of the pseudo-terminal to stderr
+    pub fn connect(binding: &str) -> io::Result<Self> {
+        match binding.split_once(':') {
+            None if binding == "none" => Ok(Self::new()),
+            None if binding == "stdio" => Ok(Self::stdio()),
+            Some(("file", path)) => Self::file(path),
+            #[cfg(unix)]
+            Some(("unix", path)) => Self::unix_socket(path),
+            #[cfg(target_os = "linux")]
+            None if binding == "pty" => Self::pty().map(|(uart, path)| {
+                eprintln!("[VM] UART connected to {}", path);
+                uart
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no such UART binding: '{}'", binding),
+            )),
+        }
+    }
+
+    fn status(&self) -> Word {
+        let mut status = 0;
+        if self.rx.is_ready() {
+            status |= UART_RX_READY;
+        }
+        if self.rx.is_closed() {
+            status |= UART_RX_CLOSED;
+        }
+        if self.tx.is_some() {
+            status |= UART_TX_READY;
+        }
+        status
+    }
+}
+
+impl Device for Uart {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        match addr {
+            0x0 => Ok(self.status()),
+            0x4 => Ok(self.rx.pop() as Word),
+            _ => Err(Trap::InvalidAddress(addr)),
+        }
+    }
+
+    fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        if addr != 0x8 {
+            return Err(Trap::InvalidAddress(addr));
+        }
+
+        if let Some(tx) = &mut self.tx {
+            tx.write_all(&[value as Byte])
+                .and_then(|_| tx.flush())
+                .map_err(|err| Trap::DeviceFault(format!("[UART] Failed to transmit: {}", err)))?;
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        if self.rx.fill() {
+            if let Some(irq) = &self.irq {
+                irq.raise();
+            }
+        }
+    }
+}
+
+/// Master side of a pseudo-terminal, the slave is kept open so reads wait for a terminal program
+/// instead of failing while none is connected
+#[cfg(target_os = "linux")]
+struct PtyMaster {
+    master: File,
+    _slave: File,
+}
+
+#[cfg(target_os = "linux")]
+impl Read for PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.master.read(buf)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_pty() -> io::Result<(File, File, String)> {
+    use std::{ffi::CStr, mem::MaybeUninit, os::unix::io::FromRawFd};
+
+    // SAFETY: the master fd is owned by the File right after it is opened, the name buffer and the
+    // termios struct outlive the calls writing them
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(fd);
+
+        let mut name = [0 as libc::c_char; 128];
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 || libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let path = CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned();
+
+        let mut termios = MaybeUninit::<libc::termios>::uninit();
+        if libc::tcgetattr(fd, termios.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut termios = termios.assume_init();
+        libc::cfmakeraw(&mut termios);
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let slave = File::options().read(true).write(true).open(&path)?;
+        Ok((master, slave, path))
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+enum Parser {
+    Ground,
+    Escape,
+    /// Parameters of a control sequence collected so far
+    Csi(String),
+}
+
+struct TerminalState {
+    columns: usize,
+    rows: Vec<Vec<char>>,
+    /// Row and column of the cursor
+    cursor: (usize, usize),
+    cursor_visible: bool,
+    /// First and last row that scroll
+    region: (usize, usize),
+    parser: Parser,
+    /// Bytes of a UTF-8 character that isn't complete yet
+    pending: Vec<u8>,
+}
+
+impl TerminalState {
+    fn blank(&self) -> Vec<char> {
+        vec![' '; self.columns]
+    }
+
+    fn print(&mut self, char: char) {
+        let (row, column) = self.cursor;
+        if let Some(cell) = self.rows.get_mut(row).and_then(|row| row.get_mut(column)) {
+            *cell = char;
+        }
+        self.cursor.1 = (column + 1).min(self.columns.saturating_sub(1));
+    }
+
+    fn scroll(&mut self, lines: usize, up: bool) {
+        let (top, bottom) = self.region;
+        if bottom >= self.rows.len() || top > bottom {
+            return;
+        }
+        for _ in 0..lines.min(bottom - top + 1) {
+            if up {
+                self.rows.remove(top);
+                self.rows.insert(bottom, self.blank());
+            } else {
+                self.rows.remove(bottom);
+                self.rows.insert(top, self.blank());
+            }
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        match std::mem::replace(&mut self.parser, Parser::Ground) {
+            Parser::Escape if byte == b'[' => self.parser = Parser::Csi(String::new()),
+            Parser::Escape => {}
+            Parser::Csi(mut parameters) => match byte {
+                0x40..=0x7E => self.control(&parameters, byte),
+                _ => {
+                    parameters.push(byte as char);
+                    self.parser = Parser::Csi(parameters);
+                }
+            },
+            Parser::Ground => match byte {
+                0x1B => self.parser = Parser::Escape,
+                0x00 => {}
+                b'\r' => self.cursor.1 = 0,
+                b'\n' if self.cursor.0 == self.region.1 => self.scroll(1, true),
+                b'\n' => self.cursor.0 = (self.cursor.0 + 1).min(self.rows.len().saturating_sub(1)),
+                _ => {
+                    self.pending.push(byte);
+                    match std::str::from_utf8(&self.pending) {
+                        Ok(text) => {
+                            let char = text.chars().next().unwrap();
+                            self.pending.clear();
+                            self.print(char);
+                        }
+                        Err(err) if err.error_len().is_some() || self.pending.len() == 4 => {
+                            self.pending.clear();
+                            self.print(char::REPLACEMENT_CHARACTER);
+                        }
+                        Err(_) => {}
+                    }
+                }
+            },
+        }
+    }
+
+    /// Runs the control sequence `ESC [ parameters final`
+    fn control(&mut self, parameters: &str, final_byte: u8) {
+        if let Some(mode) = parameters.strip_prefix('?') {
+            if mode == "25" {
+                self.cursor_visible = final_byte == b'h';
+            }
+            return;
+        }
+        let numbers: Vec<usize> = parameters.split(';').map(|n| n.parse().unwrap_or(0)).collect();
+        let number = |n: usize, default: usize| match numbers.get(n) {
+            Some(0) | None => default,
+            Some(number) => *number,
+        };
+        let (row, column) = self.cursor;
+
+        match final_byte {
+            b'H' => {
+                self.cursor = (
+                    (number(0, 1) - 1).min(self.rows.len().saturating_sub(1)),
+                    (number(1, 1) - 1).min(self.columns.saturating_sub(1)),
+                )
+            }
+            b'J' => {
+                let blank = self.blank();
+                let rows = match numbers[0] {
+                    0 => row + 1..self.rows.len(),
+                    1 => 0..row,
+                    _ => 0..self.rows.len(),
+                };
+                for cleared in rows {
+                    self.rows[cleared] = blank.clone();
+                }
+                if numbers[0] != 2 {
+                    self.control(parameters, b'K');
+                }
+            }
+            b'K' => {
+                if let Some(line) = self.rows.get_mut(row) {
+                    let columns = match numbers[0] {
+                        0 => column..line.len(),
+                        1 => 0..(column + 1).min(line.len()),
+                        _ => 0..line.len(),
+                    };
+                    line[columns].fill(' ');
+                }
+            }
+            b'r' => {
+                let bottom = number(1, self.rows.len()).min(self.rows.len());
+                self.region = (number(0, 1) - 1, bottom.saturating_sub(1));
+                self.cursor = (0, 0);
+            }
+            b'S' => self.scroll(number(0, 1), true),
+            b'T' => self.scroll(number(0, 1), false),
+            _ => {}
+        }
+    }
+}
+
+/// In-memory terminal a Screen can write to, it keeps the text the escape sequences leave on the
+/// screen. Colours and text attributes are ignored
+#[derive(Clone)]
+pub struct VirtualTerminal {
+    state: Rc<RefCell<TerminalState>>,
+}
+
+impl VirtualTerminal {
+    /// Blank terminal, a Screen of width cells needs `2 * width` columns
+    pub fn new(columns: usize, rows: usize) -> Self {
+        VirtualTerminal {
+            state: Rc::new(RefCell::new(TerminalState {
+                columns,
+                rows: vec![vec![' '; columns]; rows],
+                cursor: (0, 0),
+                cursor_visible: true,
+                region: (0, rows.saturating_sub(1)),
+                parser: Parser::Ground,
+                pending: Vec::new(),
+            })),
+        }
+    }
+
+    /// Characters of every row without trailing spaces, one line per row
+    pub fn text(&self) -> String {
+        let state = self.state.borrow();
+        state
+            .rows
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Row and column of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        self.state.borrow().cursor
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.state.borrow().cursor_visible
+    }
+}
+
+impl Write for VirtualTerminal {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        for byte in bytes {
+            state.byte(*byte);
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+#![feature(type_name_of_val)]
+
+use macros::init_registers;
+
+init_registers![
+    "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8",  // general purpose registers
+    "pc",  // program counter
+    "acc", // accumulator
+    "sr",  // status register
+    "sp",  // stack pointer
+    "fp",  // frame pointer
+    "ivt", // interrupt vector table base
+];
+
+pub mod cpu;
+pub mod device;
+pub mod machine;
+pub mod memory;
+pub mod trap;
@@ -0,0 +1,536 @@
+use std::{fmt, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    device::{
+        CastClock, Device, Dma, Framebuffer, HardDrive, ImageFormat, ImageMode, Keyboard, PixelFormat, Screen, Timer,
+        Uart, DMA_REGISTERS, FRAMEBUFFER_PIXELS, HARD_DRIVE_REGISTERS, INTERRUPT_CONTROLLER_REGISTERS, IRQ_LINES,
+        KEYBOARD_REGISTERS, TIMER_REGISTERS, UART_REGISTERS,
+    },
+    memory::{HalfWord, Memory, Word},
+};
+
+use super::{
+    MachineBuilder, DMA_ADDRESS, DMA_IRQ, HARD_DRIVE_ADDRESS, INTERRUPT_CONTROLLER_ADDRESS, KEYBOARD_ADDRESS,
+    KEYBOARD_IRQ, LOAD_ADDRESS, MEMORY_SIZE, STACK_SIZE, UART_ADDRESS, UART_IRQ,
+};
+
+/// Why a machine description was rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The file couldn't be read
+    Read(String),
+    /// The file isn't a valid TOML machine description
+    Parse(String),
+    /// Devices, or a device and main memory, are mapped to the same addresses
+    Overlap(String, String),
+    /// A value is out of range, e.g. an empty mapping or a stack outside of main memory
+    Invalid(String),
+    /// A device couldn't be connected to its host stream
+    Connect(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(err) => write!(f, "[CONFIG] Failed to read machine file: {}", err),
+            ConfigError::Parse(err) => write!(f, "[CONFIG] Invalid machine file: {}", err),
+            ConfigError::Overlap(a, b) => write!(f, "[CONFIG] {} overlaps {}", a, b),
+            ConfigError::Invalid(err) => write!(f, "[CONFIG] {}", err),
+            ConfigError::Connect(err) => write!(f, "[CONFIG] Failed to connect device: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Stack growing down from `address` with room for `size` bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StackConfig {
+    pub address: Word,
+    pub size: Word,
+}
+
+/// A device and where it is mapped, devices with registers take as many bytes as they have registers
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DeviceConfig {
+    Memory {
+        start: Word,
+        size: Word,
+    },
+    Screen {
+        start: Word,
+        end: Word,
+        width: HalfWord,
+        height: HalfWord,
+        /// `terminal`, `none`, `stderr`, `file:<path>` or `virtual`
+        #[serde(default = "default_screen_output")]
+        output: String,
+        /// asciinema cast file the output is recorded to
+        record: Option<String>,
+        #[serde(default)]
+        clock: CastClock,
+    },
+    HardDrive {
+        start: Word,
+        sector_size: Word,
+        sector_count: Word,
+        /// Disk image backing the drive, a zeroed drive is lost on exit without one
+        image: Option<String>,
+        #[serde(default)]
+        mode: DiskMode,
+        /// Copy-on-write overlay, required by and only allowed with `copy_on_write`
+        overlay: Option<String>,
+    },
+    InterruptController {
+        start: Word,
+    },
+    Timer {
+        start: Word,
+        irq: Option<Word>,
+    },
+    Keyboard {
+        start: Word,
+        irq: Option<Word>,
+        /// `none`, `stdin` or `file:<path>`
+        #[serde(default = "default_keyboard_input")]
+        input: String,
+    },
+    Uart {
+        start: Word,
+        irq: Option<Word>,
+        /// `none`, `stdio`, `file:<path>` (output only), `unix:<path>` or `pty`
+        #[serde(default = "default_uart_binding")]
+        binding: String,
+    },
+    Dma {
+        start: Word,
+        irq: Option<Word>,
+    },
+    Framebuffer {
+        start: Word,
+        width: Word,
+        height: Word,
+        #[serde(default)]
+        format: PixelFormat,
+        /// `.ppm` or `.png` file frames are written to, `{frame}` is replaced by the frame number
+        output: Option<String>,
+        /// Writes every n-th presented frame, by default frames are only written on demand and on exit
+        #[serde(default)]
+        every: Word,
+    },
+}
+
+/// How a hard drive uses its disk image, see `ImageMode`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskMode {
+    Create,
+    #[default]
+    ReadWrite,
+    ReadOnly,
+    CopyOnWrite,
+}
+
+fn default_screen_output() -> String {
+    "terminal".to_string()
+}
+
+fn default_keyboard_input() -> String {
+    "stdin".to_string()
+}
+
+fn default_uart_binding() -> String {
+    "none".to_string()
+}
+
+impl DeviceConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceConfig::Memory { .. } => "memory",
+            DeviceConfig::Screen { .. } => "screen",
+            DeviceConfig::HardDrive { .. } => "hard_drive",
+            DeviceConfig::InterruptController { .. } => "interrupt_controller",
+            DeviceConfig::Timer { .. } => "timer",
+            DeviceConfig::Keyboard { .. } => "keyboard",
+            DeviceConfig::Uart { .. } => "uart",
+            DeviceConfig::Dma { .. } => "dma",
+            DeviceConfig::Framebuffer { .. } => "framebuffer",
+        }
+    }
+
+    /// Addresses `start..end` the device is mapped to, `None` if they run past the address space
+    pub fn range(&self) -> Option<(Word, Word)> {
+        let (start, size) = match *self {
+            DeviceConfig::Memory { start, size } => (start, size),
+            DeviceConfig::Screen { start, end, .. } => (start, end.checked_sub(start)?),
+            DeviceConfig::HardDrive { start, .. } => (start, HARD_DRIVE_REGISTERS),
+            DeviceConfig::InterruptController { start } => (start, INTERRUPT_CONTROLLER_REGISTERS),
+            DeviceConfig::Timer { start, .. } => (start, TIMER_REGISTERS),
+            DeviceConfig::Keyboard { start, .. } => (start, KEYBOARD_REGISTERS),
+            DeviceConfig::Uart { start, .. } => (start, UART_REGISTERS),
+            DeviceConfig::Dma { start, .. } => (start, DMA_REGISTERS),
+            DeviceConfig::Framebuffer {
+                start,
+                width,
+                height,
+                format,
+                ..
+            } => {
+                let pixels = width.checked_mul(height)?.checked_mul(format.bytes_per_pixel())?;
+                (start, pixels.checked_add(FRAMEBUFFER_PIXELS)?)
+            }
+        };
+        Some((start, start.checked_add(size)?))
+    }
+
+    pub fn irq(&self) -> Option<Word> {
+        match *self {
+            DeviceConfig::Timer { irq, .. }
+            | DeviceConfig::Keyboard { irq, .. }
+            | DeviceConfig::Uart { irq, .. }
+            | DeviceConfig::Dma { irq, .. } => irq,
+            _ => None,
+        }
+    }
+
+    /// Options of the device that contradict each other or its mapping
+    fn problem(&self) -> Option<&'static str> {
+        match self {
+            DeviceConfig::Screen { width, height, .. } if *width == 0 || *height == 0 => Some("has no cells"),
+            // the cursor is packed into a byte each for its column and row
+            DeviceConfig::Screen { width, height, .. } if *width > 255 || *height > 255 => {
+                Some("is larger than the 255 columns and rows its cursor can address")
+            }
+            DeviceConfig::Screen {
+                start,
+                end,
+                width,
+                height,
+                ..
+            } if u64::from(end - start) < u64::from(*width) * u64::from(*height) * 2 + 4 => {
+                Some("is too small for its cells, their attributes and the TTY register")
+            }
+            DeviceConfig::HardDrive {
+                image, mode, overlay, ..
+            } => match (image, mode, overlay) {
+                (None, DiskMode::ReadWrite, None) => None,
+                (None, _, _) => Some("sets a mode or overlay without an image"),
+                (Some(_), DiskMode::CopyOnWrite, None) => Some("needs an overlay for copy_on_write"),
+                (Some(_), DiskMode::CopyOnWrite, Some(_)) => None,
+                (Some(_), _, Some(_)) => Some("has an overlay but isn't copy_on_write"),
+                (Some(_), _, None) => None,
+            },
+            DeviceConfig::Framebuffer {
+                width,
+                height,
+                output,
+                every,
+                ..
+            } => match output {
+                _ if *width == 0 || *height == 0 => Some("has no pixels"),
+                Some(path) if ImageFormat::from_path(path).is_none() => Some("writes frames to neither .ppm nor .png"),
+                None if *every != 0 => Some("writes every n-th frame without an output"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.range() {
+            Some((start, end)) => format!("{} at 0x{:X}..0x{:X}", self.name(), start, end),
+            None => self.name().to_string(),
+        }
+    }
+}
+
+/// Description of a machine's main memory, stack and devices, read from a TOML file:
+///
+/// ```toml
+/// load_address = 0x408
+/// memory_size = 0xFFFF
+/// stack = { address = 0xFFFF, size = 1024 }
+///
+/// [[device]]
+/// type = "screen"
+/// start = 0x0
+/// end = 0x400
+/// width = 16
+/// height = 16
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineConfig {
+    #[serde(default = "default_load_address")]
+    pub load_address: Word,
+    #[serde(default = "default_memory_size")]
+    pub memory_size: Word,
+    /// Defaults to the end of main memory
+    pub stack: Option<StackConfig>,
+    #[serde(default, rename = "device")]
+    pub devices: Vec<DeviceConfig>,
+}
+
+fn default_load_address() -> Word {
+    LOAD_ADDRESS
+}
+
+fn default_memory_size() -> Word {
+    MEMORY_SIZE
+}
+
+impl MachineConfig {
+    /// The machine `./vm` runs programs on without `--machine`, `MachineBuilder::standard()` with
+    /// a keyboard reading stdin and an unconnected UART
+    pub fn standard() -> Self {
+        MachineConfig {
+            load_address: LOAD_ADDRESS,
+            memory_size: MEMORY_SIZE,
+            stack: Some(StackConfig {
+                address: 0xFFFF,
+                size: STACK_SIZE,
+            }),
+            devices: vec![
+                DeviceConfig::Screen {
+                    start: 0,
+                    end: 0x400,
+                    width: 16,
+                    height: 16,
+                    output: default_screen_output(),
+                    record: None,
+                    clock: CastClock::Wall,
+                },
+                DeviceConfig::InterruptController {
+                    start: INTERRUPT_CONTROLLER_ADDRESS,
+                },
+                DeviceConfig::Keyboard {
+                    start: KEYBOARD_ADDRESS,
+                    irq: Some(KEYBOARD_IRQ),
+                    input: default_keyboard_input(),
+                },
+                DeviceConfig::Uart {
+                    start: UART_ADDRESS,
+                    irq: Some(UART_IRQ),
+                    binding: default_uart_binding(),
+                },
+                DeviceConfig::HardDrive {
+                    start: HARD_DRIVE_ADDRESS,
+                    sector_size: 8,
+                    sector_count: 128,
+                    image: None,
+                    mode: DiskMode::ReadWrite,
+                    overlay: None,
+                },
+                DeviceConfig::Dma {
+                    start: DMA_ADDRESS,
+                    irq: Some(DMA_IRQ),
+                },
+            ],
+        }
+    }
+
+    /// Parses and validates a TOML machine description
+    pub fn from_toml(source: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(source).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let source = fs::read_to_string(path).map_err(|err| ConfigError::Read(err.to_string()))?;
+        Self::from_toml(&source)
+    }
+
+    /// Checks that main memory, the stack and the devices fit into the address space without
+    /// overlapping and that every IRQ line is raised by a single device
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.memory_size == 0 {
+            return Err(ConfigError::Invalid("Main memory is empty".to_string()));
+        }
+        let memory_end = self
+            .load_address
+            .checked_add(self.memory_size)
+            .ok_or_else(|| ConfigError::Invalid("Main memory runs past the address space".to_string()))?;
+
+        if let Some(stack) = self.stack {
+            let fits = stack.size > 0
+                && stack.address <= memory_end
+                && stack
+                    .address
+                    .checked_sub(stack.size)
+                    .is_some_and(|bottom| bottom >= self.load_address);
+            if !fits {
+                return Err(ConfigError::Invalid(format!(
+                    "Stack of 0x{:X} bytes at 0x{:X} doesn't fit into main memory",
+                    stack.size, stack.address
+                )));
+            }
+        }
+
+        let memory = format!("main memory at 0x{:X}..0x{:X}", self.load_address, memory_end);
+        let mut mapped = vec![(self.load_address, memory_end, memory)];
+        let mut irqs: Vec<(Word, String)> = Vec::new();
+        let has_controller = self
+            .devices
+            .iter()
+            .any(|device| matches!(device, DeviceConfig::InterruptController { .. }));
+
+        for device in &self.devices {
+            let (start, end) = match device.range() {
+                Some((start, end)) if start < end => (start, end),
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "{} has an empty mapping",
+                        device.describe()
+                    )))
+                }
+            };
+            if let Some((_, _, other)) = mapped.iter().find(|(s, e, _)| start < *e && *s < end) {
+                return Err(ConfigError::Overlap(device.describe(), other.clone()));
+            }
+            mapped.push((start, end, device.describe()));
+
+            if let Some(problem) = device.problem() {
+                return Err(ConfigError::Invalid(format!("{} {}", device.describe(), problem)));
+            }
+
+            if let Some(irq) = device.irq() {
+                if irq >= IRQ_LINES {
+                    return Err(ConfigError::Invalid(format!(
+                        "{} raises no such IRQ line: {}",
+                        device.describe(),
+                        irq
+                    )));
+                }
+                if !has_controller {
+                    return Err(ConfigError::Invalid(format!(
+                        "{} raises IRQ line {} without an interrupt controller",
+                        device.describe(),
+                        irq
+                    )));
+                }
+                if let Some((_, other)) = irqs.iter().find(|(line, _)| *line == irq) {
+                    return Err(ConfigError::Invalid(format!(
+                        "{} and {} raise the same IRQ line {}",
+                        other,
+                        device.describe(),
+                        irq
+                    )));
+                }
+                irqs.push((irq, device.describe()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the description and maps its devices, connecting keyboards and UARTs to their
+    /// host streams
+    pub fn builder(&self) -> Result<MachineBuilder, ConfigError> {
+        self.validate()?;
+
+        let mut builder = MachineBuilder::new()
+            .load_address(self.load_address)
+            .memory_size(self.memory_size);
+        if let Some(stack) = self.stack {
+            builder = builder.stack(stack.address, stack.size);
+        }
+
+        for device in &self.devices {
+            let (start, end) = device.range().expect("validated above");
+            let connect = |err: std::io::Error| ConfigError::Connect(format!("{}: {}", device.describe(), err));
+
+            let mapped: Box<dyn Device> = match device {
+                DeviceConfig::Memory { size, .. } => Box::new(Memory::new(*size)),
+                DeviceConfig::Screen {
+                    width,
+                    height,
+                    output,
+                    record,
+                    clock,
+                    ..
+                } => {
+                    let screen = Screen::new(*width, *height).connect(output).map_err(connect)?;
+                    match record {
+                        Some(path) => Box::new(screen.record(path, *clock).map_err(connect)?),
+                        None => Box::new(screen),
+                    }
+                }
+                DeviceConfig::HardDrive {
+                    sector_size,
+                    sector_count,
+                    image: Some(image),
+                    mode,
+                    overlay,
+                    ..
+                } => {
+                    let mode = match mode {
+                        DiskMode::Create => ImageMode::Create,
+                        DiskMode::ReadWrite => ImageMode::ReadWrite,
+                        DiskMode::ReadOnly => ImageMode::ReadOnly,
+                        DiskMode::CopyOnWrite => {
+                            ImageMode::CopyOnWrite(overlay.clone().expect("validated above").into())
+                        }
+                    };
+                    Box::new(HardDrive::open(image, *sector_size, *sector_count, mode).map_err(connect)?)
+                }
+                DeviceConfig::HardDrive {
+                    sector_size,
+                    sector_count,
+                    ..
+                } => Box::new(HardDrive::new(*sector_size, *sector_count)),
+                DeviceConfig::InterruptController { .. } => {
+                    builder = builder.interrupt_controller(start);
+                    continue;
+                }
+                DeviceConfig::Timer { irq, .. } => match irq {
+                    Some(line) => Box::new(Timer::with_irq(builder.irq_line(*line))),
+                    None => Box::new(Timer::new()),
+                },
+                DeviceConfig::Keyboard { irq, input, .. } => {
+                    let keyboard = Keyboard::connect(input).map_err(connect)?;
+                    match irq {
+                        Some(line) => Box::new(keyboard.with_irq(builder.irq_line(*line))),
+                        None => Box::new(keyboard),
+                    }
+                }
+                DeviceConfig::Framebuffer {
+                    width,
+                    height,
+                    format,
+                    output,
+                    every,
+                    ..
+                } => {
+                    let framebuffer = Framebuffer::new(*width, *height, *format).every(*every);
+                    match output {
+                        Some(path) => Box::new(framebuffer.output(path).map_err(connect)?),
+                        None => Box::new(framebuffer),
+                    }
+                }
+                DeviceConfig::Dma { irq, .. } => {
+                    let dma = match irq {
+                        Some(line) => Dma::with_irq(builder.irq_line(*line)),
+                        None => Dma::new(),
+                    };
+                    builder = builder.dma_controller(start, dma);
+                    continue;
+                }
+                DeviceConfig::Uart { irq, binding, .. } => {
+                    let uart = Uart::connect(binding).map_err(connect)?;
+                    match irq {
+                        Some(line) => Box::new(uart.with_irq(builder.irq_line(*line))),
+                        None => Box::new(uart),
+                    }
+                }
+            };
+            builder = builder.device(mapped, start, end);
+        }
+
+        Ok(builder)
+    }
+}
@@ -0,0 +1,344 @@
+use macros::reg;
+
+use crate::{
+    cpu::CPU,
+    device::{
+        Device, Dma, DmaController, Framebuffer, HardDrive, InterruptController, Interrupts, IrqLine, Keyboard, Screen,
+        Timer, Uart, DMA_REGISTERS, HARD_DRIVE_REGISTERS, INTERRUPT_CONTROLLER_REGISTERS, KEYBOARD_REGISTERS,
+        TIMER_REGISTERS, UART_REGISTERS,
+    },
+    memory::{Byte, Memory, MemoryMapper, Word},
+    trap::{Trap, VmError},
+};
+
+/// Address of the Screen on the standard machine
+pub const SCREEN_ADDRESS: Word = 0x000;
+/// End of the Screen's range on the standard machine
+pub const SCREEN_END: Word = 0x400;
+/// Address programs are loaded at on the standard machine, behind the Screen
+pub const LOAD_ADDRESS: Word = 0x408;
+/// Size of the main memory of the standard machine
+pub const MEMORY_SIZE: Word = 0xFFFF;
+/// Size of the stack in bytes if none is set
+pub const STACK_SIZE: Word = 1024;
+/// Address of the interrupt controller on the standard machine, behind main memory
+pub const INTERRUPT_CONTROLLER_ADDRESS: Word = 0x11000;
+/// Address `./vm` maps its keyboard to
+pub const KEYBOARD_ADDRESS: Word = 0x11010;
+/// IRQ line of the keyboard `./vm` maps
+pub const KEYBOARD_IRQ: Word = 1;
+/// Address `./vm` maps its UART to
+pub const UART_ADDRESS: Word = 0x11020;
+/// IRQ line of the UART `./vm` maps
+pub const UART_IRQ: Word = 2;
+/// Address of the HardDrive controller on the standard machine
+pub const HARD_DRIVE_ADDRESS: Word = 0x11030;
+/// Address of the DMA controller on the standard machine
+pub const DMA_ADDRESS: Word = 0x11050;
+/// IRQ line of the DMA controller on the standard machine
+pub const DMA_IRQ: Word = 3;
+
+/// Names of the standard machine's device addresses and of the HardDrive registers programs use,
+/// the assembler knows them as constants
+pub const MACHINE_ADDRESSES: &[(&str, Word)] = &[
+    ("SCREEN_ADDRESS", SCREEN_ADDRESS),
+    ("INTERRUPT_CONTROLLER_ADDRESS", INTERRUPT_CONTROLLER_ADDRESS),
+    ("KEYBOARD_ADDRESS", KEYBOARD_ADDRESS),
+    ("UART_ADDRESS", UART_ADDRESS),
+    ("HARD_DRIVE_ADDRESS", HARD_DRIVE_ADDRESS),
+    ("HARD_DRIVE_SECTOR_ADDRESS", HARD_DRIVE_ADDRESS),
+    ("HARD_DRIVE_COUNT_ADDRESS", HARD_DRIVE_ADDRESS + 0x04),
+    ("HARD_DRIVE_COMMAND_ADDRESS", HARD_DRIVE_ADDRESS + 0x08),
+    ("HARD_DRIVE_STATUS_ADDRESS", HARD_DRIVE_ADDRESS + 0x0C),
+    ("HARD_DRIVE_ERROR_ADDRESS", HARD_DRIVE_ADDRESS + 0x10),
+    ("HARD_DRIVE_DATA_ADDRESS", HARD_DRIVE_ADDRESS + 0x1C),
+    ("DMA_ADDRESS", DMA_ADDRESS),
+];
+
+/// Whether the machine can keep executing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Running,
+    Halted,
+}
+
+/// Outcome of running the machine, `steps` counts the executed instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunResult {
+    pub steps: usize,
+    pub state: State,
+}
+
+/// Configures a `Machine`: its devices, main memory, stack and the image it starts with
+pub struct MachineBuilder {
+    devices: Vec<(Box<dyn Device>, Word, Word)>,
+    load_address: Word,
+    memory_size: Word,
+    stack: Option<(Word, Word)>,
+    image: Vec<Byte>,
+    interrupts: Interrupts,
+    dma: Vec<Dma>,
+}
+
+impl Default for MachineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineBuilder {
+    /// A machine with nothing but main memory at the load address
+    pub fn new() -> Self {
+        MachineBuilder {
+            devices: Vec::new(),
+            load_address: LOAD_ADDRESS,
+            memory_size: MEMORY_SIZE,
+            stack: None,
+            image: Vec::new(),
+            interrupts: Interrupts::new(),
+            dma: Vec::new(),
+        }
+    }
+
+    /// The machine `./vm` runs programs on: a 16x16 Screen at 0x000, 0xFFFF bytes of memory at
+    /// 0x408 with the stack growing down from 0xFFFF, the interrupt controller at 0x11000, a
+    /// HardDrive with 128 sectors of 8 bytes at 0x11030 and the DMA controller at 0x11050
+    pub fn standard() -> Self {
+        Self::new()
+            .device(Box::new(Screen::new(16, 16)), SCREEN_ADDRESS, SCREEN_END)
+            .interrupt_controller(INTERRUPT_CONTROLLER_ADDRESS)
+            .hard_drive(HARD_DRIVE_ADDRESS, HardDrive::new(8, 128))
+            .dma(DMA_ADDRESS, DMA_IRQ)
+            .stack(0xFFFF, STACK_SIZE)
+    }
+
+    /// Maps the device to the addresses `start..end`, devices mapped later take precedence over
+    /// earlier ones and over main memory
+    pub fn device(mut self, device: Box<dyn Device>, start: Word, end: Word) -> Self {
+        self.devices.push((device, start, end));
+        self
+    }
+
+    /// IRQ line of the machine's interrupt controller, for devices that raise interrupts
+    pub fn irq_line(&self, line: Word) -> IrqLine {
+        self.interrupts.line(line)
+    }
+
+    /// Maps the interrupt controller's pending and mask registers to `start..start + 8`
+    pub fn interrupt_controller(self, start: Word) -> Self {
+        let controller = InterruptController::new(self.interrupts.clone());
+        self.device(Box::new(controller), start, start + INTERRUPT_CONTROLLER_REGISTERS)
+    }
+
+    /// Maps a timer raising the IRQ line on expiry to `start..start + 16`
+    pub fn timer(self, start: Word, line: Word) -> Self {
+        let timer = Timer::with_irq(self.irq_line(line));
+        self.device(Box::new(timer), start, start + TIMER_REGISTERS)
+    }
+
+    /// Maps the keyboard's status and data registers to `start..start + 8`, raising the IRQ line
+    /// when input arrives
+    pub fn keyboard(self, start: Word, line: Word, keyboard: Keyboard) -> Self {
+        let keyboard = keyboard.with_irq(self.irq_line(line));
+        self.device(Box::new(keyboard), start, start + KEYBOARD_REGISTERS)
+    }
+
+    /// Maps the UART's status and data registers to `start..start + 12`, raising the IRQ line when
+    /// bytes are received
+    pub fn uart(self, start: Word, line: Word, uart: Uart) -> Self {
+        let uart = uart.with_irq(self.irq_line(line));
+        self.device(Box::new(uart), start, start + UART_REGISTERS)
+    }
+
+    /// Maps the hard drive controller's registers to `start..start + 0x20`
+    pub fn hard_drive(self, start: Word, drive: HardDrive) -> Self {
+        self.device(Box::new(drive), start, start + HARD_DRIVE_REGISTERS)
+    }
+
+    /// Maps a DMA controller raising the IRQ line when a transfer ends to `start..start + 0x1C`
+    pub fn dma(self, start: Word, line: Word) -> Self {
+        let dma = Dma::with_irq(self.irq_line(line));
+        self.dma_controller(start, dma)
+    }
+
+    /// Maps the controller of the DMA channel, the CPU moves its transfers
+    pub(crate) fn dma_controller(mut self, start: Word, dma: Dma) -> Self {
+        self.dma.push(dma.clone());
+        self.device(Box::new(DmaController::new(dma)), start, start + DMA_REGISTERS)
+    }
+
+    /// Maps the framebuffer's registers, palette and pixels to `start..start + framebuffer.size()`
+    pub fn framebuffer(self, start: Word, framebuffer: Framebuffer) -> Self {
+        let end = start + framebuffer.size();
+        self.device(Box::new(framebuffer), start, end)
+    }
+
+    /// Address main memory starts at, the image is loaded and execution starts there
+    pub fn load_address(mut self, address: Word) -> Self {
+        self.load_address = address;
+        self
+    }
+
+    pub fn memory_size(mut self, size: Word) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    /// Stack growing down from `address` with room for `size` bytes, by default it sits at the end
+    /// of main memory
+    pub fn stack(mut self, address: Word, size: Word) -> Self {
+        self.stack = Some((address, size));
+        self
+    }
+
+    /// Program image loaded at the load address
+    pub fn image(mut self, image: Vec<Byte>) -> Self {
+        self.image = image;
+        self
+    }
+
+    pub fn build(self) -> Machine {
+        let mut mm = MemoryMapper::new();
+        let memory_end = self.load_address + self.memory_size;
+        mm.map(
+            Box::new(Memory::from(self.image, self.memory_size)),
+            self.load_address,
+            memory_end,
+        );
+        for (device, start, end) in self.devices {
+            mm.map(device, start, end);
+        }
+
+        let mut cpu = CPU::new(mm, self.load_address);
+        cpu.interrupts = self.interrupts;
+        cpu.dma = self.dma;
+        let (stack_address, stack_size) = self.stack.unwrap_or((memory_end, STACK_SIZE.min(self.memory_size)));
+        cpu.set_stack(stack_address, stack_size);
+
+        Machine { cpu, steps: 0 }
+    }
+}
+
+/// A CPU wired to its devices, ready to run an image
+pub struct Machine {
+    pub cpu: CPU,
+    steps: usize,
+}
+
+impl Machine {
+    pub fn builder() -> MachineBuilder {
+        MachineBuilder::new()
+    }
+
+    /// Executes a single instruction, a trapping instruction counts as executed
+    pub fn step(&mut self) -> Result<State, VmError> {
+        if !self.cpu.is_halted() {
+            self.steps += 1;
+            self.cpu.step()?;
+        }
+        Ok(self.state())
+    }
+
+    /// Runs until the machine halts or traps
+    pub fn run(&mut self) -> Result<RunResult, VmError> {
+        self.run_for(usize::MAX)
+    }
+
+    /// Runs until the machine halts, traps or `max_steps` instructions were executed
+    pub fn run_for(&mut self, max_steps: usize) -> Result<RunResult, VmError> {
+        let mut steps = 0;
+        while steps < max_steps && !self.cpu.is_halted() {
+            steps += 1;
+            self.step()?;
+        }
+
+        Ok(RunResult {
+            steps,
+            state: self.state(),
+        })
+    }
+
+    pub fn state(&self) -> State {
+        if self.cpu.is_halted() {
+            State::Halted
+        } else {
+            State::Running
+        }
+    }
+
+    /// Clears the halt signal so execution continues behind the HALT
+    pub fn resume(&mut self) {
+        self.cpu.resume();
+    }
+
+    /// Instructions executed since the machine was built
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Value of the register with the given name, e.g. "r1" or "acc"
+    pub fn register(&self, name: &str) -> Option<Word> {
+        register_address(name).map(|addr| self.cpu.get_reg(addr))
+    }
+
+    /// Sets the register with the given name, returns false if there is no such register
+    pub fn set_register(&mut self, name: &str, value: Word) -> bool {
+        match register_address(name) {
+            Some(addr) => {
+                self.cpu.set_reg(addr, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every register with its name in the order of the register file
+    pub fn registers(&self) -> Vec<(&'static str, Word)> {
+        crate::REGISTERS
+            .iter()
+            .map(|(name, addr)| (*name, self.cpu.get_reg(*addr)))
+            .collect()
+    }
+
+    pub fn pc(&self) -> Word {
+        self.cpu.get_reg(reg!("pc"))
+    }
+
+    /// Moves execution to the address
+    pub fn jump(&mut self, address: Word) {
+        self.cpu.set_reg(reg!("pc"), address);
+    }
+
+    pub fn read_byte(&self, address: Word) -> Result<Byte, Trap> {
+        self.cpu.memory_mapper.get_byte(address)
+    }
+
+    pub fn read_word(&self, address: Word) -> Result<Word, Trap> {
+        self.cpu.memory_mapper.get_word(address)
+    }
+
+    pub fn read_range(&self, address: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+        self.cpu.memory_mapper.get_range(address, size)
+    }
+
+    pub fn write_word(&mut self, address: Word, value: Word) -> Result<(), Trap> {
+        self.cpu.memory_mapper.set_word(address, value)
+    }
+
+    /// Writes buffered device data back to the host, e.g. disk images
+    pub fn flush(&mut self) -> Result<(), Trap> {
+        self.cpu.memory_mapper.flush()
+    }
+
+    /// Copies an image into memory at the address, e.g. to add a routine or data next to the program
+    pub fn load(&mut self, address: Word, image: Vec<Byte>) -> Result<(), Trap> {
+        self.cpu.memory_mapper.set_range(address, image)
+    }
+}
+
+fn register_address(name: &str) -> Option<Word> {
+    crate::REGISTERS
+        .iter()
+        .find(|(register, _)| *register == name)
+        .map(|(_, addr)| *addr)
+}
@@ -0,0 +1,2 @@
+mod machine_struct; pub use machine_struct::*;
+mod config; pub use config::*;
\ No newline at end of file
@@ -1,35 +1,24 @@
 #![feature(panic_info_message)]
-#![feature(type_name_of_val)]
 
-use macros::init_registers;
+use std::{
+    env,
+    fs::File,
+    io::{self, IsTerminal, Read},
+    panic,
+    path::Path,
+    process,
+};
 
-init_registers![
-    "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8",  // general purpose registers
-    "pc",  // program counter
-    "acc", // accumulator
-    "sr",  // status register
-    "sp",  // stack pointer
-    "fp",  // frame pointer
-];
-
-mod device;
-
-mod memory;
-use std::{env, fs::File, io::Read, panic};
-
-use memory::{Byte, Memory};
-mod cpu;
-use cpu::CPU;
-
-use crate::{device::{HardDrive, Screen}, memory::MemoryMapper};
+use vm::{
+    device::{Cast, CastClock},
+    machine::{DeviceConfig, DiskMode, MachineConfig},
+    memory::Byte,
+};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        panic!(
-            "[VM] Usage: {0} <program_path> [-debug]\nExample: {0} a.bin",
-            args.get(0).unwrap()
-        );
+        panic!("{}", usage(args.get(0).unwrap()));
     }
 
     // custom panic outputs
@@ -43,6 +32,25 @@ fn main() {
         }
     }));
 
+    // -play replays a recording instead of running a program
+    if args[1] == "-play" {
+        let path = match args.get(2) {
+            Some(path) if !path.starts_with('-') => path,
+            _ => panic!("{}", usage(&args[0])),
+        };
+        let speed = match &args[3..] {
+            [] => 1.0,
+            [name, speed] if name == "-speed" => speed
+                .parse()
+                .unwrap_or_else(|_| panic!("[VM] Invalid speed '{}'", speed)),
+            _ => panic!("{}", usage(&args[0])),
+        };
+        let cast = Cast::load(path).unwrap_or_else(|err| panic!("[VM] Failed to load the recording: {}", err));
+        cast.play(&mut io::stdout(), speed)
+            .unwrap_or_else(|err| panic!("[VM] Failed to play the recording: {}", err));
+        return;
+    }
+
     let mut bin = match File::open(args.get(1).unwrap()) {
         Ok(file) => file,
         Err(_) => panic!("[VM] Failed to open program file"),
@@ -54,23 +62,128 @@ fn main() {
 
     // #################
     // # PROGRAM START #
-    // #################    
-    
-    let memory = Memory::from(buff, 0xFFFF);
-    let screen = Screen::new(16, 16);
-    let hard_drive = HardDrive::new(8, 128);
-
-    let mut mm = MemoryMapper::new();
-    mm.map(Box::new(screen), 0, 0x400);
-    mm.map(Box::new(hard_drive), 0x400, 0x408);
-    mm.map(Box::new(memory), 0x408, 0xFFFF + 0x408);
-
-    let mut cpu = CPU::new(mm, 0x408);
-    cpu.set_stack(0xFFFF, 1024);
-
-    if args.len() > 2 && args.get(2).unwrap() == "-debug" {
-        cpu.run_debug(16);
+    // #################
+
+    let debug = args.iter().skip(2).any(|arg| arg == "-debug");
+    let mut config = match option(&args, "--machine") {
+        Some(path) => MachineConfig::load(path).unwrap_or_else(|err| panic!("{}", err)),
+        None => MachineConfig::standard(),
+    };
+
+    // -input and -uart rebind the keyboards and UARTs of the machine, the keyboards read stdin
+    // unless the debugger or a UART use it
+    let input = option(&args, "-input").map(|path| format!("file:{}", path));
+    let uart_binding = option(&args, "-uart");
+    if let Some(uart_binding) = uart_binding {
+        for device in config.devices.iter_mut() {
+            if let DeviceConfig::Uart { binding, .. } = device {
+                *binding = uart_binding.to_string();
+            }
+        }
+    }
+    // -screen redirects the escape sequences of the screens, -record records them timed by -clock
+    let screen_output = option(&args, "-screen");
+    let screen_record = option(&args, "-record");
+    let screen_clock = option(&args, "-clock")
+        .map(|name| CastClock::from_name(name).unwrap_or_else(|| panic!("[VM] No such clock: '{}'", name)));
+    for device in config.devices.iter_mut() {
+        if let DeviceConfig::Screen {
+            output, record, clock, ..
+        } = device
+        {
+            if let Some(screen_output) = screen_output {
+                *output = screen_output.to_string();
+            }
+            if let Some(path) = screen_record {
+                *record = Some(path.to_string());
+            }
+            if let Some(screen_clock) = screen_clock {
+                *clock = screen_clock;
+            }
+        }
+    }
+    // -disk backs the hard drives with the image, which is created if it doesn't exist yet
+    if let Some(path) = option(&args, "-disk") {
+        for device in config.devices.iter_mut() {
+            if let DeviceConfig::HardDrive {
+                image, mode, overlay, ..
+            } = device
+            {
+                *image = Some(path.to_string());
+                *mode = if Path::new(path).exists() {
+                    DiskMode::ReadWrite
+                } else {
+                    DiskMode::Create
+                };
+                *overlay = None;
+            }
+        }
+    }
+
+    let stdin_taken = debug
+        || config
+            .devices
+            .iter()
+            .any(|device| matches!(device, DeviceConfig::Uart { binding, .. } if binding == "stdio"));
+
+    let mut reads_stdin = false;
+    for device in config.devices.iter_mut() {
+        if let DeviceConfig::Keyboard {
+            input: keyboard_input, ..
+        } = device
+        {
+            if let Some(input) = &input {
+                *keyboard_input = input.clone();
+            } else if stdin_taken && keyboard_input == "stdin" {
+                *keyboard_input = "none".to_string();
+            }
+            reads_stdin |= keyboard_input == "stdin";
+        }
+    }
+
+    #[cfg(unix)]
+    let raw_terminal = if reads_stdin && io::stdin().is_terminal() {
+        vm::device::RawTerminal::enable().ok()
     } else {
-        cpu.run();
+        None
+    };
+
+    let mut machine = config
+        .builder()
+        .unwrap_or_else(|err| panic!("{}", err))
+        .image(buff)
+        .build();
+
+    let result = if debug {
+        machine.cpu.run_debug(16)
+    } else {
+        machine.run().map(|_| ())
+    };
+    #[cfg(unix)]
+    drop(raw_terminal);
+
+    if let Err(err) = machine.flush() {
+        println!("0xVM failed to flush its devices:\n{}", err);
+    }
+
+    if let Err(err) = result {
+        println!("0xVM trapped:\n{}", err);
+        process::exit(1);
+    }
+}
+
+fn usage(program: &str) -> String {
+    format!(
+        "[VM] Usage: {0} <program_path> [-debug] [--machine <machine.toml>] [-disk <image_path>] [-input <input_path>] [-uart <stdio|file:path (output only)|unix:path|pty>] [-screen <terminal|none|stderr|file:path|virtual>] [-record <cast_path>] [-clock <wall|instructions>]\n       {0} -play <cast_path> [-speed <factor>]\nExample: {0} a.bin",
+        program
+    )
+}
+
+/// Value following the option in the arguments behind the program path
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let i = args.iter().skip(2).position(|arg| arg == name)?;
+    match args.get(i + 3) {
+        Some(value) => Some(value),
+        None => panic!("[VM] Missing value after '{}'", name),
     }
 }
@@ -1,4 +1,4 @@
-use crate::device::Device;
+use crate::{device::Device, trap::Trap};
 
 use super::{Byte, Word};
 
@@ -20,65 +20,90 @@ impl MemoryMapper {
         }
     }
 
-    fn find_region(&self, addr: Word) -> usize {
+    fn find_region(&self, addr: Word) -> Result<usize, Trap> {
         for (i, region) in self.regions.iter().enumerate() {
             if region.start <= addr && addr < region.end {
-                return i;
+                return Ok(i);
             }
         }
 
-        panic!("[MEMORY MAPPER] No such region: '0x{:08X}'", addr);
+        Err(Trap::UnmappedAddress(addr))
     }
 
-    fn get_region_and_addr(&self, addr: Word) -> (usize, Word) {
-        let region_index = self.find_region(addr);
-        let final_addr = addr - self.regions[region_index].start;
+    /// Region containing addr, the address relative to its start and the start to offset device traps by
+    fn get_region_and_addr(&self, addr: Word) -> Result<(usize, Word, Word), Trap> {
+        let region_index = self.find_region(addr)?;
+        let start = self.regions[region_index].start;
 
-        (region_index, final_addr)
+        Ok((region_index, addr - start, start))
     }
 
-    pub fn get_word(&self, addr: Word) -> Word {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn get_word(&self, addr: Word) -> Result<Word, Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
-        self.regions[region_index].device.get_word(final_addr)
+        self.regions[region_index].device.get_word(final_addr).map_err(|trap| trap.offset(start))
     }
 
-    pub fn get_byte(&self, addr: Word) -> Byte {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn get_byte(&self, addr: Word) -> Result<Byte, Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
-        self.regions[region_index].device.get_byte(final_addr)
+        self.regions[region_index].device.get_byte(final_addr).map_err(|trap| trap.offset(start))
     }
 
-    pub fn get_range(&self, addr: Word, size: Word) -> Vec<Byte> {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
         self.regions[region_index]
             .device
             .get_range(final_addr, size)
+            .map_err(|trap| trap.offset(start))
     }
 
-    pub fn set_word(&mut self, addr: Word, value: Word) {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn set_word(&mut self, addr: Word, value: Word) -> Result<(), Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
         self.regions[region_index]
             .device
-            .set_word(final_addr, value);
+            .set_word(final_addr, value)
+            .map_err(|trap| trap.offset(start))
     }
 
-    pub fn set_byte(&mut self, addr: Word, value: Byte) {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn set_byte(&mut self, addr: Word, value: Byte) -> Result<(), Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
         self.regions[region_index]
             .device
-            .set_byte(final_addr, value);
+            .set_byte(final_addr, value)
+            .map_err(|trap| trap.offset(start))
     }
 
-    pub fn set_range(&mut self, addr: Word, values: Vec<Byte>) {
-        let (region_index, final_addr) = self.get_region_and_addr(addr);
+    pub fn set_range(&mut self, addr: Word, values: Vec<Byte>) -> Result<(), Trap> {
+        let (region_index, final_addr, start) = self.get_region_and_addr(addr)?;
 
         self.regions[region_index]
             .device
-            .set_range(final_addr, values);
+            .set_range(final_addr, values)
+            .map_err(|trap| trap.offset(start))
+    }
+
+    /// Advances every mapped device by one executed instruction
+    pub fn tick(&mut self) {
+        for region in self.regions.iter_mut() {
+            region.device.tick();
+        }
+    }
+
+    /// Flushes every mapped device, traps of the first failing one are returned after the rest
+    /// were flushed
+    pub fn flush(&mut self) -> Result<(), Trap> {
+        let mut result = Ok(());
+        for region in self.regions.iter_mut() {
+            let flushed = region.device.flush();
+            if result.is_ok() {
+                result = flushed;
+            }
+        }
+        result
     }
 
     pub fn map(&mut self, device: Box<dyn Device>, start: Word, end: Word) {
@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-use crate::device::Device;
+use crate::{device::Device, trap::Trap};
 
 pub type Word = u32;
 pub type HalfWord = u16;
@@ -58,56 +58,46 @@ impl Memory {
 
 #[allow(dead_code)]
 impl Device for Memory {
-    fn get_byte(&self, addr: Word) -> Byte {
-        *self
-            .data
-            .get(addr as usize)
-            .expect(format!("[MEMORY] get_byte: No such addr '0x{:08X}'", addr).as_str())
+    fn get_byte(&self, addr: Word) -> Result<Byte, Trap> {
+        self.data.get(addr as usize).copied().ok_or(Trap::InvalidAddress(addr))
     }
 
-    fn get_word(&self, addr: Word) -> Word {
+    fn get_word(&self, addr: Word) -> Result<Word, Trap> {
         let data = self
             .data
             .get(addr as usize..addr as usize + 4)
-            .expect(format!("[MEMORY] get_word: No such addr '0x{:08X}'", addr).as_str())
+            .ok_or(Trap::InvalidAddress(addr))?
             .try_into()
             .expect("[MEMORY] get_word: Oddly sized Word");
 
-        Word::from_le_bytes(data)
+        Ok(Word::from_le_bytes(data))
     }
 
-    fn get_range(&self, addr: Word, size: Word) -> Vec<Byte> {
+    fn get_range(&self, addr: Word, size: Word) -> Result<Vec<Byte>, Trap> {
         self.data
             .get(addr as usize..addr as usize + size as usize)
-            .expect(format!("[MEMORY] get_range: No such addr range '0x{:08X}-{:08X}'", addr, addr + size).as_str())
-            .to_vec()
+            .map(|data| data.to_vec())
+            .ok_or(Trap::InvalidAddress(addr))
     }
 
-    fn set_byte(&mut self, addr: Word, byte: Byte) {
-        if self.data.len() < addr as usize {
-            panic!("[MEMORY] set_byte: No such addr '0x{:08X}'", addr);
-        }
+    fn set_byte(&mut self, addr: Word, byte: Byte) -> Result<(), Trap> {
+        let target = self.data.get_mut(addr as usize).ok_or(Trap::InvalidAddress(addr))?;
+        *target = byte;
 
-        self.data[addr as usize] = byte;
+        Ok(())
     }
 
-    fn set_word(&mut self, addr: Word, word: Word) {
-        if self.data.len() < addr as usize + 3 {
-            panic!("[MEMORY] set_word: No such addr '0x{:08X}'", addr);
-        }
-
-        for (i, byte) in word.to_le_bytes().iter().enumerate() {
-            self.data[addr as usize + i] = *byte;
-        }
+    fn set_word(&mut self, addr: Word, word: Word) -> Result<(), Trap> {
+        self.set_range(addr, word.to_le_bytes().to_vec())
     }
 
-    fn set_range(&mut self, addr: Word, data: Vec<Byte>) {
-        if addr as usize + data.len() > self.data.len() {
-            panic!("[MEMORY] set_range: No such addr range '0x{:08X}-{:08X}'", addr, addr + data.len() as Word);
-        }
+    fn set_range(&mut self, addr: Word, data: Vec<Byte>) -> Result<(), Trap> {
+        let target = self
+            .data
+            .get_mut(addr as usize..addr as usize + data.len())
+            .ok_or(Trap::InvalidAddress(addr))?;
+        target.copy_from_slice(&data);
 
-        for (i, byte) in data.iter().enumerate() {
-            self.data[addr as usize + i] = *byte;
-        }
+        Ok(())
     }
 }
@@ -0,0 +1 @@
+mod trap_struct; pub use trap_struct::*;
\ No newline at end of file
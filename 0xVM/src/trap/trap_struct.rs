@@ -0,0 +1,98 @@
+use std::{error::Error, fmt};
+
+use crate::memory::{Byte, Word};
+
+/// A fault raised while executing an instruction or accessing a device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trap {
+    /// Opcode that isn't part of the instruction set
+    IllegalInstruction(Byte),
+    /// Address no device is mapped at
+    UnmappedAddress(Word),
+    /// Address inside a mapped region the device has no storage for
+    InvalidAddress(Word),
+    /// Register address outside of the register file
+    InvalidRegister(Word),
+    /// Access the device doesn't implement, with the device type and method name
+    Unsupported(&'static str, &'static str),
+    /// Access the device rejected, e.g. a UART failing to transmit
+    DeviceFault(String),
+    StackOverflow,
+    StackUnderflow,
+    DivisionByZero,
+    /// The CPU was run before `set_stack()`
+    StackNotSet,
+}
+
+impl Trap {
+    /// Memory or register address involved in the fault
+    pub fn address(&self) -> Option<Word> {
+        match self {
+            Trap::UnmappedAddress(addr) | Trap::InvalidAddress(addr) | Trap::InvalidRegister(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Moves device relative addresses to the region the device is mapped at
+    pub fn offset(self, start: Word) -> Self {
+        match self {
+            Trap::InvalidAddress(addr) => Trap::InvalidAddress(start.wrapping_add(addr)),
+            trap => trap,
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::IllegalInstruction(instr) => write!(f, "[CPU] No such instruction: '0x{:02X}'", instr),
+            Trap::UnmappedAddress(addr) => write!(f, "[MEMORY MAPPER] No such region: '0x{:08X}'", addr),
+            Trap::InvalidAddress(addr) => write!(f, "[MEMORY] No such addr '0x{:08X}'", addr),
+            Trap::InvalidRegister(addr) => write!(f, "[CPU] No such register: '0x{:08X}'", addr),
+            Trap::Unsupported(device, method) => {
+                write!(f, "[DEVICE] Device '{}' didn't implement '{}()'", device, method)
+            }
+            Trap::DeviceFault(message) => write!(f, "{}", message),
+            Trap::StackOverflow => write!(f, "[CPU] Stack overflow"),
+            Trap::StackUnderflow => write!(f, "[CPU] Stack underflow"),
+            Trap::DivisionByZero => write!(f, "[CPU] Division by zero"),
+            Trap::StackNotSet => write!(f, "[VM] Stack not set"),
+        }
+    }
+}
+
+impl Error for Trap {}
+
+/// A trap together with where it happened
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VmError {
+    pub trap: Trap,
+    /// Address of the instruction that trapped
+    pub pc: Word,
+    /// Opcode of the instruction, `None` if it couldn't be fetched
+    pub instruction: Option<Byte>,
+    /// Memory or register address involved, if any
+    pub address: Option<Word>,
+}
+
+impl VmError {
+    pub fn new(trap: Trap, pc: Word, instruction: Option<Byte>) -> Self {
+        VmError {
+            address: trap.address(),
+            trap,
+            pc,
+            instruction,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.instruction {
+            Some(instr) => write!(f, "{} (pc 0x{:08X}, instruction 0x{:02X})", self.trap, self.pc, instr),
+            None => write!(f, "{} (pc 0x{:08X})", self.trap, self.pc),
+        }
+    }
+}
+
+impl Error for VmError {}
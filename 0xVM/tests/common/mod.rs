@@ -0,0 +1,20 @@
+//! Helpers shared by the integration tests, each test crate uses some of them
+#![allow(dead_code)]
+
+use std::{env, path::PathBuf};
+
+use vm::memory::Byte;
+
+/// Encodes an instruction with its operands as little endian words
+pub fn instr(code: Byte, operands: &[u32]) -> Vec<Byte> {
+    let mut bytes = vec![code];
+    for operand in operands {
+        bytes.extend_from_slice(&operand.to_le_bytes());
+    }
+    bytes
+}
+
+/// Path of a file in the temp directory that other test processes don't use
+pub fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("0xvm-{}-{}", std::process::id(), name))
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use vm::machine::{ConfigError, DeviceConfig, MachineConfig, State};
+
+use common::instr;
+
+#[test]
+fn standard_machine_file() {
+    let config = MachineConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/machines/standard.toml")).unwrap();
+    assert_eq!(config, MachineConfig::standard());
+}
+
+#[test]
+fn build_from_config() {
+    let config = MachineConfig::from_toml(
+        "
+load_address = 0x1000
+memory_size = 0x1000
+
+[[device]]
+type = \"memory\"
+start = 0x0
+size = 0x100
+
+[[device]]
+type = \"keyboard\"
+start = 0x200
+input = \"none\"
+",
+    )
+    .unwrap();
+    assert_eq!(config.stack, None);
+    assert_eq!(config.devices[0].range(), Some((0, 0x100)));
+    assert_eq!(
+        config.devices[1],
+        DeviceConfig::Keyboard {
+            start: 0x200,
+            irq: None,
+            input: "none".to_string()
+        }
+    );
+
+    let image = [
+        instr(0x14, &[0x200, 0x00]), // MOVMR 0x200, r1
+        instr(0x13, &[0x00, 0x10]),  // MOVRM r1, 0x10
+        instr(0x15, &[0x99]),        // PUSH 0x99
+        instr(0xFF, &[]),            // HALT
+    ]
+    .concat();
+    let mut machine = config.builder().unwrap().image(image).build();
+    assert_eq!(machine.run().unwrap().state, State::Halted);
+    // the keyboard without input reports it closed, the stack sits at the end of main memory
+    assert_eq!(machine.read_word(0x10), Ok(2));
+    assert_eq!(machine.read_word(0x2000 - 4), Ok(0x99));
+}
+
+#[test]
+fn invalid_configs() {
+    let err = |source: &str| MachineConfig::from_toml(source).unwrap_err();
+
+    assert_eq!(
+        err("[[device]]\ntype = \"hard_drive\"\nstart = 0x404\nsector_size = 8\nsector_count = 1"),
+        ConfigError::Overlap(
+            "hard_drive at 0x404..0x424".to_string(),
+            "main memory at 0x408..0x10407".to_string()
+        )
+    );
+    assert_eq!(
+        err("[[device]]\ntype = \"timer\"\nstart = 0x0\n[[device]]\ntype = \"interrupt_controller\"\nstart = 0x8")
+            .to_string(),
+        "[CONFIG] interrupt_controller at 0x8..0x10 overlaps timer at 0x0..0x10"
+    );
+    assert_eq!(
+        err("stack = { address = 0x400, size = 0x10 }"),
+        ConfigError::Invalid("Stack of 0x10 bytes at 0x400 doesn't fit into main memory".to_string())
+    );
+    assert_eq!(
+        err("[[device]]\ntype = \"timer\"\nstart = 0x0\nirq = 3"),
+        ConfigError::Invalid("timer at 0x0..0x10 raises IRQ line 3 without an interrupt controller".to_string())
+    );
+
+    let controller = "[[device]]\ntype = \"interrupt_controller\"\nstart = 0x100\n";
+    assert_eq!(
+        err(&format!(
+            "{}[[device]]\ntype = \"timer\"\nstart = 0x0\nirq = 32",
+            controller
+        )),
+        ConfigError::Invalid("timer at 0x0..0x10 raises no such IRQ line: 32".to_string())
+    );
+    assert_eq!(
+        err(&format!(
+            "{}[[device]]\ntype = \"timer\"\nstart = 0x0\nirq = 1\n[[device]]\ntype = \"uart\"\nstart = 0x10\nirq = 1",
+            controller
+        )),
+        ConfigError::Invalid("timer at 0x0..0x10 and uart at 0x10..0x1C raise the same IRQ line 1".to_string())
+    );
+    assert_eq!(
+        err("[[device]]\ntype = \"screen\"\nstart = 0x10\nend = 0x10\nwidth = 1\nheight = 1"),
+        ConfigError::Invalid("screen at 0x10..0x10 has an empty mapping".to_string())
+    );
+
+    // typos are caught instead of silently falling back to defaults
+    assert!(matches!(err("memory_sise = 0x100"), ConfigError::Parse(_)));
+    assert!(matches!(
+        err("[[device]]\ntype = \"printer\"\nstart = 0x0"),
+        ConfigError::Parse(_)
+    ));
+    assert!(matches!(
+        err("[[device]]\ntype = \"timer\"\nstart = 0x0\nreload = 4"),
+        ConfigError::Parse(_)
+    ));
+
+    let config = MachineConfig::from_toml("[[device]]\ntype = \"uart\"\nstart = 0x0\nbinding = \"serial\"").unwrap();
+    assert!(matches!(config.builder(), Err(ConfigError::Connect(_))));
+    assert!(matches!(
+        MachineConfig::load("no/such/machine.toml"),
+        Err(ConfigError::Read(_))
+    ));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use vm::{
+    device::{
+        HardDrive, DMA_BUSY, DMA_DONE, DMA_ERROR, DMA_FIXED_SOURCE, DMA_INTERRUPT, DMA_START, HARD_DRIVE_READ,
+        HARD_DRIVE_READY,
+    },
+    machine::Machine,
+    memory::Word,
+};
+
+use common::instr;
+
+const PIC: Word = 0x100;
+const DMA: Word = 0x200;
+const DRIVE: Word = 0x300;
+
+/// Endless loop, the controller is programmed from the outside
+fn machine() -> Machine {
+    Machine::builder()
+        .interrupt_controller(PIC)
+        .dma(DMA, 3)
+        .hard_drive(DRIVE, HardDrive::from(b"sector 0sector 1".to_vec(), 8, 2))
+        .image(instr(0x01, &[0x408]))
+        .build()
+}
+
+fn program(machine: &mut Machine, source: Word, destination: Word, length: Word, burst: Word, control: Word) {
+    machine.write_word(DMA, source).unwrap();
+    machine.write_word(DMA + 0x4, destination).unwrap();
+    machine.write_word(DMA + 0x8, length).unwrap();
+    machine.write_word(DMA + 0x10, burst).unwrap();
+    machine.write_word(DMA + 0xC, control).unwrap();
+}
+
+#[test]
+fn memory_transfer() {
+    let mut machine = machine();
+    machine.load(0x1000, b"direct memory".to_vec()).unwrap();
+
+    // a burst of 4 bytes moves after every instruction
+    program(&mut machine, 0x1000, 0x2000, 13, 4, DMA_START | DMA_INTERRUPT);
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(DMA_BUSY));
+    machine.run_for(3).unwrap();
+    assert_eq!(machine.read_word(DMA + 0x18), Ok(1));
+    assert_eq!(machine.read_word(DMA), Ok(0x100C));
+    assert_eq!(machine.read_range(0x2000, 13).unwrap(), b"direct memor\0");
+    assert_eq!(machine.read_word(PIC), Ok(0));
+
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_range(0x2000, 13).unwrap(), b"direct memory");
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(DMA_DONE));
+    assert_eq!(machine.read_word(PIC), Ok(1 << 3));
+    machine.write_word(DMA + 0x14, DMA_DONE).unwrap();
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(0));
+
+    // without a burst the whole transfer moves at once, an access the regions reject ends it
+    program(&mut machine, 0x1000, 0x3000, 6, 0, DMA_START);
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_range(0x3000, 6).unwrap(), b"direct");
+    program(&mut machine, 0x1000, 0x20000, 8, 0, DMA_START);
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(DMA_ERROR));
+    assert_eq!(machine.read_word(DMA + 0x18), Ok(8));
+
+    // the controller's own registers reject the transfer instead of being overwritten by it
+    program(&mut machine, 0x1000, DMA, 8, 0, DMA_START);
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(DMA_ERROR));
+    assert_eq!(machine.read_word(DMA), Ok(0x1000));
+    assert_eq!(machine.read_word(DMA + 0x8), Ok(8));
+
+    // writing control without the start bit aborts the transfer
+    program(&mut machine, 0x1000, 0x4000, 8, 2, DMA_START);
+    machine.write_word(DMA + 0xC, 0).unwrap();
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_range(0x4000, 2).unwrap(), b"\0\0");
+}
+
+#[test]
+fn device_transfer() {
+    let mut machine = machine();
+    machine.write_word(DRIVE, 0).unwrap();
+    machine.write_word(DRIVE + 0x4, 2).unwrap();
+    machine.write_word(DRIVE + 0x8, HARD_DRIVE_READ).unwrap();
+
+    // the drive's data register stays the source while the sectors are read a burst at a time
+    program(&mut machine, DRIVE + 0x1C, 0x1000, 16, 8, DMA_START | DMA_FIXED_SOURCE);
+    machine.run_for(2).unwrap();
+    assert_eq!(machine.read_range(0x1000, 16).unwrap(), b"sector 0sector 1");
+    assert_eq!(machine.read_word(DMA), Ok(DRIVE + 0x1C));
+    assert_eq!(machine.read_word(DMA + 0x14), Ok(DMA_DONE));
+    assert_eq!(machine.read_word(DRIVE + 0xC), Ok(HARD_DRIVE_READY));
+}
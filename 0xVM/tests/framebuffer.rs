@@ -0,0 +1,128 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use vm::{
+    device::{
+        Framebuffer, PixelFormat, FRAMEBUFFER_DUMP, FRAMEBUFFER_PALETTE, FRAMEBUFFER_PIXELS, FRAMEBUFFER_PRESENT,
+    },
+    machine::{Machine, MachineConfig},
+    memory::Word,
+};
+
+use common::temp_path;
+
+const FRAMEBUFFER: Word = 0x20000;
+const PIXELS: Word = FRAMEBUFFER + FRAMEBUFFER_PIXELS;
+
+#[test]
+fn indexed_ppm_frames() {
+    let path = temp_path("frame-{frame}.ppm");
+    let framebuffer = Framebuffer::new(2, 2, PixelFormat::Indexed)
+        .output(path.to_str().unwrap())
+        .unwrap()
+        .every(2);
+    let mut machine = Machine::builder().framebuffer(FRAMEBUFFER, framebuffer).build();
+    assert_eq!(machine.read_word(FRAMEBUFFER), Ok(2));
+    assert_eq!(machine.read_word(FRAMEBUFFER + 0x8), Ok(1));
+    assert!(machine.read_word(FRAMEBUFFER + 0x14).is_err());
+    assert!(machine.write_word(FRAMEBUFFER, 4).is_err());
+
+    // palette entries are red, green, blue and alpha bytes, the default palette is grayscale
+    assert_eq!(
+        machine.read_word(FRAMEBUFFER + FRAMEBUFFER_PALETTE + 4 * 0x80),
+        Ok(0xFF808080)
+    );
+    machine
+        .write_word(FRAMEBUFFER + FRAMEBUFFER_PALETTE + 4, 0xFF0000FF)
+        .unwrap();
+    machine.load(PIXELS, vec![1, 0, 0x80, 0xFF]).unwrap();
+
+    machine.write_word(FRAMEBUFFER + 0xC, FRAMEBUFFER_PRESENT).unwrap();
+    let frame = |n: &str| PathBuf::from(path.to_str().unwrap().replace("{frame}", n));
+    assert!(!frame("000001").exists());
+    machine.write_word(FRAMEBUFFER + 0xC, FRAMEBUFFER_PRESENT).unwrap();
+    assert_eq!(machine.read_word(FRAMEBUFFER + 0x10), Ok(2));
+    assert_eq!(
+        fs::read(frame("000002")).unwrap(),
+        [
+            &b"P6\n2 2\n255\n"[..],
+            &[0xFF, 0, 0, 0, 0, 0, 0x80, 0x80, 0x80, 0xFF, 0xFF, 0xFF]
+        ]
+        .concat()
+    );
+    fs::remove_file(frame("000002")).unwrap();
+
+    // dumping on demand and flushing write the current frame without presenting it
+    machine.write_word(FRAMEBUFFER + 0xC, FRAMEBUFFER_DUMP).unwrap();
+    assert!(frame("000002").exists());
+    fs::remove_file(frame("000002")).unwrap();
+    machine.flush().unwrap();
+    assert!(frame("000002").exists());
+    fs::remove_file(frame("000002")).unwrap();
+}
+
+#[test]
+fn rgba_png_on_flush() {
+    let path = temp_path("framebuffer.png");
+    let framebuffer = Framebuffer::new(3, 1, PixelFormat::Rgba)
+        .output(path.to_str().unwrap())
+        .unwrap();
+    let mut machine = Machine::builder().framebuffer(FRAMEBUFFER, framebuffer).build();
+    assert_eq!(machine.read_word(FRAMEBUFFER + 0x8), Ok(4));
+    let pixels = vec![0xFF, 0, 0, 0xFF, 0, 0xFF, 0, 0x80, 0, 0, 0xFF, 0];
+    machine.load(PIXELS, pixels.clone()).unwrap();
+    assert!(machine.load(PIXELS + 4, vec![0; 12]).is_err());
+    drop(machine);
+    assert!(!path.exists());
+
+    let framebuffer = Framebuffer::new(3, 1, PixelFormat::Rgba)
+        .output(path.to_str().unwrap())
+        .unwrap();
+    let mut machine = Machine::builder().framebuffer(FRAMEBUFFER, framebuffer).build();
+    machine.load(PIXELS, pixels.clone()).unwrap();
+    machine.flush().unwrap();
+
+    let decoder = png::Decoder::new(fs::File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut image = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut image).unwrap();
+    assert_eq!((info.width, info.height, info.color_type), (3, 1, png::ColorType::Rgba));
+    assert_eq!(image, pixels);
+    fs::remove_file(&path).unwrap();
+
+    assert!(Framebuffer::new(1, 1, PixelFormat::Rgba).output("frame.bmp").is_err());
+}
+
+#[test]
+fn framebuffer_config() {
+    let framebuffer = |options: &str| {
+        format!(
+            "[[device]]\ntype = \"framebuffer\"\nstart = 0x20000\nwidth = 320\nheight = 200\n{}",
+            options
+        )
+    };
+    let config = MachineConfig::from_toml(&framebuffer("format = \"rgba\"")).unwrap();
+    assert_eq!(
+        config.devices[0].range(),
+        Some((0x20000, 0x20000 + FRAMEBUFFER_PIXELS + 320 * 200 * 4))
+    );
+    let machine = config.builder().unwrap().build();
+    assert_eq!(machine.read_word(0x20004), Ok(200));
+
+    let err = |options: &str| MachineConfig::from_toml(&framebuffer(options)).unwrap_err().to_string();
+    assert_eq!(
+        err("output = \"frame.gif\""),
+        "[CONFIG] framebuffer at 0x20000..0x2FE20 writes frames to neither .ppm nor .png"
+    );
+    assert_eq!(
+        err("every = 10"),
+        "[CONFIG] framebuffer at 0x20000..0x2FE20 writes every n-th frame without an output"
+    );
+    assert_eq!(
+        MachineConfig::from_toml("[[device]]\ntype = \"framebuffer\"\nstart = 0x20000\nwidth = 0\nheight = 200")
+            .unwrap_err()
+            .to_string(),
+        "[CONFIG] framebuffer at 0x20000..0x20420 has no pixels"
+    );
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::fs;
+
+use vm::{
+    device::{
+        HardDrive, ImageMode, HARD_DRIVE_DATA, HARD_DRIVE_ERROR, HARD_DRIVE_ERR_COMMAND, HARD_DRIVE_ERR_DATA,
+        HARD_DRIVE_ERR_READ_ONLY, HARD_DRIVE_ERR_SECTOR, HARD_DRIVE_IDENTIFY, HARD_DRIVE_IMAGE, HARD_DRIVE_READ,
+        HARD_DRIVE_READY, HARD_DRIVE_READ_ONLY, HARD_DRIVE_WRITE,
+    },
+    machine::{ConfigError, Machine, MachineConfig},
+    memory::Word,
+};
+
+use common::temp_path;
+
+const DRIVE: Word = 0x100;
+const STATUS: Word = DRIVE + 0xC;
+const ERROR: Word = DRIVE + 0x10;
+const DATA: Word = DRIVE + 0x1C;
+
+fn with_drive(drive: HardDrive) -> Machine {
+    Machine::builder().hard_drive(DRIVE, drive).build()
+}
+
+/// Runs the command on the sectors, returns the error code
+fn command(machine: &mut Machine, command: Word, sector: Word, count: Word) -> Word {
+    machine.write_word(DRIVE, sector).unwrap();
+    machine.write_word(DRIVE + 0x4, count).unwrap();
+    machine.write_word(DRIVE + 0x8, command).unwrap();
+    machine.read_word(ERROR).unwrap()
+}
+
+/// Writes 4 byte sectors from sector on like a program copying them into the data register
+fn write_sectors(machine: &mut Machine, sector: Word, data: &[u8]) -> Word {
+    match command(machine, HARD_DRIVE_WRITE, sector, data.len() as Word / 4) {
+        0 => {
+            machine.load(DATA, data.to_vec()).unwrap();
+            machine.read_word(ERROR).unwrap()
+        }
+        error => error,
+    }
+}
+
+fn read_sectors(machine: &mut Machine, sector: Word, count: Word) -> Vec<u8> {
+    assert_eq!(command(machine, HARD_DRIVE_READ, sector, count), 0);
+    machine.read_range(DATA, count * 4).unwrap()
+}
+
+#[test]
+fn controller_registers() {
+    let mut machine = with_drive(HardDrive::new(4, 4));
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY));
+    assert_eq!(machine.read_word(DRIVE + 0x14), Ok(4));
+    assert_eq!(machine.read_word(DRIVE + 0x18), Ok(4));
+    assert!(machine.write_word(DRIVE + 0x18, 8).is_err());
+
+    assert_eq!(command(&mut machine, HARD_DRIVE_IDENTIFY, 0, 0), 0);
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_DATA));
+    assert_eq!(machine.read_word(DATA), Ok(4));
+    assert_eq!(machine.read_word(DATA), Ok(4));
+    assert_eq!(machine.read_word(DATA), Ok(0));
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY));
+
+    // a transfer spans sectors and the data register takes words as well as ranges
+    assert_eq!(command(&mut machine, HARD_DRIVE_WRITE, 1, 2), 0);
+    machine.write_word(DATA, 0x4D567830).unwrap();
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_DATA));
+    machine.load(DATA, b"disk".to_vec()).unwrap();
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY));
+    assert_eq!(read_sectors(&mut machine, 0, 3), b"\0\0\0\x000xVMdisk");
+    assert_eq!(command(&mut machine, HARD_DRIVE_READ, 2, 1), 0);
+    assert_eq!(machine.read_word(DATA), Ok(u32::from_le_bytes(*b"disk")));
+
+    // errors end up in the status and error registers instead of trapping
+    assert_eq!(command(&mut machine, 9, 0, 1), HARD_DRIVE_ERR_COMMAND);
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY | HARD_DRIVE_ERROR));
+    machine.write_word(ERROR, 0).unwrap();
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY));
+    assert_eq!(command(&mut machine, HARD_DRIVE_READ, 3, 2), HARD_DRIVE_ERR_SECTOR);
+    assert_eq!(command(&mut machine, HARD_DRIVE_WRITE, 0, 0), HARD_DRIVE_ERR_SECTOR);
+    assert_eq!(
+        command(&mut machine, HARD_DRIVE_READ, Word::MAX, 2),
+        HARD_DRIVE_ERR_SECTOR
+    );
+    // the first error is kept until it is cleared
+    assert_eq!(machine.read_word(DATA), Ok(0));
+    assert_eq!(machine.read_word(ERROR), Ok(HARD_DRIVE_ERR_SECTOR));
+    machine.write_word(ERROR, 0).unwrap();
+    assert_eq!(machine.read_word(DATA), Ok(0));
+    assert_eq!(machine.read_word(ERROR), Ok(HARD_DRIVE_ERR_DATA));
+    assert_eq!(write_sectors(&mut machine, 0, b"0xVM"), 0);
+    machine.write_word(DRIVE + 0x8, HARD_DRIVE_WRITE).unwrap();
+    machine.load(DATA, b"too long".to_vec()).unwrap();
+    assert_eq!(machine.read_word(ERROR), Ok(HARD_DRIVE_ERR_DATA));
+    assert_eq!(machine.read_word(STATUS), Ok(HARD_DRIVE_READY | HARD_DRIVE_ERROR));
+    assert_eq!(read_sectors(&mut machine, 0, 1), b"0xVM");
+
+    // a range read stops at the end of the transfer instead of padding up to the size the guest asked for
+    assert_eq!(command(&mut machine, HARD_DRIVE_READ, 0, 1), 0);
+    assert_eq!(machine.read_range(DATA, Word::MAX), Ok(b"0xVM".to_vec()));
+    assert_eq!(machine.read_word(ERROR), Ok(HARD_DRIVE_ERR_DATA));
+    assert_eq!(machine.read_range(DATA, Word::MAX), Ok(Vec::new()));
+}
+
+#[test]
+fn read_write_image() {
+    let path = temp_path("disk.img");
+    let _ = fs::remove_file(&path);
+
+    let mut machine = with_drive(HardDrive::open(&path, 4, 4, ImageMode::Create).unwrap());
+    assert_eq!(write_sectors(&mut machine, 2, b"0xVM"), 0);
+    machine.flush().unwrap();
+    assert_eq!(fs::read(&path).unwrap(), [&[0; 8][..], b"0xVM", &[0; 4]].concat());
+    drop(machine);
+
+    // an existing image isn't overwritten by create and must match the geometry
+    assert!(HardDrive::open(&path, 4, 4, ImageMode::Create).is_err());
+    assert_eq!(
+        HardDrive::open(&path, 4, 8, ImageMode::ReadWrite)
+            .err()
+            .unwrap()
+            .to_string(),
+        "disk image is 16 bytes but 8 sectors of 4 bytes take 32"
+    );
+
+    // sectors are written back when the drive is dropped
+    let mut machine = with_drive(HardDrive::open(&path, 4, 4, ImageMode::ReadWrite).unwrap());
+    assert_eq!(read_sectors(&mut machine, 2, 1), b"0xVM");
+    assert_eq!(write_sectors(&mut machine, 0, b"disk"), 0);
+    drop(machine);
+    assert_eq!(&fs::read(&path).unwrap()[..4], b"disk");
+
+    let mut machine = with_drive(HardDrive::open(&path, 4, 4, ImageMode::ReadOnly).unwrap());
+    assert_eq!(read_sectors(&mut machine, 0, 1), b"disk");
+    assert_eq!(write_sectors(&mut machine, 1, b"nope"), HARD_DRIVE_ERR_READ_ONLY);
+    assert_eq!(command(&mut machine, HARD_DRIVE_IDENTIFY, 0, 0), 0);
+    assert_eq!(
+        machine.read_range(DATA, 12).unwrap(),
+        [4, 4, HARD_DRIVE_IMAGE | HARD_DRIVE_READ_ONLY]
+            .iter()
+            .flat_map(|word: &Word| word.to_le_bytes())
+            .collect::<Vec<_>>()
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn copy_on_write_image() {
+    let path = temp_path("base.img");
+    let overlay = temp_path("base.cow");
+    let _ = fs::remove_file(&overlay);
+    fs::write(&path, b"aaaabbbbcccc").unwrap();
+
+    let open = || HardDrive::open(&path, 4, 3, ImageMode::CopyOnWrite(overlay.clone())).unwrap();
+    let mut machine = with_drive(open());
+    assert_eq!(write_sectors(&mut machine, 1, b"BBBB"), 0);
+    drop(machine);
+    assert_eq!(fs::read(&path).unwrap(), b"aaaabbbbcccc");
+    assert_eq!(fs::read(&overlay).unwrap(), b"\x01\0\0\0BBBB");
+
+    // the overlay is read back and keeps its sectors when more are written
+    let mut machine = with_drive(open());
+    assert_eq!(read_sectors(&mut machine, 1, 1), b"BBBB");
+    assert_eq!(write_sectors(&mut machine, 0, b"AAAA"), 0);
+    drop(machine);
+    assert_eq!(fs::read(&overlay).unwrap(), b"\0\0\0\0AAAA\x01\0\0\0BBBB");
+    assert_eq!(fs::read(&path).unwrap(), b"aaaabbbbcccc");
+
+    fs::write(&overlay, b"\x07\0\0\0CCCC").unwrap();
+    assert!(HardDrive::open(&path, 4, 3, ImageMode::CopyOnWrite(overlay.clone())).is_err());
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&overlay).unwrap();
+}
+
+#[test]
+fn image_config() {
+    let path = temp_path("config.img");
+    let _ = fs::remove_file(&path);
+    let drive = |options: &str| {
+        format!(
+            "[[device]]\ntype = \"hard_drive\"\nstart = 0x100\nsector_size = 4\nsector_count = 2\n{}",
+            options
+        )
+    };
+
+    let config = MachineConfig::from_toml(&drive(&format!("image = {:?}\nmode = \"create\"", path))).unwrap();
+    let mut machine = config.builder().unwrap().build();
+    assert_eq!(write_sectors(&mut machine, 1, b"toml"), 0);
+    drop(machine);
+    assert_eq!(fs::read(&path).unwrap(), b"\0\0\0\0toml");
+    assert!(matches!(config.builder(), Err(ConfigError::Connect(_))));
+    fs::remove_file(&path).unwrap();
+
+    let err = |options: &str| MachineConfig::from_toml(&drive(options)).unwrap_err().to_string();
+    assert_eq!(
+        err("mode = \"read_only\""),
+        "[CONFIG] hard_drive at 0x100..0x120 sets a mode or overlay without an image"
+    );
+    assert_eq!(
+        err("image = \"disk.img\"\nmode = \"copy_on_write\""),
+        "[CONFIG] hard_drive at 0x100..0x120 needs an overlay for copy_on_write"
+    );
+    assert_eq!(
+        err("image = \"disk.img\"\noverlay = \"disk.cow\""),
+        "[CONFIG] hard_drive at 0x100..0x120 has an overlay but isn't copy_on_write"
+    );
+}
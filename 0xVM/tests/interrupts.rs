@@ -0,0 +1,125 @@
+mod common;
+
+use vm::{
+    cpu::FLAG_INTERRUPTS,
+    machine::Machine,
+    memory::Word,
+};
+
+use common::instr;
+
+const IVT: Word = 0x2000;
+const HANDLER: Word = 0x3000;
+
+#[test]
+fn software_interrupt() {
+    let image = [
+        instr(0x10, &[IVT, 0x34]), // MOVR 0x2000, ivt
+        instr(0x10, &[7, 0x00]),   // MOVR 7, r1
+        instr(0x08, &[]),          // EI
+        instr(0x06, &[2]),         // INT 0x2
+        instr(0xFF, &[]),          // HALT
+    ]
+    .concat();
+    let handler = [
+        instr(0x10, &[0x55, 0x00]),   // MOVR 0x55, r1
+        instr(0x13, &[0x00, 0x1000]), // MOVRM r1, 0x1000
+        instr(0x13, &[0x28, 0x1004]), // MOVRM sr, 0x1004
+        instr(0x07, &[]),             // IRET
+    ]
+    .concat();
+    let mut machine = Machine::builder().image(image).build();
+    machine.load(HANDLER, handler).unwrap();
+    machine.write_word(IVT + 2 * 4, HANDLER).unwrap();
+    let sp = machine.register("sp").unwrap();
+
+    machine.run().unwrap();
+    // the handler ran with interrupts disabled
+    assert_eq!(machine.read_word(0x1000), Ok(0x55));
+    assert_eq!(machine.read_word(0x1004).unwrap() & (1 << FLAG_INTERRUPTS), 0);
+    // IRET restored the registers and sr of the interrupted code
+    assert_eq!(machine.register("r1"), Some(7));
+    assert_eq!(machine.register("sr"), Some(1 << FLAG_INTERRUPTS));
+    assert_eq!(machine.register("sp"), Some(sp));
+}
+
+#[test]
+fn hardware_interrupt() {
+    let image = [
+        instr(0x10, &[IVT, 0x34]), // MOVR 0x2000, ivt
+        instr(0x08, &[]),          // EI
+        instr(0x2A, &[0x04]),      // loop: INC r2
+        instr(0x01, &[0x412]),     // JMP loop
+    ]
+    .concat();
+    let handler = [
+        instr(0x2A, &[0x08]),         // INC r3
+        instr(0x13, &[0x08, 0x1000]), // MOVRM r3, 0x1000
+        instr(0x07, &[]),             // IRET
+    ]
+    .concat();
+    let builder = Machine::builder().interrupt_controller(0x100).image(image);
+    let line = builder.irq_line(3);
+    let mut machine = builder.build();
+    machine.load(HANDLER, handler).unwrap();
+    machine.write_word(IVT + 3 * 4, HANDLER).unwrap();
+
+    // the line stays pending while it is masked out
+    line.raise();
+    machine.run_for(4).unwrap();
+    assert_eq!(machine.read_word(0x1000), Ok(0));
+    assert_eq!(machine.read_word(0x100), Ok(1 << 3));
+
+    // unmasking it enters the handler in place of the next instruction
+    machine.write_word(0x104, 1 << 3).unwrap();
+    machine.step().unwrap();
+    assert_eq!(machine.pc(), HANDLER);
+    assert_eq!(machine.read_word(0x100), Ok(0));
+
+    machine.run_for(3).unwrap();
+    assert_eq!(machine.read_word(0x1000), Ok(1));
+    assert_eq!(machine.register("r3"), Some(0));
+    assert_eq!(machine.pc(), 0x412);
+
+    // nothing is taken while interrupts are disabled, writing the pending register acknowledges
+    machine.set_register("sr", 0);
+    line.raise();
+    machine.run_for(4).unwrap();
+    assert_eq!(machine.read_word(0x1000), Ok(1));
+    assert_eq!(machine.read_word(0x100), Ok(1 << 3));
+    machine.write_word(0x100, 1 << 3).unwrap();
+    assert_eq!(machine.read_word(0x100), Ok(0));
+}
+
+#[test]
+fn accumulator_preserved() {
+    let image = [
+        instr(0x10, &[IVT, 0x34]),  // MOVR 0x2000, ivt
+        instr(0x08, &[]),           // EI
+        instr(0x10, &[0x40, 0x00]), // MOVR 0x40, r1
+        instr(0x20, &[2, 0x00]),    // ADD 2, r1
+        instr(0x12, &[0x24, 0x04]), // MOVRR acc, r2
+        instr(0xFF, &[]),           // HALT
+    ]
+    .concat();
+    let handler = [
+        instr(0x20, &[0x10, 0x08]),   // ADD 0x10, r3
+        instr(0x13, &[0x24, 0x1000]), // MOVRM acc, 0x1000
+        instr(0x07, &[]),             // IRET
+    ]
+    .concat();
+    let builder = Machine::builder().interrupt_controller(0x100).image(image);
+    let line = builder.irq_line(1);
+    let mut machine = builder.build();
+    machine.load(HANDLER, handler).unwrap();
+    machine.write_word(IVT + 4, HANDLER).unwrap();
+    machine.write_word(0x104, 1 << 1).unwrap();
+
+    // the interrupt is taken right after the ADD, before its result is used
+    machine.run_for(4).unwrap();
+    assert_eq!(machine.register("acc"), Some(0x42));
+    line.raise();
+    machine.run().unwrap();
+    assert_eq!(machine.read_word(0x1000), Ok(0x10));
+    assert_eq!(machine.register("r2"), Some(0x42));
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::io::Cursor;
+
+use vm::{
+    device::{Keyboard, KEYBOARD_CLOSED, KEYBOARD_FIFO_SIZE, KEYBOARD_READY},
+    machine::Machine,
+    memory::Word,
+};
+
+use common::instr;
+
+const KEYBOARD: Word = 0x200;
+
+/// Polls the keyboard until its input is closed, summing the bytes in r5 and counting them in r6
+fn sum_input(keyboard: Keyboard) -> Machine {
+    let image = [
+        instr(0x14, &[KEYBOARD, 0x00]),               // loop: MOVMR 0x200, r1
+        instr(0x34, &[0x00, KEYBOARD_CLOSED, 0x450]), // BREQRW r1, 2, done
+        instr(0x34, &[0x00, 0, 0x408]),               // BREQRW r1, 0, loop
+        instr(0x14, &[KEYBOARD + 0x4, 0x04]),         // MOVMR 0x204, r2
+        instr(0x21, &[0x04, 0x10]),                   // ADDR r2, r5
+        instr(0x12, &[0x24, 0x10]),                   // MOVRR acc, r5
+        instr(0x2A, &[0x14]),                         // INC r6
+        instr(0x01, &[0x408]),                        // JMP loop
+        instr(0xFF, &[]),                             // done: HALT
+    ]
+    .concat();
+    let mut machine = Machine::builder()
+        .interrupt_controller(0x100)
+        .keyboard(KEYBOARD, 1, keyboard)
+        .image(image)
+        .build();
+
+    machine.run().unwrap();
+    machine
+}
+
+#[test]
+fn scripted_input() {
+    let machine = sum_input(Keyboard::scripted(b"hi!\n"));
+    assert_eq!(machine.register("r5"), Some((b'h' + b'i' + b'!' + b'\n') as Word));
+    assert_eq!(machine.register("r6"), Some(4));
+}
+
+#[test]
+fn piped_input() {
+    let input = vec![b'x'; 1000];
+    let machine = sum_input(Keyboard::reader(Cursor::new(input)));
+    assert_eq!(machine.register("r5"), Some(b'x' as Word * 1000));
+    assert_eq!(machine.register("r6"), Some(1000));
+}
+
+#[test]
+fn fifo_and_irq() {
+    let mut machine = Machine::builder()
+        .interrupt_controller(0x100)
+        .keyboard(KEYBOARD, 1, Keyboard::scripted(&[b'a'; 100]))
+        .image(instr(0x01, &[0x408])) // endless loop
+        .build();
+    assert_eq!(machine.read_word(KEYBOARD), Ok(0));
+
+    // input arrives as the devices are ticked and raises the IRQ line
+    machine.step().unwrap();
+    assert_eq!(machine.read_word(KEYBOARD), Ok(KEYBOARD_READY));
+    assert_eq!(machine.read_word(0x100), Ok(1 << 1));
+
+    // the FIFO holds only so many bytes, the rest of the input waits until there is room
+    for _ in 0..KEYBOARD_FIFO_SIZE {
+        assert_eq!(machine.read_word(KEYBOARD + 0x4), Ok(b'a' as Word));
+    }
+    assert_eq!(machine.read_word(KEYBOARD), Ok(0));
+    assert_eq!(machine.read_word(KEYBOARD + 0x4), Ok(0));
+
+    machine.step().unwrap();
+    for _ in KEYBOARD_FIFO_SIZE..100 {
+        assert_eq!(machine.read_word(KEYBOARD + 0x4), Ok(b'a' as Word));
+    }
+    machine.step().unwrap();
+    assert_eq!(machine.read_word(KEYBOARD), Ok(KEYBOARD_CLOSED));
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use vm::{
+    machine::{Machine, RunResult, State},
+    memory::{Byte, Memory},
+    trap::{Trap, VmError},
+};
+
+use common::instr;
+
+#[test]
+fn run_and_inspect() {
+    let image = [
+        instr(0x10, &[0x2A, 0x00]),   // MOVR 0x2A, r1
+        instr(0x2A, &[0x00]),         // INC r1
+        instr(0x13, &[0x00, 0x1000]), // MOVRM r1, 0x1000
+        instr(0xFF, &[]),             // HALT
+    ]
+    .concat();
+    let mut machine = Machine::builder().image(image).build();
+
+    assert_eq!(machine.pc(), 0x408);
+    assert_eq!(machine.step(), Ok(State::Running));
+    assert_eq!(machine.register("r1"), Some(0x2A));
+
+    assert_eq!(
+        machine.run(),
+        Ok(RunResult {
+            steps: 3,
+            state: State::Halted
+        })
+    );
+    assert_eq!(machine.steps(), 4);
+    assert_eq!(machine.register("r1"), Some(0x2B));
+    assert_eq!(machine.read_word(0x1000), Ok(0x2B));
+    assert_eq!(machine.registers()[0], ("r1", 0x2B));
+    assert_eq!(machine.register("r9"), None);
+}
+
+#[test]
+fn devices_and_layout() {
+    let image = [
+        instr(0x10, &[0x1234, 0x04]), // MOVR 0x1234, r2
+        instr(0x13, &[0x04, 0x10]),   // MOVRM r2, 0x10
+        instr(0x15, &[0x99]),         // PUSH 0x99
+        instr(0x01, &[0x2000]),       // JMP 0x2000
+    ]
+    .concat();
+    let mut machine = Machine::builder()
+        .device(Box::new(Memory::new(0x100)), 0, 0x100)
+        .load_address(0x1000)
+        .memory_size(0x2000)
+        .image(image)
+        .build();
+    // endless loop loaded next to the program
+    machine.load(0x2000, instr(0x01, &[0x2000])).unwrap();
+
+    assert_eq!(machine.register("sp"), Some(0x3000 - 4));
+    assert_eq!(
+        machine.run_for(10),
+        Ok(RunResult {
+            steps: 10,
+            state: State::Running
+        })
+    );
+    assert_eq!(machine.pc(), 0x2000);
+    assert_eq!(machine.read_word(0x10), Ok(0x1234));
+    assert_eq!(machine.read_word(0x3000 - 4), Ok(0x99));
+
+    machine.jump(0x2005);
+    machine.load(0x2005, instr(0xFF, &[])).unwrap();
+    assert_eq!(machine.run().unwrap().state, State::Halted);
+}
+
+#[test]
+fn traps() {
+    let run = |image: Vec<Byte>| Machine::builder().memory_size(0x100).image(image).build().run();
+
+    let err = run([instr(0x10, &[0, 0x08]), instr(0x27, &[0x08, 0])].concat()).unwrap_err(); // MOVR 0, r3; DIV r3, 0
+    assert_eq!(err, VmError::new(Trap::DivisionByZero, 0x411, Some(0x27)));
+    assert_eq!(
+        err.to_string(),
+        "[CPU] Division by zero (pc 0x00000411, instruction 0x27)"
+    );
+
+    let err = run(instr(0x05, &[0x00])).unwrap_err(); // POP r1
+    assert_eq!(err.trap, Trap::StackUnderflow);
+
+    // registers pointing at the end of the address space trap instead of overflowing
+    let err = run(instr(0x01, &[0xFFFFFFFF])).unwrap_err(); // JMP 0xFFFFFFFF
+    assert_eq!(err.trap, Trap::UnmappedAddress(0xFFFFFFFF));
+    // MOVR 0xFFFFFFFE, sp; POP r1
+    let err = run([instr(0x10, &[0xFFFFFFFE, 0x2C]), instr(0x05, &[0x00])].concat()).unwrap_err();
+    assert_eq!(err.trap, Trap::StackUnderflow);
+    let err = run([instr(0x10, &[2, 0x2C]), instr(0x16, &[0x00])].concat()).unwrap_err(); // MOVR 2, sp; PUSHR r1
+    assert_eq!(err.trap, Trap::StackOverflow);
+
+    let err = run(instr(0x10, &[1, 0x1000])).unwrap_err(); // MOVR 1, <no register>
+    assert_eq!((err.trap, err.address), (Trap::InvalidRegister(0x1000), Some(0x1000)));
+
+    let err = run(instr(0x11, &[1, 0x2000])).unwrap_err(); // MOVM 1, 0x2000
+    assert_eq!(err.trap, Trap::UnmappedAddress(0x2000));
+
+    // a word starting on the last byte of memory runs past its end
+    let err = run(instr(0x11, &[1, 0x507])).unwrap_err(); // MOVM 1, 0x507
+    assert_eq!(err.trap, Trap::InvalidAddress(0x507));
+
+    let err = run(vec![0xEE]).unwrap_err();
+    assert_eq!(err.trap, Trap::IllegalInstruction(0xEE));
+    assert_eq!(err.instruction, Some(0xEE));
+
+    // the machine can be inspected and resumed after a trap
+    let mut machine = Machine::builder().image(instr(0x05, &[0x00])).build();
+    assert!(machine.step().is_err());
+    machine.jump(0x408);
+    machine.load(0x408, instr(0xFF, &[])).unwrap();
+    assert_eq!(machine.step(), Ok(State::Halted));
+}
+
+#[test]
+fn calls_restore_frame() {
+    let image = [
+        instr(0x12, &[0x30, 0x04]), // MOVRR fp, r2
+        instr(0x15, &[0x5]),        // PUSH 0x5
+        instr(0x15, &[1]),          // PUSH 1
+        instr(0x02, &[0x500]),      // CALL 0x500
+        instr(0x15, &[0]),          // PUSH 0
+        instr(0x02, &[0x500]),      // CALL 0x500
+        instr(0x12, &[0x30, 0x00]), // MOVRR fp, r1
+        instr(0xFF, &[]),           // HALT
+    ]
+    .concat();
+    let subroutine = [
+        instr(0x15, &[0x7]), // PUSH 0x7
+        instr(0x04, &[]),    // RET
+    ]
+    .concat();
+    let mut machine = Machine::builder().image(image).build();
+    machine.load(0x500, subroutine).unwrap();
+    let sp = machine.register("sp").unwrap();
+
+    machine.run().unwrap();
+    // every RET leaves fp and sp where they were before the arguments of its CALL were pushed
+    assert_eq!(machine.register("r1"), machine.register("r2"));
+    assert_eq!(machine.register("fp"), machine.register("r2"));
+    assert_eq!(machine.register("sp"), Some(sp));
+
+    // a RET through a frame pointer that doesn't point at a frame pops zeroes and traps
+    let image = [
+        instr(0x10, &[0xFE00, 0x30]), // MOVR 0xFE00, fp
+        instr(0x04, &[]),             // RET
+    ]
+    .concat();
+    let err = Machine::builder().image(image).build().run().unwrap_err();
+    assert_eq!(err.trap, Trap::StackUnderflow);
+}
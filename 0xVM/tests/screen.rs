@@ -0,0 +1,241 @@
+mod common;
+
+use std::{collections::HashSet, io};
+
+use vm::{
+    device::{
+        screen_escape, Cast, CastClock, Cell, Screen, VirtualTerminal, SCREEN_ATTRIBUTE_BACKGROUND,
+        SCREEN_ATTRIBUTE_BOLD, SCREEN_ATTRIBUTE_FOREGROUND, SCREEN_BACKGROUND, SCREEN_BACKGROUND_256, SCREEN_BOLD,
+        SCREEN_CLEAR, SCREEN_CLEAR_LINE_END, SCREEN_CODES, SCREEN_FOREGROUND, SCREEN_FOREGROUND_256,
+        SCREEN_HIDE_CURSOR, SCREEN_MOVE_CURSOR, SCREEN_NO_SCROLL_REGION, SCREEN_RESET, SCREEN_SCROLL_REGION,
+        SCREEN_SCROLL_UP,
+    },
+    machine::{Machine, MachineConfig},
+    memory::Word,
+};
+
+use common::{instr, temp_path};
+
+fn escape(code: u32) -> String {
+    screen_escape(code).unwrap()
+}
+
+#[test]
+fn codes() {
+    let names: HashSet<_> = SCREEN_CODES.iter().map(|(name, _)| name).collect();
+    let values: HashSet<_> = SCREEN_CODES.iter().map(|(_, value)| value).collect();
+    assert_eq!((names.len(), values.len()), (SCREEN_CODES.len(), SCREEN_CODES.len()));
+    assert!(SCREEN_CODES.iter().all(|(_, code)| screen_escape(*code).is_some()));
+
+    assert_eq!(escape(SCREEN_CLEAR), "\x1b[2J");
+    assert_eq!(escape(SCREEN_HIDE_CURSOR), "\x1b[?25l");
+    assert_eq!(escape(SCREEN_NO_SCROLL_REGION), "\x1b[r");
+    // words drawn at a cell are little endian UTF-8, so their last byte never starts a code
+    assert_eq!(screen_escape(u32::from_le_bytes(*b"ab\0\0")), None);
+    assert_eq!(screen_escape(0xF4_80_80_95), None);
+}
+
+#[test]
+fn parameters() {
+    assert_eq!(escape(SCREEN_FOREGROUND | 1), "\x1b[31m");
+    assert_eq!(escape(SCREEN_FOREGROUND | 9), "\x1b[91m");
+    assert_eq!(escape(SCREEN_BACKGROUND | 4), "\x1b[44m");
+    assert_eq!(escape(SCREEN_BACKGROUND | 15), "\x1b[107m");
+    assert_eq!(escape(SCREEN_FOREGROUND_256 | 208), "\x1b[38;5;208m");
+    assert_eq!(escape(SCREEN_BACKGROUND_256 | 17), "\x1b[48;5;17m");
+
+    // the cursor moves to column 3 and row 1 of the cells, every cell is two terminal columns wide
+    assert_eq!(escape(SCREEN_MOVE_CURSOR | (3 << 8) | 1), "\x1b[2;8H");
+    assert_eq!(escape(SCREEN_SCROLL_REGION | (2 << 8) | 10), "\x1b[3;11r");
+}
+
+/// Draws the characters of the text from the cell on
+fn draw(machine: &mut Machine, cell: Word, text: &str) {
+    for (n, char) in text.chars().enumerate() {
+        let mut bytes = [0; 4];
+        char.encode_utf8(&mut bytes);
+        machine
+            .write_word(cell + n as Word, Word::from_le_bytes(bytes))
+            .unwrap();
+    }
+}
+
+#[test]
+fn cell_buffer() {
+    let screen = Screen::new(4, 3).headless();
+    let buffer = screen.buffer();
+    let mut machine = Machine::builder().device(Box::new(screen), 0, 0x400).build();
+    draw(&mut machine, 0, "hi");
+    machine.write_word(0, SCREEN_BOLD).unwrap();
+    machine.write_word(0, SCREEN_FOREGROUND | 2).unwrap();
+    machine.write_word(0, SCREEN_BACKGROUND_256 | 200).unwrap();
+    draw(&mut machine, 4, "ünd");
+    machine.write_word(0, SCREEN_RESET).unwrap();
+    draw(&mut machine, 9, "x");
+    assert_eq!(buffer.text(), "hi\nünd\n x");
+
+    // characters are read back at the cell, attributes behind the cells
+    let attributes = SCREEN_ATTRIBUTE_BOLD | SCREEN_ATTRIBUTE_FOREGROUND | SCREEN_ATTRIBUTE_BACKGROUND | 200 << 8 | 2;
+    assert_eq!(machine.read_word(1), Ok('i' as Word));
+    assert_eq!(machine.read_word(12 + 4), Ok(attributes));
+    assert_eq!(machine.read_word(12 + 9), Ok(0));
+    assert_eq!(machine.read_range(0, 4).unwrap(), b"hi\0\0");
+    assert_eq!(
+        buffer.cell(1, 1),
+        Some(Cell {
+            character: 'n' as Word,
+            attributes
+        })
+    );
+    assert!(machine.read_word(25).is_err());
+    assert!(machine.write_word(12, 'a' as Word).is_err());
+
+    // clearing to the end of the line starts behind the last drawn cell
+    machine.write_word(0, SCREEN_MOVE_CURSOR | (1 << 8) | 1).unwrap();
+    machine.write_word(0, SCREEN_CLEAR_LINE_END).unwrap();
+    machine.write_word(0, SCREEN_SCROLL_UP).unwrap();
+    assert_eq!(buffer.text(), "ü\n x\n");
+    machine.write_word(0, SCREEN_CLEAR).unwrap();
+    assert_eq!(buffer.text(), "\n\n");
+}
+
+#[test]
+fn virtual_terminal() {
+    let (screen, terminal) = Screen::new(4, 3).virtual_terminal();
+    let buffer = screen.buffer();
+    let mut machine = Machine::builder().device(Box::new(screen), 0, 0x400).build();
+    draw(&mut machine, 0, "üb");
+    draw(&mut machine, 4, "cd");
+    // every cell is two columns wide with its character in the second one
+    assert_eq!(terminal.text(), " ü b\n c d\n");
+    assert_eq!(terminal.cursor(), (1, 4));
+
+    machine.write_word(0, SCREEN_HIDE_CURSOR).unwrap();
+    machine.write_word(0, SCREEN_SCROLL_UP).unwrap();
+    assert_eq!(terminal.text(), " c d\n\n");
+    assert_eq!(buffer.text(), "cd\n\n");
+    assert!(!terminal.cursor_visible());
+}
+
+#[test]
+fn outputs() {
+    let path = temp_path("screen");
+    let screen = Screen::new(2, 2).connect(&format!("file:{}", path.display())).unwrap();
+    let mut machine = Machine::builder().device(Box::new(screen), 0, 0x400).build();
+    machine.write_word(3, 'x' as Word).unwrap();
+    machine.write_word(0, SCREEN_CLEAR).unwrap();
+    machine.flush().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"\x1b[2;4Hx\0\0\0\x1b[2J");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(Screen::new(2, 2).connect("none").is_ok());
+    assert!(Screen::new(2, 2).connect("printer").is_err());
+    let config = |output: &str| {
+        MachineConfig::from_toml(&format!(
+            "[[device]]\ntype = \"screen\"\nstart = 0\nend = 0x400\nwidth = 2\nheight = 2\noutput = \"{}\"",
+            output
+        ))
+        .unwrap()
+        .builder()
+    };
+    assert!(config("none").is_ok());
+    assert!(config("printer").is_err());
+    assert_eq!(
+        MachineConfig::from_toml("[[device]]\ntype = \"screen\"\nstart = 0\nend = 0x200\nwidth = 16\nheight = 16")
+            .unwrap_err()
+            .to_string(),
+        "[CONFIG] screen at 0x0..0x200 is too small for its cells, their attributes and the TTY register"
+    );
+    assert_eq!(
+        MachineConfig::from_toml("[[device]]\ntype = \"screen\"\nstart = 0x20000\nend = 0x20800\nwidth = 256\nheight = 2")
+            .unwrap_err()
+            .to_string(),
+        "[CONFIG] screen at 0x20000..0x20800 is larger than the 255 columns and rows its cursor can address"
+    );
+    assert_eq!(
+        MachineConfig::from_toml("[[device]]\ntype = \"screen\"\nstart = 0\nend = 0x400\nwidth = 4\nheight = 0")
+            .unwrap_err()
+            .to_string(),
+        "[CONFIG] screen at 0x0..0x400 has no cells"
+    );
+}
+
+#[test]
+fn recording() {
+    let path = temp_path("screen.cast");
+    let screen = Screen::new(2, 2)
+        .headless()
+        .record(&path, CastClock::Instructions)
+        .unwrap();
+    let image = [
+        instr(0x10, &['a' as Word, 0x00]), // MOVR 'a', r1
+        instr(0x13, &[0x00, 0x0]),         // MOVRM r1, 0x0
+        instr(0x10, &['b' as Word, 0x00]), // MOVR 'b', r1
+        instr(0x13, &[0x00, 0x1]),         // MOVRM r1, 0x1
+        instr(0xFF, &[]),                  // HALT
+    ]
+    .concat();
+    let mut machine = Machine::builder()
+        .device(Box::new(screen), 0, 0x400)
+        .image(image)
+        .build();
+    machine.run().unwrap();
+    machine.flush().unwrap();
+
+    // a million instructions make up a second
+    let cast = Cast::load(&path).unwrap();
+    assert_eq!((cast.width, cast.height), (4, 2));
+    assert_eq!(
+        cast.events,
+        vec![
+            (0.000001, "\x1b[1;2H".to_string()),
+            (0.000001, "a".to_string()),
+            (0.000003, "\x1b[1;4H".to_string()),
+            (0.000003, "b".to_string()),
+        ]
+    );
+
+    let mut terminal = VirtualTerminal::new(4, 2);
+    cast.play(&mut terminal, 0.0).unwrap();
+    assert_eq!(terminal.text(), " a b\n");
+    std::fs::remove_file(&path).unwrap();
+
+    // pauses no Duration holds fail instead of panicking
+    let cast = Cast {
+        events: vec![(1e300, "a".to_string())],
+        ..cast
+    };
+    assert_eq!(cast.play(&mut terminal, 1.0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let cast = Cast {
+        events: vec![(1.0, "a".to_string())],
+        ..cast
+    };
+    assert_eq!(cast.play(&mut terminal, 1e-320).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn tty_mode() {
+    let (screen, terminal) = Screen::new(4, 3).virtual_terminal();
+    let tty = screen.tty_register();
+    let buffer = screen.buffer();
+    let mut machine = Machine::builder().device(Box::new(screen), 0, 0x400).build();
+    assert_eq!(tty, 24);
+
+    // a tab past the last column and a full row move on to the next row, the last row scrolls up
+    machine.load(tty, b"ab\tc\nline two\rL\x08X".to_vec()).unwrap();
+    assert_eq!(buffer.text(), "c\nline\nXtwo");
+    assert_eq!(terminal.text(), " c\n l i n e\n X t w o");
+    assert_eq!(machine.read_word(tty), Ok((1 << 8) | 2));
+
+    // words are characters or codes like at the cells, the cursor moves with them
+    machine.write_word(0, SCREEN_MOVE_CURSOR | 1).unwrap();
+    machine
+        .write_word(tty, Word::from_le_bytes([0xC3, 0xBC, 0, 0]))
+        .unwrap();
+    machine.write_word(tty, SCREEN_BOLD).unwrap();
+    machine.write_word(tty, '\n' as Word).unwrap();
+    machine.write_word(tty, '!' as Word).unwrap();
+    assert_eq!(buffer.text(), "c\nüine\n!two");
+    assert_eq!(terminal.text(), " c\n ü i n e\n ! t w o");
+    assert_eq!(buffer.cell(0, 2).unwrap().attributes, SCREEN_ATTRIBUTE_BOLD);
+}
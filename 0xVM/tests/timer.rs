@@ -0,0 +1,83 @@
+mod common;
+
+use vm::{
+    device::{TIMER_ENABLE, TIMER_EXPIRED, TIMER_INTERRUPT, TIMER_PERIODIC},
+    machine::Machine,
+    memory::Word,
+};
+
+use common::instr;
+
+const TIMER: Word = 0x110;
+
+#[test]
+fn one_shot_and_periodic() {
+    // endless loop, the timer is programmed from the outside
+    let mut machine = Machine::builder().timer(TIMER, 0).image(instr(0x01, &[0x408])).build();
+    machine.write_word(TIMER + 0x4, 3).unwrap();
+    machine.write_word(TIMER, TIMER_ENABLE).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0x8), Ok(3));
+
+    machine.run_for(2).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0x8), Ok(1));
+    assert_eq!(machine.read_word(TIMER + 0xC), Ok(0));
+
+    // a one-shot timer stops on expiry
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0xC), Ok(TIMER_EXPIRED));
+    assert_eq!(machine.read_word(TIMER), Ok(0));
+    machine.run_for(5).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0x8), Ok(0));
+
+    // writing the status register acknowledges the expiry
+    machine.write_word(TIMER + 0xC, TIMER_EXPIRED).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0xC), Ok(0));
+
+    // a periodic timer starts over with the reload value
+    machine.write_word(TIMER, TIMER_ENABLE | TIMER_PERIODIC).unwrap();
+    machine.run_for(4).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0xC), Ok(TIMER_EXPIRED));
+    assert_eq!(machine.read_word(TIMER + 0x8), Ok(2));
+    assert_eq!(machine.read_word(TIMER), Ok(TIMER_ENABLE | TIMER_PERIODIC));
+
+    // changing the mode of a running timer keeps its count
+    machine.run_for(1).unwrap();
+    machine.write_word(TIMER, TIMER_ENABLE).unwrap();
+    assert_eq!(machine.read_word(TIMER + 0x8), Ok(1));
+    machine.run_for(1).unwrap();
+    assert_eq!(machine.read_word(TIMER), Ok(0));
+}
+
+#[test]
+fn timer_interrupt() {
+    let control = TIMER_ENABLE | TIMER_PERIODIC | TIMER_INTERRUPT;
+    let image = [
+        instr(0x10, &[0x2000, 0x34]),    // MOVR 0x2000, ivt
+        instr(0x11, &[1, 0x104]),        // MOVM 1, 0x104
+        instr(0x11, &[10, TIMER + 0x4]), // MOVM 10, 0x114
+        instr(0x11, &[control, TIMER]),  // MOVM 7, 0x110
+        instr(0x08, &[]),                // EI
+        instr(0x01, &[0x42D]),           // loop: JMP loop
+    ]
+    .concat();
+    let handler = [
+        instr(0x14, &[0x1000, 0x00]), // MOVMR 0x1000, r1
+        instr(0x2A, &[0x00]),         // INC r1
+        instr(0x13, &[0x00, 0x1000]), // MOVRM r1, 0x1000
+        instr(0x07, &[]),             // IRET
+    ]
+    .concat();
+    let mut machine = Machine::builder()
+        .interrupt_controller(0x100)
+        .timer(TIMER, 0)
+        .image(image)
+        .build();
+    machine.load(0x3000, handler).unwrap();
+    machine.write_word(0x2000, 0x3000).unwrap();
+
+    // the 4th instruction starts the timer and is its first tick, it expires every 10 instructions
+    // and entering the handler and its four instructions take the five steps after an expiry
+    machine.run_for(3 + 10 * 3 + 5).unwrap();
+    assert_eq!(machine.read_word(0x1000), Ok(3));
+    assert_eq!(machine.pc(), 0x42D);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::net::UnixListener,
+};
+
+use vm::{
+    device::{Uart, UART_RX_CLOSED, UART_RX_READY, UART_TX_READY},
+    machine::Machine,
+    memory::Word,
+};
+
+use common::{instr, temp_path};
+
+const UART: Word = 0x200;
+
+/// Transmits every received byte until the input is closed
+fn echo(uart: Uart) -> Machine {
+    let image = [
+        instr(0x14, &[UART, 0x00]),                                  // loop: MOVMR 0x200, r1
+        instr(0x34, &[0x00, UART_RX_CLOSED | UART_TX_READY, 0x442]), // BREQRW r1, 6, done
+        instr(0x34, &[0x00, UART_TX_READY, 0x408]),                  // BREQRW r1, 4, loop
+        instr(0x14, &[UART + 0x4, 0x04]),                            // MOVMR 0x204, r2
+        instr(0x13, &[0x04, UART + 0x8]),                            // MOVRM r2, 0x208
+        instr(0x01, &[0x408]),                                       // JMP loop
+        instr(0xFF, &[]),                                            // done: HALT
+    ]
+    .concat();
+    let mut machine = Machine::builder()
+        .interrupt_controller(0x100)
+        .uart(UART, 2, uart)
+        .image(image)
+        .build();
+
+    machine.run().unwrap();
+    machine
+}
+
+#[test]
+fn registers_and_file() {
+    let path = temp_path("uart.txt");
+    let uart = Uart::file(&path).unwrap().rx_scripted(b"cat\n");
+    let mut machine = Machine::builder().uart(UART, 2, uart).build();
+    assert_eq!(machine.read_word(UART), Ok(UART_TX_READY));
+
+    machine.step().unwrap();
+    assert_eq!(machine.read_word(UART), Ok(UART_RX_READY | UART_TX_READY));
+    assert_eq!(machine.read_word(UART + 0x4), Ok(b'c' as Word));
+    machine.write_word(UART + 0x8, b'!' as Word).unwrap();
+    assert!(machine.read_word(UART + 0x8).is_err());
+    drop(machine);
+    assert_eq!(fs::read(&path).unwrap(), b"!");
+
+    echo(Uart::file(&path).unwrap().rx_scripted(b"cat\n"));
+    assert_eq!(fs::read(&path).unwrap(), b"cat\n");
+    fs::remove_file(&path).unwrap();
+
+    // an unconnected UART drops what it transmits
+    let mut machine = Machine::builder().uart(UART, 2, Uart::new()).build();
+    assert_eq!(machine.read_word(UART), Ok(UART_RX_CLOSED));
+    assert_eq!(machine.write_word(UART + 0x8, b'!' as Word), Ok(()));
+}
+
+#[test]
+fn unix_socket() {
+    let path = temp_path("uart.sock");
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let uart = Uart::unix_socket(&path).unwrap();
+    let (mut harness, _) = listener.accept().unwrap();
+    harness.write_all(b"ping\n").unwrap();
+    harness.shutdown(Shutdown::Write).unwrap();
+
+    drop(echo(uart));
+    let mut output = String::new();
+    harness.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "ping\n");
+    fs::remove_file(&path).unwrap();
+}